@@ -0,0 +1,123 @@
+use std::{env, fs, path::Path};
+
+// Makes simlo.h from the functions in src/ffi.rs, so the header can't drift from the library.
+// Only `#[no_mangle]` functions with their signature on one line are picked up. It is always
+// written to OUT_DIR, and only written into the source tree when asked for with
+// `SIMLO_HEADER=include/simlo.h cargo build`, so a build never changes the checkout.
+
+const FFI: &str = "src/ffi.rs";
+const HEADER: &str = "include/simlo.h";
+const HEADER_VARIABLE: &str = "SIMLO_HEADER";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", FFI);
+    println!("cargo:rerun-if-changed={}", HEADER);
+    println!("cargo:rerun-if-env-changed={}", HEADER_VARIABLE);
+    let source = fs::read_to_string(FFI).expect("src/ffi.rs should be readable");
+
+    let mut header = String::from(
+"/* Made by build.rs from src/ffi.rs, changes here will be written over. */
+#ifndef SIMLO_H
+#define SIMLO_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+
+typedef struct SimloCircuit SimloCircuit;
+");
+
+    let mut comments: Vec<&str> = Vec::new();
+    let mut exported = false;
+    for line in source.lines().map(|l| l.trim()) {
+        if let Some(comment) = line.strip_prefix("// ") {
+            comments.push(comment);
+            continue;
+        }
+        if line == "#[no_mangle]" {
+            exported = true;
+            continue;
+        }
+        if exported {
+            let declaration = declaration(line).unwrap_or_else(|| panic!("Can't turn \"{}\" into C", line));
+            header.push('\n');
+            for comment in comments.iter() {
+                header.push_str(&format!("/* {} */\n", comment));
+            }
+            header.push_str(&declaration);
+            header.push('\n');
+        }
+        exported = false;
+        comments.clear();
+    }
+
+    header.push_str(
+"
+#ifdef __cplusplus
+}
+#endif
+
+#endif
+");
+    let out_dir = env::var("OUT_DIR").expect("cargo should set OUT_DIR");
+    fs::write(Path::new(&out_dir).join("simlo.h"), &header).expect("OUT_DIR should be writable");
+
+    if let Ok(target) = env::var(HEADER_VARIABLE) {
+        if let Some(folder) = Path::new(&target).parent().filter(|f| !f.as_os_str().is_empty()) {
+            fs::create_dir_all(folder).expect("the folder for the header should be creatable");
+        }
+        if fs::read_to_string(&target).ok().as_deref() != Some(header.as_str()) {
+            fs::write(&target, &header).expect("the header should be writable");
+        }
+    } else if fs::read_to_string(HEADER).ok().as_deref().is_some_and(|h| h != header) {
+        println!("cargo:warning={} is out of date with {}, build with {}={} to update it", HEADER, FFI, HEADER_VARIABLE, HEADER);
+    }
+}
+
+// `pub unsafe extern "C" fn name(a: T, b: U) -> R {` becomes `R name(T a, U b);`
+fn declaration(line: &str) -> Option<String> {
+    let rest = line.split_once("extern \"C\" fn ")?.1;
+    let (name, rest) = rest.split_once('(')?;
+    let (arguments, rest) = rest.split_once(')')?;
+    let returns = match rest.trim().trim_end_matches('{').trim().strip_prefix("->") {
+        Some(t) => c_type(t.trim())?,
+        None => String::from("void"),
+    };
+    let mut parameters = Vec::new();
+    for argument in arguments.split(',').map(|a| a.trim()).filter(|a| !a.is_empty()) {
+        let (argument, t) = argument.split_once(':')?;
+        parameters.push(join(&c_type(t.trim())?, argument.trim()));
+    }
+    if parameters.is_empty() {
+        parameters.push(String::from("void"));
+    }
+    Some(format!("{}({});", join(&returns, name), parameters.join(", ")))
+}
+
+// Pointers are written as `char *name`
+fn join(t: &str, name: &str) -> String {
+    if t.ends_with('*') { format!("{}{}", t, name) } else { format!("{} {}", t, name) }
+}
+
+fn c_type(t: &str) -> Option<String> {
+    if let Some(t) = t.strip_prefix("*const ") {
+        return Some(format!("const {} *", c_type(t)?));
+    }
+    if let Some(t) = t.strip_prefix("*mut ") {
+        return Some(format!("{} *", c_type(t)?));
+    }
+    Some(String::from(match t {
+        "bool" => "bool",
+        "c_char" => "char",
+        "u32" => "uint32_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "usize" => "size_t",
+        "SimloCircuit" => "SimloCircuit",
+        _ => return None,
+    }))
+}
@@ -0,0 +1,70 @@
+/* Made by build.rs from src/ffi.rs, changes here will be written over. */
+#ifndef SIMLO_H
+#define SIMLO_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct SimloCircuit SimloCircuit;
+
+/* The message from the last call on this thread that failed, or NULL. It stays valid until the next call that fails. */
+const char *simlo_last_error(void);
+
+/* A new empty circuit. */
+SimloCircuit *simlo_circuit_new(void);
+
+/* Loads a circuit from a .lo file, or gives NULL if it couldn't. */
+SimloCircuit *simlo_circuit_load(const char *path);
+
+/* Saves the circuit to a .lo file. */
+bool simlo_circuit_save(SimloCircuit *circuit, const char *path);
+
+/* Frees a circuit from simlo_circuit_new or simlo_circuit_load. NULL is ignored. */
+void simlo_circuit_free(SimloCircuit *circuit);
+
+/* How many gates are in the circuit, not counting the ones inside ICs. */
+size_t simlo_gate_count(SimloCircuit *circuit);
+
+/* Finds the id of the first component with the label. */
+bool simlo_find_label(SimloCircuit *circuit, const char *label, uint32_t *id);
+
+/* Sets the state of a component, which is how inputs are switched. */
+bool simlo_set(SimloCircuit *circuit, uint32_t id, bool state);
+
+/* Sets the state of the first component with the label. */
+bool simlo_set_label(SimloCircuit *circuit, const char *label, bool state);
+
+/* The state of a component as 1 or 0, or -1 if it doesn't exist. */
+int32_t simlo_get(SimloCircuit *circuit, uint32_t id);
+
+/* The state of the first component with the label as 1 or 0, or -1 if there isn't one. */
+int32_t simlo_get_label(SimloCircuit *circuit, const char *label);
+
+/* Steps the circuit the given number of times. */
+void simlo_step(SimloCircuit *circuit, uint32_t count);
+
+/* Steps until nothing changes and gives the number of steps taken, or -1 if it was still changing after limit steps. */
+int64_t simlo_settle(SimloCircuit *circuit, uint32_t limit);
+
+/* How many Input gates the circuit has. */
+size_t simlo_input_count(SimloCircuit *circuit);
+
+/* The id and state of the nth Input, in the order they are in the circuit. id or state can be NULL. */
+bool simlo_input(SimloCircuit *circuit, size_t index, uint32_t *id, bool *state);
+
+/* How many Output gates the circuit has. */
+size_t simlo_output_count(SimloCircuit *circuit);
+
+/* The id and state of the nth Output, in the order they are in the circuit. id or state can be NULL. */
+bool simlo_output(SimloCircuit *circuit, size_t index, uint32_t *id, bool *state);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
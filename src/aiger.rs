@@ -0,0 +1,429 @@
+use std::{collections::HashMap, fs::File, io::Write};
+
+use crate::{invalid_data, layout, Circuit, Gate, GateType};
+
+// An and-inverter graph has nothing but two input ANDs, with any wire able to be inverted.
+// Literals are twice the variable, plus one when inverted, and literal 0 is always off.
+
+struct Graph {
+    inputs: usize,
+    latches: Vec<(u32, bool)>, // Next literal; Initial value
+    ands: Vec<(u32, u32)>,
+    known: HashMap<(u32, u32), u32>,
+}
+
+impl Graph {
+    fn and(&mut self, a: u32, b: u32) -> u32 {
+        let (a, b) = (a.max(b), a.min(b));
+        if b == 0 || a == b ^ 1 {
+            return 0;
+        }
+        if b == 1 || a == b {
+            return a;
+        }
+        if let Some(literal) = self.known.get(&(a, b)) {
+            return *literal;
+        }
+        self.ands.push((a, b));
+        let literal = 2 * (self.inputs + self.latches.len() + self.ands.len()) as u32;
+        self.known.insert((a, b), literal);
+        literal
+    }
+    fn all(&mut self, literals: &[u32]) -> u32 {
+        literals.iter().fold(1, |all, l| self.and(all, *l))
+    }
+    fn xor(&mut self, a: u32, b: u32) -> u32 {
+        let (one, other) = (self.and(a, b ^ 1), self.and(a ^ 1, b));
+        self.and(one ^ 1, other ^ 1) ^ 1
+    }
+}
+
+// ICs are flattened, as AIGER has no hierarchy, and gates that loops come back to become latches
+// like in BLIF. Files ending in `.aag` are written as ASCII and anything else as binary.
+pub fn export_aiger(circuit: &Circuit, fp: &str) -> std::io::Result<()> {
+    let flat = circuit.flattened();
+    let predecessors = layout::gate_predecessors(&flat);
+    let latched = layout::feedback(&predecessors);
+
+    let inputs: Vec<usize> = (0..flat.gates.len()).filter(|i| flat.gates[*i].gate_type == GateType::Input).collect();
+    let latches: Vec<usize> = (0..flat.gates.len()).filter(|i| latched[*i] && flat.gates[*i].gate_type != GateType::Input).collect();
+    let outputs: Vec<usize> = (0..flat.gates.len()).filter(|i| flat.gates[*i].gate_type == GateType::Output).collect();
+
+    let mut graph = Graph { inputs: inputs.len(), latches: Vec::new(), ands: Vec::new(), known: HashMap::new() };
+    let mut literals: Vec<Option<u32>> = vec![Option::None; flat.gates.len()];
+    for (n, gate) in inputs.iter().enumerate() {
+        literals[*gate] = Some(2 * (n as u32 + 1));
+    }
+    for (n, gate) in latches.iter().enumerate() {
+        literals[*gate] = Some(2 * (inputs.len() + n + 1) as u32);
+    }
+    graph.latches = latches.iter().map(|g| (0, flat.gates[*g].state)).collect();
+
+    // Gates are worked out after what they read from. Latches already have a literal for what they
+    // were, but still need working out for what they will be next.
+    let mut next: Vec<Option<u32>> = vec![Option::None; flat.gates.len()];
+    let mut done = vec![false; flat.gates.len()];
+    let mut on_stack = vec![false; flat.gates.len()];
+    for start in 0..flat.gates.len() {
+        let mut stack = vec![start];
+        on_stack[start] = true;
+        while let Some(&gate) = stack.last() {
+            if done[gate] {
+                on_stack[gate] = false;
+                stack.pop();
+                continue;
+            }
+            let waiting: Vec<usize> = predecessors[gate].iter().copied().filter(|p| literals[*p].is_none() && !on_stack[*p]).collect();
+            if !waiting.is_empty() {
+                for p in waiting.iter() {
+                    on_stack[*p] = true;
+                }
+                stack.extend(waiting);
+                continue;
+            }
+            let ins: Vec<u32> = predecessors[gate].iter().map(|p| literals[*p].unwrap_or(0)).collect();
+            let first = ins.first().copied().unwrap_or(0);
+            let literal = match flat.gates[gate].gate_type {
+                GateType::Input => literals[gate].unwrap(),
+                _ if ins.is_empty() => 0,
+                GateType::Buffer | GateType::Output => first,
+                GateType::Not => first ^ 1,
+                GateType::And => graph.all(&ins),
+                GateType::Nand => graph.all(&ins) ^ 1,
+                GateType::Or => graph.all(&ins.iter().map(|l| l ^ 1).collect::<Vec<u32>>()) ^ 1,
+                GateType::Nor => graph.all(&ins.iter().map(|l| l ^ 1).collect::<Vec<u32>>()),
+                // Only the first two inputs of an Xor are used when simulating
+                GateType::Xor => graph.xor(first, ins.get(1).copied().unwrap_or(0)),
+                GateType::Nxor => graph.xor(first, ins.get(1).copied().unwrap_or(0)) ^ 1,
+                // With two values a Tristate that lets go is off and a Wire is the Or of what drives it
+                GateType::Tristate => graph.all(&ins[..ins.len().min(2)]),
+                GateType::Wire => graph.all(&ins.iter().map(|l| l ^ 1).collect::<Vec<u32>>()) ^ 1,
+            };
+            next[gate] = Some(literal);
+            if literals[gate].is_none() {
+                literals[gate] = Some(literal);
+            }
+            done[gate] = true;
+            on_stack[gate] = false;
+            stack.pop();
+        }
+    }
+    for (n, gate) in latches.iter().enumerate() {
+        graph.latches[n].0 = next[*gate].unwrap_or(0);
+    }
+
+    let binary = !fp.ends_with(".aag");
+    let (i, l, a) = (graph.inputs, graph.latches.len(), graph.ands.len());
+    let mut buf: Vec<u8> = Vec::new();
+    writeln!(buf, "{} {} {} {} {} {}", if binary {"aig"} else {"aag"}, i + l + a, i, l, outputs.len(), a)?;
+    if !binary {
+        for n in 0..i {
+            writeln!(buf, "{}", 2 * (n + 1))?;
+        }
+    }
+    for (n, (next, initial)) in graph.latches.iter().enumerate() {
+        if !binary {
+            write!(buf, "{} ", 2 * (i + n + 1))?;
+        }
+        writeln!(buf, "{}{}", next, if *initial {" 1"} else {""})?;
+    }
+    for gate in outputs.iter() {
+        writeln!(buf, "{}", literals[*gate].unwrap_or(0))?;
+    }
+    for (n, (a, b)) in graph.ands.iter().enumerate() {
+        let lhs = 2 * (i + l + n + 1) as u32;
+        if binary {
+            // Each AND is the two gaps down to its inputs, 7 bits to a byte
+            for mut delta in [lhs - a, a - b] {
+                while delta >= 0x80 {
+                    buf.push((delta & 0x7f) as u8 | 0x80);
+                    delta >>= 7;
+                }
+                buf.push(delta as u8);
+            }
+        } else {
+            writeln!(buf, "{} {} {}", lhs, a, b)?;
+        }
+    }
+
+    for (kind, gates) in [('i', &inputs), ('l', &latches), ('o', &outputs)] {
+        for (n, gate) in gates.iter().enumerate() {
+            if let Some(label) = &flat.gates[*gate].label {
+                writeln!(buf, "{}{} {}", kind, n, label.replace('\n', " "))?;
+            }
+        }
+    }
+    if let Some(name) = &circuit.name {
+        writeln!(buf, "c\n{}", name)?;
+    }
+
+    let mut file = File::create(fp)?;
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+// Reads an ASCII or binary AIGER file as one circuit in the catalogue. The ANDs are turned back
+// into simlo gates, so an AND of two inverted wires is a NOR, inverting one is an OR, and the
+// three ANDs an XOR is made of are an XOR again. Latches are Buffers starting at their initial value.
+pub fn import_aiger(fp: &str, id: &mut u32, catalogue: &mut Vec<Circuit>) -> std::io::Result<Vec<u32>> {
+    let bytes = std::fs::read(fp)?;
+    let mut position = 0;
+    let mut line = || -> String {
+        let end = bytes[position..].iter().position(|b| *b == b'\n').map(|e| position + e).unwrap_or(bytes.len());
+        let text = String::from_utf8_lossy(&bytes[position..end]).trim().to_string();
+        position = (end + 1).min(bytes.len());
+        text
+    };
+    let numbers = |text: &str| -> std::io::Result<Vec<u32>> {
+        text.split_whitespace().map(|n| n.parse::<u32>().map_err(|_| invalid_data(format!("\"{}\" should be numbers", text)))).collect()
+    };
+
+    let header = line();
+    let mut words = header.split_whitespace();
+    let binary = match words.next() {
+        Some("aag") => false,
+        Some("aig") => true,
+        _ => return Err(invalid_data(String::from("This isn't an AIGER file"))),
+    };
+    let counts = numbers(&words.collect::<Vec<&str>>().join(" "))?;
+    if counts.len() < 5 {
+        return Err(invalid_data(String::from("The header needs M I L O A")));
+    }
+    if counts[5..].iter().any(|c| *c != 0) {
+        return Err(invalid_data(String::from("Bad states, constraints, justice and fairness properties aren't supported")));
+    }
+    let (input_count, latch_count, output_count, and_count) = (counts[1] as usize, counts[2] as usize, counts[3] as usize, counts[4] as usize);
+
+    let mut inputs = Vec::new();
+    for n in 0..input_count {
+        inputs.push(if binary {2 * (n as u32 + 1)} else {*numbers(&line())?.first().unwrap_or(&0)});
+    }
+    let mut latches = Vec::new(); // Literal; Next; Initial value
+    for n in 0..latch_count {
+        let values = numbers(&line())?;
+        let values = if binary {[vec![2 * (input_count + n + 1) as u32], values].concat()} else {values};
+        if values.len() < 2 {
+            return Err(invalid_data(format!("Latch {} needs a next value", n)));
+        }
+        // An initial value the same as the latch itself means it isn't known, which starts off
+        latches.push((values[0], values[1], values.get(2) == Some(&1)));
+    }
+    let mut outputs = Vec::new();
+    for _ in 0..output_count {
+        outputs.push(*numbers(&line())?.first().unwrap_or(&0));
+    }
+
+    let mut ands: HashMap<u32, (u32, u32)> = HashMap::new(); // Variable; Inputs
+    for n in 0..and_count {
+        if binary {
+            let lhs = 2 * (input_count + latch_count + n + 1) as u32;
+            let mut delta = || -> std::io::Result<u32> {
+                let (mut value, mut shift) = (0u32, 0);
+                loop {
+                    let Some(byte) = bytes.get(position) else {
+                        return Err(invalid_data(String::from("The file ends in the middle of the ANDs")));
+                    };
+                    position += 1;
+                    value |= ((byte & 0x7f) as u32) << shift;
+                    shift += 7;
+                    if byte & 0x80 == 0 {
+                        return Ok(value);
+                    }
+                }
+            };
+            let a = lhs.checked_sub(delta()?);
+            let b = a.and_then(|a| delta().ok().and_then(|d| a.checked_sub(d)));
+            let (Some(a), Some(b)) = (a, b) else {
+                return Err(invalid_data(format!("AND {} is broken", n)));
+            };
+            ands.insert(lhs / 2, (a, b));
+        } else {
+            let end = bytes[position..].iter().position(|b| *b == b'\n').map(|e| position + e).unwrap_or(bytes.len());
+            let values = numbers(&String::from_utf8_lossy(&bytes[position..end]))?;
+            position = (end + 1).min(bytes.len());
+            if values.len() != 3 {
+                return Err(invalid_data(format!("AND {} needs 3 literals", n)));
+            }
+            ands.insert(values[0] / 2, (values[1], values[2]));
+        }
+    }
+
+    // Names come after the ANDs, until the comments
+    let mut names: HashMap<(char, usize), String> = HashMap::new();
+    let mut comment = Option::None;
+    for text in String::from_utf8_lossy(&bytes[position..]).lines() {
+        if text == "c" {
+            comment = Some(String::new());
+            continue;
+        }
+        if let Some(comment) = comment.as_mut() {
+            if comment.is_empty() {
+                comment.push_str(text.trim());
+            }
+            continue;
+        }
+        let Some((kind, rest)) = text.split_once(|c: char| c.is_ascii_digit()).map(|(k, _)| (k, &text[k.len()..])) else { continue };
+        let Some((n, name)) = rest.split_once(' ') else { continue };
+        if let (Some(kind), Ok(n)) = (kind.chars().next(), n.parse::<usize>()) {
+            names.insert((kind, n), name.to_string());
+        }
+    }
+
+    let mut builder = Builder { gates: Vec::new(), literals: HashMap::new(), waiting: Vec::new(), ands };
+    for (n, literal) in inputs.iter().enumerate() {
+        builder.literals.insert(*literal, builder.gates.len() as u32);
+        builder.gates.push((GateType::Input, Vec::new(), names.get(&('i', n)).cloned(), false));
+    }
+    for (n, (literal, _, initial)) in latches.iter().enumerate() {
+        builder.literals.insert(*literal, builder.gates.len() as u32);
+        builder.gates.push((GateType::Buffer, Vec::new(), names.get(&('l', n)).cloned(), *initial));
+    }
+    for (literal, next, _) in latches.iter() {
+        let gate = builder.literals[literal];
+        let next = builder.request(*next);
+        builder.gates[gate as usize].1 = vec![next];
+    }
+    let sources: Vec<u32> = outputs.iter().map(|o| builder.request(*o)).collect();
+    builder.finish()?;
+    // A latch that starts on has its next value start on too, like BLIF latches
+    for (literal, _, _) in latches.iter().filter(|l| l.2) {
+        let next = builder.gates[builder.literals[literal] as usize].1[0] as usize;
+        if builder.gates[next].0 != GateType::Input {
+            builder.gates[next].3 = true;
+        }
+    }
+    for (n, source) in sources.into_iter().enumerate() {
+        builder.gates.push((GateType::Output, vec![source], names.get(&('o', n)).cloned(), false));
+    }
+
+    *id += 1;
+    let mut circuit = Circuit::new(*id);
+    circuit.name = comment.filter(|c| !c.is_empty()).or(std::path::Path::new(fp).file_stem().map(|s| s.to_string_lossy().to_string()));
+    for (gate_type, inputs, label, state) in builder.gates {
+        // Ids are the same as indexes here so they are also where to find each input
+        let mut gate = Gate::new(gate_type, circuit.id_counter, inputs.into_iter().map(|i| (i as usize, i)).collect(), label);
+        gate.state = state;
+        circuit.gates.push(gate);
+        circuit.id_counter += 1;
+    }
+    catalogue.push(circuit);
+    Ok(vec![*id])
+}
+
+// Gates are made for literals as they are asked for, and what they read from is filled in after,
+// so deep graphs don't need deep recursion
+struct Builder {
+    gates: Vec<(GateType, Vec<u32>, Option<String>, bool)>,
+    literals: HashMap<u32, u32>, // Literal; Gate
+    waiting: Vec<u32>,
+    ands: HashMap<u32, (u32, u32)>,
+}
+
+impl Builder {
+    fn request(&mut self, literal: u32) -> u32 {
+        if let Some(gate) = self.literals.get(&literal) {
+            return *gate;
+        }
+        let gate = self.gates.len() as u32;
+        self.gates.push((GateType::Buffer, Vec::new(), Option::None, false));
+        self.literals.insert(literal, gate);
+        self.waiting.push(literal);
+        gate
+    }
+    fn finish(&mut self) -> std::io::Result<()> {
+        while let Some(literal) = self.waiting.pop() {
+            let gate = self.literals[&literal] as usize;
+            let inverted = literal & 1 == 1;
+            let (gate_type, inputs) = if literal == 0 {
+                // A Buffer with nothing in is always off
+                (GateType::Buffer, Vec::new())
+            } else if inverted && !self.ands.contains_key(&(literal / 2)) {
+                (GateType::Not, vec![self.request(literal ^ 1)])
+            } else if let Some((a, b)) = self.ands.get(&(literal / 2)).copied() {
+                let (gate_type, a, b) = match (self.xor(a, b), a & 1 == 1 && b & 1 == 1) {
+                    (Some((x, y)), _) => (if inverted {GateType::Nxor} else {GateType::Xor}, x, y),
+                    (None, true) => (if inverted {GateType::Or} else {GateType::Nor}, a ^ 1, b ^ 1),
+                    (None, false) => (if inverted {GateType::Nand} else {GateType::And}, a, b),
+                };
+                (gate_type, vec![self.request(a), self.request(b)])
+            } else {
+                return Err(invalid_data(format!("Literal {} isn't an input, latch or AND", literal)));
+            };
+            self.gates[gate].0 = gate_type;
+            self.gates[gate].1 = inputs;
+        }
+        Ok(())
+    }
+    // An AND of !(x & y) and !(!x & !y) is x XOR y
+    fn xor(&self, a: u32, b: u32) -> Option<(u32, u32)> {
+        if a & 1 == 0 || b & 1 == 0 {
+            return Option::None;
+        }
+        let (p, q) = (self.ands.get(&(a / 2))?, self.ands.get(&(b / 2))?);
+        if (q.0 == p.0 ^ 1 && q.1 == p.1 ^ 1) || (q.0 == p.1 ^ 1 && q.1 == p.0 ^ 1) {
+            Some((p.0, p.1))
+        } else {
+            Option::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{stepped_outputs, temp_path, wide};
+
+    fn imported(source: &[u8]) -> std::io::Result<Vec<Circuit>> {
+        let fp = temp_path("malformed.aag");
+        std::fs::write(&fp, source)?;
+        let mut catalogue = Vec::new();
+        let result = import_aiger(&fp, &mut 100, &mut catalogue);
+        std::fs::remove_file(&fp)?;
+        result.map(|_| catalogue)
+    }
+
+    #[test]
+    fn export_then_import_steps_the_same() {
+        let mut circuit = wide();
+        circuit.name = Some(String::from("wide"));
+        circuit.add_component(GateType::Nor, vec![0, 28], Option::None); // 27
+        circuit.add_component(GateType::Nor, vec![1, 27], Option::None); // 28
+        circuit.add_component(GateType::Output, vec![27], Option::None);
+        // Starting set, or stepping with both inputs off never settles
+        circuit.gates.iter_mut().find(|g| g.id == 27).unwrap().state = true;
+
+        // Binary and ASCII
+        for name in ["round_trip.aig", "round_trip.aag"] {
+            let fp = temp_path(name);
+            export_aiger(&circuit, &fp).unwrap();
+            let mut catalogue = Vec::new();
+            import_aiger(&fp, &mut 100, &mut catalogue).unwrap();
+            std::fs::remove_file(&fp).unwrap();
+
+            assert_eq!(catalogue.len(), 1);
+            assert_eq!(catalogue[0].name, Some(String::from("wide")));
+            let labels: Vec<Option<String>> = catalogue[0].gates.iter().filter(|g| g.gate_type == GateType::Input).map(|g| g.label.clone()).collect();
+            assert_eq!(labels, (0..8).map(|i| Some(format!("I{}", i))).collect::<Vec<_>>());
+            for vector in 0..256 {
+                assert_eq!(stepped_outputs(&catalogue[0], vector), stepped_outputs(&circuit, vector), "{} vector {:08b}", name, vector);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_aiger_is_an_error() {
+        for (source, error) in [
+            (&b"c\n"[..], "This isn't an AIGER file"),
+            (b"aag 1 1 0\n", "The header needs M I L O A"),
+            (b"aag 1 1 0 0 0 1\n", "Bad states, constraints, justice and fairness properties aren't supported"),
+            (b"aag 1 x 0 0 0\n", "\"1 x 0 0 0\" should be numbers"),
+            (b"aag 2 1 1 0 0\n2\n4\n", "Latch 0 needs a next value"),
+            (b"aag 2 1 0 1 1\n2\n4\n4 2\n", "AND 0 needs 3 literals"),
+            (b"aag 1 1 0 1 0\n2\n6\n", "Literal 6 isn't an input, latch or AND"),
+            (b"aig 2 1 0 1 1\n4\n", "The file ends in the middle of the ANDs"),
+        ] {
+            let result = imported(source);
+            assert_eq!(result.err().map(|e| e.to_string()), Some(String::from(error)), "{:?}", String::from_utf8_lossy(source));
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::io::Write;
+
+use crate::{fault, invalid_data, layout, logic4::{self, Level}, packed::{lane_bits, Fault, Packed, LANES}, vectors::{self, Vector}, Circuit, GateType};
+
+// Makes test vectors for the stuck-at faults of a circuit without loops, using PODEM. For each
+// fault it only ever decides Inputs: it picks something that needs to be 0 or 1, either to set
+// off the fault or to let its effect through the next gate, follows that back to an Input that
+// isn't set yet, sets it, and works out the rest in 0/1/X for the circuit with and without the
+// fault. When that can no longer work it tries the last Input the other way. Trying every way
+// without finding a vector means nothing can tell the fault apart, so its logic is redundant.
+// Each new vector is run against the faults left so they are dropped as soon as anything finds
+// them, and at the end vectors that only find what later ones do are dropped too.
+
+// Times one fault can go back on a choice before it is given up on
+const MAX_BACKTRACKS: usize = 10_000;
+
+enum Outcome {
+    Found(Vec<Level>),
+    Redundant,
+    GaveUp,
+}
+
+struct Podem<'a> {
+    gates: &'a [(GateType, Vec<usize>)],
+    order: Vec<usize>,
+    inputs: Vec<Option<usize>>, // Where each gate is in the Inputs, if it is one
+    outputs: Vec<usize>,
+}
+
+// How many of its inputs a gate reads, as the packed simulator does
+fn used(gate_type: &GateType, count: usize) -> usize {
+    match gate_type {
+        GateType::Output | GateType::Buffer | GateType::Not => count.min(1),
+        GateType::Tristate | GateType::Xor | GateType::Nxor => count.min(2),
+        _ => count,
+    }
+}
+
+// The two valued meaning of each gate with unknowns, so a Tristate is an And and a Wire an Or
+fn eval(gate_type: &GateType, inputs: &[Level]) -> Level {
+    if inputs.is_empty() {
+        return Level::Zero;
+    }
+    let gate_type = match gate_type {
+        GateType::Tristate => GateType::And,
+        GateType::Wire => GateType::Or,
+        other => other.clone(),
+    };
+    logic4::resolve(&gate_type, Level::X, inputs, Option::None)
+}
+
+fn known(level: Level) -> bool {
+    level == Level::Zero || level == Level::One
+}
+
+impl Podem<'_> {
+    // What a gate sees on one of its pins with and without the fault
+    fn pin(&self, good: &[Level], bad: &[Level], fault: &Fault, gate: usize, pin: usize) -> (Level, Level) {
+        let from = self.gates[gate].1[pin];
+        match fault.pin {
+            Some(p) if fault.gate == gate && p == pin => (good[from], Level::from_bool(fault.stuck)),
+            _ => (good[from], bad[from]),
+        }
+    }
+    fn pins(&self, good: &[Level], bad: &[Level], fault: &Fault, gate: usize) -> Vec<(Level, Level)> {
+        let (gate_type, inputs) = &self.gates[gate];
+        (0..used(gate_type, inputs.len())).map(|p| self.pin(good, bad, fault, gate, p)).collect()
+    }
+
+    // Every gate with and without the fault, from the Inputs set so far
+    fn imply(&self, assigned: &[Level], fault: &Fault) -> (Vec<Level>, Vec<Level>) {
+        let mut good = vec![Level::X; self.gates.len()];
+        let mut bad = vec![Level::X; self.gates.len()];
+        for gate in self.order.iter().copied() {
+            if let Some(input) = self.inputs[gate] {
+                good[gate] = assigned[input];
+                bad[gate] = assigned[input];
+            } else {
+                let (goods, bads): (Vec<Level>, Vec<Level>) = self.pins(&good, &bad, fault, gate).into_iter().unzip();
+                good[gate] = eval(&self.gates[gate].0, &goods);
+                bad[gate] = eval(&self.gates[gate].0, &bads);
+            }
+            if fault.gate == gate && fault.pin.is_none() {
+                bad[gate] = Level::from_bool(fault.stuck);
+            }
+        }
+        (good, bad)
+    }
+
+    // A gate to set to a state next, or None when this way can't find the fault
+    fn objective(&self, good: &[Level], bad: &[Level], fault: &Fault) -> Option<(usize, bool)> {
+        let site = match fault.pin {
+            Some(pin) => self.gates[fault.gate].1[pin],
+            Option::None => fault.gate,
+        };
+        if good[site] == Level::X {
+            return Some((site, !fault.stuck));
+        }
+        if good[site] == Level::from_bool(fault.stuck) {
+            return Option::None;
+        }
+        // The fault is set off, so carry it on through the deepest gate it has got to the input of
+        // and whose output isn't known yet
+        let differs = |(g, b): &(Level, Level)| known(*g) && known(*b) && g != b;
+        let frontier = self.order.iter().rev().copied().find(|gate| {
+            self.inputs[*gate].is_none()
+                && (good[*gate] == Level::X || bad[*gate] == Level::X)
+                && self.pins(good, bad, fault, *gate).iter().any(differs)
+        })?;
+        let pins = self.pins(good, bad, fault, frontier);
+        let pin = pins.iter().position(|p| p.0 == Level::X).or(pins.iter().position(|p| p.1 == Level::X))?;
+        // Whatever lets the other inputs through
+        let through = !matches!(self.gates[frontier].0, GateType::Or | GateType::Nor | GateType::Wire | GateType::Xor | GateType::Nxor);
+        Some((self.gates[frontier].1[pin], through))
+    }
+
+    // Follows an objective back through unknown gates to an Input that isn't set yet
+    fn backtrace(&self, good: &[Level], bad: &[Level], fault: &Fault, mut gate: usize, mut state: bool) -> (usize, bool) {
+        while self.inputs[gate].is_none() {
+            let gate_type = &self.gates[gate].0;
+            let pins = self.pins(good, bad, fault, gate);
+            if matches!(gate_type, GateType::Not | GateType::Nand | GateType::Nor | GateType::Nxor) {
+                state = !state;
+            }
+            if matches!(gate_type, GateType::Xor | GateType::Nxor) && pins.iter().any(|p| p.0 == Level::One) {
+                state = !state;
+            }
+            let pin = pins.iter().position(|p| p.0 == Level::X).or(pins.iter().position(|p| p.1 == Level::X)).unwrap_or(0);
+            gate = self.gates[gate].1[pin];
+        }
+        (gate, state)
+    }
+
+    fn detected(&self, good: &[Level], bad: &[Level]) -> bool {
+        self.outputs.iter().any(|o| known(good[*o]) && known(bad[*o]) && good[*o] != bad[*o])
+    }
+
+    fn search(&self, fault: &Fault, input_count: usize) -> Outcome {
+        let mut assigned = vec![Level::X; input_count];
+        let mut decisions: Vec<(usize, bool)> = Vec::new(); // Input; Whether it has been tried both ways
+        let mut backtracks = 0;
+        loop {
+            let (good, bad) = self.imply(&assigned, fault);
+            if self.detected(&good, &bad) {
+                return Outcome::Found(assigned);
+            }
+            if let Some((gate, state)) = self.objective(&good, &bad, fault) {
+                let (input, state) = self.backtrace(&good, &bad, fault, gate, state);
+                let input = self.inputs[input].unwrap_or(0);
+                if assigned[input] == Level::X {
+                    assigned[input] = Level::from_bool(state);
+                    decisions.push((input, false));
+                    continue;
+                }
+            }
+            backtracks += 1;
+            if backtracks > MAX_BACKTRACKS {
+                return Outcome::GaveUp;
+            }
+            loop {
+                match decisions.pop() {
+                    Some((input, true)) => assigned[input] = Level::X,
+                    Some((input, false)) => {
+                        assigned[input] = Level::from_bool(assigned[input] != Level::One);
+                        decisions.push((input, true));
+                        break;
+                    }
+                    Option::None => return Outcome::Redundant,
+                }
+            }
+        }
+    }
+}
+
+pub fn generate(circuit: &Circuit, fp: &str, out: &mut impl Write) -> std::io::Result<()> {
+    let mut packed = Packed::new(circuit);
+    let gates = packed.gates().to_vec();
+    let predecessors: Vec<Vec<usize>> = gates.iter().map(|(gate_type, inputs)| {
+        if *gate_type == GateType::Input { Vec::new() } else { inputs.clone() }
+    }).collect();
+    if layout::feedback(&predecessors).contains(&true) {
+        return Err(invalid_data(String::from("The circuit has loops, so it has states vectors alone can't test")));
+    }
+    if packed.output_count() == 0 {
+        return Err(invalid_data(String::from("The circuit has no Outputs to see faults on")));
+    }
+    let depths = layout::longest_paths(&predecessors);
+    let mut order: Vec<usize> = (0..gates.len()).collect();
+    order.sort_by_key(|g| depths[*g]);
+    let mut input_count = 0;
+    let inputs = gates.iter().map(|g| (g.0 == GateType::Input).then(|| { input_count += 1; input_count - 1 })).collect();
+    let outputs = (0..gates.len()).filter(|g| gates[*g].0 == GateType::Output).collect();
+    let podem = Podem { gates: &gates, order, inputs, outputs };
+
+    let faults = fault::faults(circuit, &packed);
+    let mut found = vec![false; faults.len()];
+    let (mut redundant, mut gave_up) = (Vec::new(), Vec::new());
+    let mut made: Vec<Vec<bool>> = Vec::new();
+    for f in 0..faults.len() {
+        if found[f] {
+            continue;
+        }
+        match podem.search(&faults[f], input_count) {
+            // Inputs it didn't need are left at 0
+            Outcome::Found(assigned) => {
+                let vector: Vec<bool> = assigned.iter().map(|l| *l == Level::One).collect();
+                let left: Vec<usize> = (0..faults.len()).filter(|i| !found[*i]).collect();
+                let left_faults: Vec<Fault> = left.iter().map(|i| faults[*i]).collect();
+                for (i, hit) in left.iter().zip(fault::detected(&mut packed, &left_faults, &[&vector])) {
+                    found[*i] |= hit;
+                }
+                made.push(vector);
+            }
+            Outcome::Redundant => redundant.push(f),
+            Outcome::GaveUp => gave_up.push(f),
+        }
+    }
+
+    // Going back from the last, keep only vectors that find something the ones kept so far don't
+    let mut covered = vec![false; faults.len()];
+    let mut kept: Vec<Vec<bool>> = Vec::new();
+    for vector in made.into_iter().rev() {
+        let left: Vec<usize> = (0..faults.len()).filter(|i| found[*i] && !covered[*i]).collect();
+        let left_faults: Vec<Fault> = left.iter().map(|i| faults[*i]).collect();
+        let hits = fault::detected(&mut packed, &left_faults, &[&vector]);
+        if hits.contains(&true) {
+            for (i, hit) in left.iter().zip(hits) {
+                covered[*i] |= hit;
+            }
+            kept.push(vector);
+        }
+    }
+    kept.reverse();
+
+    // Save them with the Outputs the circuit gives, so FAULTSIM can check them again
+    let batches: Vec<Vec<&[bool]>> = kept.chunks(LANES as usize).map(|c| c.iter().map(|v| v.as_slice()).collect()).collect();
+    let responses = fault::run(&mut packed, &batches);
+    let saved: Vec<Vector> = kept.iter().enumerate().map(|(i, vector)| Vector {
+        inputs: vector.clone(),
+        outputs: Some(lane_bits(&responses[i / LANES as usize], (i % LANES as usize) as u64).chars().map(|c| c == '1').collect()),
+    }).collect();
+    vectors::write_vectors(fp, &packed.input_names(), &packed.output_names(), &saved)?;
+
+    let flat = circuit.flattened();
+    let count = found.iter().filter(|f| **f).count();
+    let detectable = faults.len() - redundant.len();
+    writeln!(out, "Saved {} vector(s) to {}", kept.len(), fp)?;
+    writeln!(out, "Fault coverage: {} of {} stuck-at fault(s) ({:.1}%)", count, faults.len(), 100.0 * count as f64 / faults.len().max(1) as f64)?;
+    writeln!(out, "Of the faults that can be detected: {} of {} ({:.1}%)", count, detectable, 100.0 * count as f64 / detectable.max(1) as f64)?;
+    if !redundant.is_empty() {
+        writeln!(out, "Redundant, nothing at the Outputs can tell these apart:")?;
+        for f in redundant.iter() {
+            writeln!(out, "  {}", fault::describe(circuit, &flat, &faults[*f]))?;
+        }
+    }
+    if !gave_up.is_empty() {
+        writeln!(out, "Gave up after {} backtracks on:", MAX_BACKTRACKS)?;
+        for f in gave_up.iter() {
+            writeln!(out, "  {}", fault::describe(circuit, &flat, &faults[*f]))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::{half_adder, run, temp_path}, Circuit};
+
+    fn atpg(circuit: &mut Circuit, name: &str) -> String {
+        let fp = temp_path(name);
+        let text = run(circuit, &mut Vec::new(), &[&format!("ATPG {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        text
+    }
+
+    #[test]
+    fn faultsim_reads_back_what_atpg_saves() {
+        let fp = temp_path("atpg_replay.txt");
+        let mut circuit = Circuit::new(1);
+        let text = run(&mut circuit, &mut Vec::new(), &["INPUT", "INPUT", "AND 0 1", "OUTPUT 2", &format!("ATPG {}", fp), &format!("FAULTSIM {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        assert!(text.contains("Fault coverage: 14 of 14 stuck-at fault(s) (100.0%)"), "{}", text);
+        assert!(text.contains("Ran 3 vector(s) against 14 stuck-at fault(s)\nFault coverage: 14 of 14 (100.0%)"), "{}", text);
+    }
+
+    #[test]
+    fn every_fault_of_a_half_adder_is_found() {
+        let text = atpg(&mut half_adder(1), "atpg_half_adder.txt");
+        assert!(text.contains("Fault coverage: 24 of 24 stuck-at fault(s) (100.0%)"), "{}", text);
+        assert!(!text.contains("Redundant") && !text.contains("Gave up"), "{}", text);
+    }
+
+    // A or (A and B) is just A, so the And stuck at 0 changes nothing
+    #[test]
+    fn the_redundant_and_of_an_absorbed_term_is_reported() {
+        let mut circuit = Circuit::new(1);
+        run(&mut circuit, &mut Vec::new(), &["INPUT", "INPUT", "AND 0 1", "OR 0 2", "OUTPUT 3"]);
+        let text = atpg(&mut circuit, "atpg_redundant.txt");
+        assert!(text.contains("Of the faults that can be detected: 13 of 13 (100.0%)"), "{}", text);
+        let redundant = text.split("Redundant, nothing at the Outputs can tell these apart:\n").nth(1).unwrap_or_else(|| panic!("{}", text));
+        assert!(redundant.contains("  Output of And 2 stuck at 0\n"), "{}", text);
+        assert!(!redundant.contains("  Output of And 2 stuck at 1\n"), "{}", text);
+        assert_eq!(redundant.lines().count(), 7, "{}", text);
+    }
+}
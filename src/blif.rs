@@ -0,0 +1,370 @@
+use std::{fs::File, io::Write};
+
+use crate::{invalid_data, layout, verilog::{self, Expr, Instance, Module}, Circuit, GateType};
+
+// Each gate is written as a `.names` table and each IC as a `.subckt` of a model written after
+// this one. simlo gates take a step to change, so loops like latches work, but BLIF doesn't allow
+// loops outside of `.latch`. Any gate a loop comes back to is written as a `.latch` holding what
+// it was the step before, which gives the same result once the circuit has settled.
+pub fn export_blif(circuit: &Circuit, fp: &str) -> std::io::Result<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    write_model(circuit, &mut Vec::new(), &mut Vec::new(), &mut buf)?;
+    let mut file = File::create(fp)?;
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+fn write_model(circuit: &Circuit, names: &mut Vec<(u32, String)>, written: &mut Vec<u32>, buf: &mut Vec<u8>) -> std::io::Result<()> {
+    written.push(circuit.id);
+    let nets = verilog::net_names(circuit);
+    let net = |id: u32| nets.iter().find(|n| n.0 == id).map(|n| n.1.clone()).unwrap_or_default();
+    let ports = |gate_type: GateType| -> Vec<String> {
+        circuit.gates.iter().filter(|g| g.gate_type == gate_type).map(|g| net(g.id)).collect()
+    };
+
+    writeln!(buf, ".model {}", verilog::module_name(circuit, names))?;
+    for (keyword, gate_type) in [(".inputs", GateType::Input), (".outputs", GateType::Output)] {
+        let ports = ports(gate_type);
+        if !ports.is_empty() {
+            writeln!(buf, "{} {}", keyword, ports.join(" "))?;
+        }
+    }
+
+    let feedback = layout::feedback(&layout::gate_predecessors(circuit));
+    let driven_by_ics: Vec<u32> = circuit.intergrated_circuits.iter().flat_map(|ic| ic.outputs.iter().map(|o| o[0].1)).collect();
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        if gate.gate_type == GateType::Input {
+            continue;
+        }
+        let output = net(gate.id);
+        if feedback[index] {
+            writeln!(buf, ".latch {}_next {} {}", output, output, if gate.state {1} else {0})?;
+        }
+        let target = if feedback[index] {format!("{}_next", output)} else {output.clone()};
+        if driven_by_ics.contains(&gate.id) {
+            // The IC drives the net itself, so a latched one needs passing through
+            if feedback[index] {
+                writeln!(buf, ".names {}_ic {}\n1 1", output, target)?;
+            }
+            continue;
+        }
+
+        let mut sources: Vec<String> = gate.inputs.iter().map(|i| net(i.1)).filter(|n| !n.is_empty()).collect();
+        if sources.is_empty() {
+            writeln!(buf, ".names {}", target)?;
+            continue;
+        }
+        match gate.gate_type {
+            GateType::Buffer | GateType::Output | GateType::Not => sources.truncate(1),
+            // Only the first two inputs of an Xor are used when simulating
+            GateType::Xor | GateType::Nxor | GateType::Tristate => sources.truncate(2),
+            _ => {}
+        }
+        let count = sources.len();
+        let rows: Vec<String> = match gate.gate_type {
+            GateType::Input => continue,
+            GateType::Buffer | GateType::Output => vec![String::from("1 1")],
+            GateType::Not => vec![String::from("0 1")],
+            // BLIF has no way to let go of a net, so a Tristate is off when it isn't enabled
+            GateType::And | GateType::Tristate => vec![format!("{} 1", "1".repeat(count))],
+            GateType::Nor => vec![format!("{} 1", "0".repeat(count))],
+            GateType::Or | GateType::Wire | GateType::Nand => (0..count).map(|i| {
+                let one: String = (0..count).map(|j| if i != j {'-'} else if gate.gate_type != GateType::Nand {'1'} else {'0'}).collect();
+                format!("{} 1", one)
+            }).collect(),
+            GateType::Xor if count == 2 => vec![String::from("01 1"), String::from("10 1")],
+            GateType::Nxor if count == 2 => vec![String::from("00 1"), String::from("11 1")],
+            GateType::Xor => vec![String::from("1 1")],
+            GateType::Nxor => vec![String::from("0 1")],
+        };
+        writeln!(buf, ".names {} {}", sources.join(" "), target)?;
+        for row in rows {
+            writeln!(buf, "{}", row)?;
+        }
+    }
+
+    for ic in circuit.intergrated_circuits.iter() {
+        let inner_nets = verilog::net_names(&ic.circuit);
+        let mut connections = Vec::new();
+        for port in ic.inputs.iter().chain(ic.outputs.iter()) {
+            let inner = inner_nets.iter().find(|n| n.0 == port[1].1).map(|n| n.1.clone()).unwrap_or_default();
+            let index = circuit.gates.iter().position(|g| g.id == port[0].1);
+            let outer = match index {
+                Some(index) if feedback[index] && ic.outputs.contains(port) => format!("{}_ic", net(port[0].1)),
+                _ => net(port[0].1),
+            };
+            connections.push(format!("{}={}", inner, outer));
+        }
+        writeln!(buf, ".subckt {} {}", verilog::module_name(&ic.circuit, names), connections.join(" "))?;
+    }
+    writeln!(buf, ".end")?;
+
+    for ic in circuit.intergrated_circuits.iter() {
+        if !written.contains(&ic.circuit.id) {
+            writeln!(buf)?;
+            write_model(&ic.circuit, names, written, buf)?;
+        }
+    }
+    Ok(())
+}
+
+// Reading a BLIF file turns each model into a circuit in the catalogue, the same way as Verilog
+// modules. `.names` tables become the gates they match, or an OR of ANDs when they don't match
+// one, and a `.latch` is a Buffer starting at its initial value. Latches all change together
+// each step, so their clocks are ignored.
+pub fn import_blif(fp: &str, id: &mut u32, catalogue: &mut Vec<Circuit>) -> std::io::Result<Vec<u32>> {
+    let source = std::fs::read_to_string(fp)?;
+
+    // Lines ending in `\` carry on to the next one, and `#` starts a comment
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut joined = String::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        if let Some(line) = line.trim_end().strip_suffix('\\') {
+            joined.push_str(line);
+            joined.push(' ');
+            continue;
+        }
+        joined.push_str(line);
+        if !joined.trim().is_empty() {
+            lines.push((number + 1, joined.trim().to_string()));
+        }
+        joined.clear();
+    }
+
+    let mut modules: Vec<Module> = Vec::new();
+    let mut latches: Vec<(usize, String)> = Vec::new(); // Module; Net that starts on
+    let mut i = 0;
+    while i < lines.len() {
+        let (line, text) = &lines[i];
+        i += 1;
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let error = |message: String| Err(invalid_data(format!("Line {}: {}", line, message)));
+
+        if words[0] == ".model" {
+            modules.push(Module {
+                name: words.get(1).map(|w| w.to_string()).unwrap_or(format!("model{}", modules.len())),
+                ports: Vec::new(),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                drivers: Vec::new(),
+                tris: Vec::new(),
+                instances: Vec::new(),
+                line: *line,
+            });
+            continue;
+        }
+        let Some(module) = modules.last_mut() else {
+            return error(String::from("everything has to be inside a .model"));
+        };
+        match words[0] {
+            ".inputs" => module.inputs.extend(words[1..].iter().map(|w| w.to_string())),
+            ".outputs" => module.outputs.extend(words[1..].iter().map(|w| w.to_string())),
+            ".names" => {
+                let Some((output, inputs)) = words[1..].split_last() else {
+                    return error(String::from(".names needs an output"));
+                };
+                let mut rows = Vec::new();
+                while i < lines.len() && !lines[i].1.starts_with('.') {
+                    rows.push(lines[i].1.split_whitespace().collect::<Vec<&str>>().join(" "));
+                    i += 1;
+                }
+                let expr = match cover(inputs, &rows) {
+                    Ok(expr) => expr,
+                    Err(message) => return error(message),
+                };
+                module.drivers.push((output.to_string(), expr, *line));
+            }
+            ".latch" => {
+                if words.len() < 3 {
+                    return error(String::from(".latch needs an input and an output"));
+                }
+                // The initial value is last when there are 3 or 5 words after `.latch`
+                if [4, 6].contains(&words.len()) && words[words.len() - 1] == "1" {
+                    latches.push((modules.len() - 1, words[2].to_string()));
+                }
+                let module = modules.last_mut().unwrap();
+                module.drivers.push((words[2].to_string(), Expr::Gate(GateType::Buffer, vec![Expr::Net(words[1].to_string())]), *line));
+            }
+            ".subckt" => {
+                let Some(model) = words.get(1) else {
+                    return error(String::from(".subckt needs a model"));
+                };
+                let mut connections = Vec::new();
+                for connection in words[2..].iter() {
+                    let Some((formal, actual)) = connection.split_once('=') else {
+                        return error(format!("{} should be port=net", connection));
+                    };
+                    connections.push((Some(formal.to_string()), Some(Expr::Net(actual.to_string()))));
+                }
+                let name = format!("{}{}", model, module.instances.len());
+                module.instances.push(Instance { module: model.to_string(), name, connections, line: *line });
+            }
+            ".end" => {}
+            // These are only about timing and area, which simlo doesn't have
+            ".clock" | ".area" | ".delay" | ".wire_load_slope" | ".wire" | ".input_arrival" | ".output_required"
+                | ".default_input_arrival" | ".default_output_required" | ".input_drive" | ".default_input_drive"
+                | ".output_load" | ".default_output_load" | ".max_input_load" | ".cname" | ".attr" | ".param" => {}
+            other => return error(format!("{} isn't supported", other)),
+        }
+    }
+    if modules.is_empty() {
+        return Err(invalid_data(String::from("There are no models in the file")));
+    }
+    for module in modules.iter_mut() {
+        module.ports = [module.inputs.clone(), module.outputs.clone()].concat();
+    }
+
+    let mut circuits = verilog::build_modules(&modules, id, catalogue)?;
+    for (module, net) in latches {
+        let circuit = circuits.iter_mut().find(|c| c.name.as_ref() == Some(&modules[module].name)).unwrap();
+        let Some(index) = circuit.gates.iter().position(|g| g.label.as_ref() == Some(&net) && g.gate_type == GateType::Buffer) else { continue };
+        circuit.gates[index].state = true;
+        // What the latch reads starts the same, as it would be once settled, or loops through it can go round forever
+        if let Some(input) = circuit.gates[index].inputs.first().map(|i| i.1) {
+            if let Some(gate) = circuit.gates.iter_mut().find(|g| g.id == input && g.gate_type != GateType::Input) {
+                gate.state = true;
+            }
+        }
+    }
+    let ids = circuits.iter().map(|c| c.id).collect();
+    catalogue.extend(circuits);
+    Ok(ids)
+}
+
+// Each row of a table is a set of input values, with `-` for either, and the value the output has
+// when the inputs match any of the rows
+fn cover(inputs: &[&str], rows: &[String]) -> Result<Expr, String> {
+    let mut cubes: Vec<&str> = Vec::new();
+    let mut on = Option::None;
+    for row in rows.iter() {
+        let (cube, value) = if inputs.is_empty() {("", row.as_str())} else {row.split_once(' ').unwrap_or((row, ""))};
+        if cube.len() != inputs.len() || cube.chars().any(|c| !"01-".contains(c)) {
+            return Err(format!("\"{}\" doesn't fit {} input(s)", row, inputs.len()));
+        }
+        let value = match value {
+            "1" => true,
+            "0" => false,
+            _ => return Err(format!("\"{}\" needs an output of 0 or 1", row)),
+        };
+        if on.is_some_and(|o| o != value) {
+            return Err(String::from("a table can't have rows for both 0 and 1"));
+        }
+        on = Some(value);
+        cubes.push(cube);
+    }
+    let Some(on) = on else { return Ok(Expr::Constant(false)) };
+    let nets: Vec<Expr> = inputs.iter().map(|i| Expr::Net(i.to_string())).collect();
+    let flip = |gate_type: GateType, other: GateType| if on {gate_type} else {other};
+    let all = |c: char| cubes.len() == 1 && cubes[0].chars().all(|x| x == c);
+
+    if nets.is_empty() {
+        return Ok(Expr::Constant(on));
+    }
+    if nets.len() == 1 && cubes.len() == 1 && cubes[0] != "-" {
+        let same = (cubes[0] == "1") == on;
+        return Ok(Expr::Gate(if same {GateType::Buffer} else {GateType::Not}, nets));
+    }
+    if all('1') {
+        return Ok(Expr::Gate(flip(GateType::And, GateType::Nand), nets));
+    }
+    if all('0') {
+        return Ok(Expr::Gate(flip(GateType::Nor, GateType::Or), nets));
+    }
+    // One input set in each row, and every input has a row
+    let single = |c: char| {
+        let mut positions: Vec<usize> = cubes.iter().filter_map(|cube| {
+            let set: Vec<(usize, char)> = cube.char_indices().filter(|x| x.1 != '-').collect();
+            if set.len() == 1 && set[0].1 == c {Some(set[0].0)} else {Option::None}
+        }).collect();
+        positions.sort();
+        positions == (0..nets.len()).collect::<Vec<usize>>()
+    };
+    if single('1') {
+        return Ok(Expr::Gate(flip(GateType::Or, GateType::Nor), nets));
+    }
+    if single('0') {
+        return Ok(Expr::Gate(flip(GateType::Nand, GateType::And), nets));
+    }
+    if nets.len() == 2 && cubes.len() == 2 {
+        let mut sorted = cubes.clone();
+        sorted.sort();
+        if sorted == ["01", "10"] {
+            return Ok(Expr::Gate(flip(GateType::Xor, GateType::Nxor), nets));
+        }
+        if sorted == ["00", "11"] {
+            return Ok(Expr::Gate(flip(GateType::Nxor, GateType::Xor), nets));
+        }
+    }
+
+    let terms: Vec<Expr> = cubes.iter().map(|cube| {
+        let literals: Vec<Expr> = cube.chars().zip(nets.iter()).filter_map(|(c, net)| match c {
+            '1' => Some(net.clone()),
+            '0' => Some(Expr::Gate(GateType::Not, vec![net.clone()])),
+            _ => Option::None,
+        }).collect();
+        match literals.len() {
+            0 => Expr::Constant(true),
+            1 => literals.into_iter().next().unwrap(),
+            _ => Expr::Gate(GateType::And, literals),
+        }
+    }).collect();
+    let sum = if terms.len() == 1 {terms.into_iter().next().unwrap()} else {Expr::Gate(GateType::Or, terms)};
+    Ok(if on {sum} else {Expr::Gate(GateType::Not, vec![sum])})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{stepped_outputs, temp_path, wide};
+
+    fn imported(source: &str) -> std::io::Result<Vec<Circuit>> {
+        let fp = temp_path("malformed.blif");
+        std::fs::write(&fp, source)?;
+        let mut catalogue = Vec::new();
+        let result = import_blif(&fp, &mut 100, &mut catalogue);
+        std::fs::remove_file(&fp)?;
+        result.map(|_| catalogue)
+    }
+
+    #[test]
+    fn export_then_import_steps_the_same() {
+        // A Nor latch loops back on itself, so it is written with .latch
+        let mut circuit = wide();
+        circuit.add_component(GateType::Nor, vec![0, 28], Option::None); // 27
+        circuit.add_component(GateType::Nor, vec![1, 27], Option::None); // 28
+        circuit.add_component(GateType::Output, vec![27], Option::None);
+        // Starting set, or stepping with both inputs off never settles
+        circuit.gates.iter_mut().find(|g| g.id == 27).unwrap().state = true;
+
+        let fp = temp_path("round_trip.blif");
+        export_blif(&circuit, &fp).unwrap();
+        assert!(std::fs::read_to_string(&fp).unwrap().contains(".latch"));
+        let mut catalogue = Vec::new();
+        import_blif(&fp, &mut 100, &mut catalogue).unwrap();
+        std::fs::remove_file(&fp).unwrap();
+
+        let names: Vec<Option<String>> = catalogue.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, [Some(String::from("half_adder")), Some(String::from("circuit4"))]);
+        for vector in 0..256 {
+            assert_eq!(stepped_outputs(&catalogue[1], vector), stepped_outputs(&circuit, vector), "vector {:08b}", vector);
+        }
+    }
+
+    #[test]
+    fn malformed_blif_is_an_error() {
+        for (source, error) in [
+            ("", "There are no models in the file"),
+            (".inputs a\n", "Line 1: everything has to be inside a .model"),
+            (".model m\n.inputs a\n.outputs y\n.names a y\n1 1\n0 0\n.end\n", "Line 4: a table can't have rows for both 0 and 1"),
+            (".model m\n.inputs a\n.outputs y\n.names a y\n11 1\n.end\n", "Line 4: \"11 1\" doesn't fit 1 input(s)"),
+            (".model m\n.inputs a\n.outputs y\n.latch a\n.end\n", "Line 4: .latch needs an input and an output"),
+            (".model m\n.inputs a\n.outputs y\n.subckt n a y\n.end\n", "Line 4: a should be port=net"),
+            (".model m\n.inputs a\n.outputs y\n.gate and2 A=a O=y\n.end\n", "Line 4: .gate isn't supported"),
+            (".model m\n.inputs a\n.outputs y\n.subckt n x=a\n.end\n", "Line 4: there is no module called n"),
+        ] {
+            let result = imported(source);
+            assert_eq!(result.err().map(|e| e.to_string()), Some(String::from(error)), "{:?}", source);
+        }
+    }
+}
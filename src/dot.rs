@@ -0,0 +1,82 @@
+use std::{fs::File, io::Write};
+
+use crate::{Circuit, GateType};
+
+// Gates are drawn with a shape for their type, with a second outline on the inverted ones, and
+// each IC gets a box of its own with dashed lines to the gates outside that it is connected to.
+// Coloured graphs fill in the gates that are on, so they show the state the circuit was in.
+pub fn export_dot(circuit: &Circuit, fp: &str, states: bool) -> std::io::Result<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    let name = circuit.name.clone().unwrap_or(format!("Circuit {}", circuit.id));
+    writeln!(buf, "digraph \"{}\" {{", escape(&name))?;
+    writeln!(buf, "    rankdir=LR;")?;
+    writeln!(buf, "    node [fontname=\"monospace\"];")?;
+    write_gates(circuit, "", 1, states, &mut buf)?;
+    writeln!(buf, "}}")?;
+
+    let mut file = File::create(fp)?;
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+fn write_gates(circuit: &Circuit, prefix: &str, depth: usize, states: bool, buf: &mut Vec<u8>) -> std::io::Result<()> {
+    let indent = "    ".repeat(depth);
+    for gate in circuit.gates.iter() {
+        let (shape, inverted) = match gate.gate_type {
+            GateType::Input => ("invhouse", false),
+            GateType::Output => ("house", false),
+            GateType::Buffer => ("triangle", false),
+            GateType::Not => ("triangle", true),
+            GateType::And => ("box", false),
+            GateType::Nand => ("box", true),
+            GateType::Or => ("ellipse", false),
+            GateType::Nor => ("ellipse", true),
+            GateType::Xor => ("diamond", false),
+            GateType::Nxor => ("diamond", true),
+            GateType::Tristate => ("trapezium", false),
+            GateType::Wire => ("point", false),
+        };
+        let mut label = format!("{:?} {}", gate.gate_type, gate.id);
+        if let Some(l) = &gate.label {
+            label = format!("{}\\n{}", label, escape(l));
+        }
+        write!(buf, "{}{}g{} [shape={}, label=\"{}\"", indent, prefix, gate.id, shape, label)?;
+        if inverted {
+            write!(buf, ", peripheries=2")?;
+        }
+        if states {
+            write!(buf, ", style=filled, fillcolor=\"{}\"", if gate.state {"#7fd97f"} else {"#e0e0e0"})?;
+        }
+        writeln!(buf, "];")?;
+    }
+
+    for gate in circuit.gates.iter() {
+        for input in gate.inputs.iter() {
+            if circuit.gates.iter().any(|g| g.id == input.1) {
+                writeln!(buf, "{}{}g{} -> {}g{};", indent, prefix, input.1, prefix, gate.id)?;
+            }
+        }
+    }
+
+    for (i, ic) in circuit.intergrated_circuits.iter().enumerate() {
+        let inner = format!("{}ic{}_", prefix, i);
+        let name = ic.circuit.name.clone().unwrap_or(format!("Circuit {}", ic.circuit.id));
+        writeln!(buf, "{}subgraph cluster_{}ic{} {{", indent, prefix, i)?;
+        writeln!(buf, "{}    label=\"IC {}: {}\";", indent, i, escape(&name))?;
+        writeln!(buf, "{}    style=rounded;", indent)?;
+        write_gates(&ic.circuit, &inner, depth + 1, states, buf)?;
+        writeln!(buf, "{}}}", indent)?;
+
+        for port in ic.inputs.iter() {
+            writeln!(buf, "{}{}g{} -> {}g{} [style=dashed];", indent, prefix, port[0].1, inner, port[1].1)?;
+        }
+        for port in ic.outputs.iter() {
+            writeln!(buf, "{}{}g{} -> {}g{} [style=dashed];", indent, inner, port[1].1, prefix, port[0].1)?;
+        }
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
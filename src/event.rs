@@ -0,0 +1,211 @@
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap}, io::Write};
+
+use crate::{invalid_data, Circuit, Gate, GateType};
+
+// Simulates the flattened circuit in time instead of in steps. Each gate takes its own delay to
+// follow its inputs, so when paths of different lengths meet again a gate can see its inputs
+// change at different times and its output flick before settling, like a real glitch. A change
+// puts an event on the queue for each gate reading it, at the time that gate's output would
+// follow. The delays are transport delays, so a pulse gets through however short it is.
+
+// More events than this without time moving on means a loop of gates with no delay
+const MAX_EVENTS: usize = 1_000_000;
+
+pub struct Events {
+    gates: Vec<Gate>,
+    inputs: Vec<Vec<usize>>,
+    fanout: Vec<Vec<usize>>,
+    states: Vec<bool>,
+    queue: BinaryHeap<Reverse<(u64, u64, usize, bool)>>, // Time; Order it was scheduled in; Gate; State
+    pending: Vec<HashMap<u64, u64>>, // For each gate, the order of the last event queued for each time
+    scheduled: u64,
+    time: u64,
+}
+
+impl Events {
+    // Starts from the states the circuit is in, as if its Inputs had just been set to what they
+    // are, so every gate gets an event for what it should go to next
+    pub fn new(circuit: &Circuit) -> Events {
+        let flat = circuit.flattened();
+        let inputs: Vec<Vec<usize>> = flat.gates.iter().map(|g| g.inputs.iter().map(|i| i.1 as usize).collect()).collect();
+        let mut fanout = vec![Vec::new(); flat.gates.len()];
+        for (gate, ins) in inputs.iter().enumerate() {
+            for input in ins.iter() {
+                if !fanout[*input].contains(&gate) {
+                    fanout[*input].push(gate);
+                }
+            }
+        }
+        let mut events = Events {
+            states: flat.gates.iter().map(|g| g.state).collect(),
+            pending: vec![HashMap::new(); flat.gates.len()],
+            gates: flat.gates,
+            inputs,
+            fanout,
+            queue: BinaryHeap::new(),
+            scheduled: 0,
+            time: 0,
+        };
+        for gate in 0..events.gates.len() {
+            events.schedule(gate);
+        }
+        events
+    }
+    fn schedule(&mut self, gate: usize) {
+        if self.gates[gate].gate_type == GateType::Input {
+            return;
+        }
+        let state = self.gates[gate].get_new_state(self.inputs[gate].iter().map(|i| self.states[*i]).collect());
+        let time = self.time + self.gates[gate].delay() as u64;
+        self.queue.push(Reverse((time, self.scheduled, gate, state)));
+        self.pending[gate].insert(time, self.scheduled);
+        self.scheduled += 1;
+    }
+    // Carries out every event up to and including tick `until`, giving back each change of state
+    // in the order it happened as the tick, gate index and new state
+    pub fn run(&mut self, until: u64) -> std::io::Result<Vec<(u64, usize, bool)>> {
+        let mut changes = Vec::new();
+        let mut events = 0;
+        while let Some(Reverse((time, order, gate, state))) = self.queue.peek().copied() {
+            if time > until {
+                break;
+            }
+            self.queue.pop();
+            events = if time == self.time { events + 1 } else { 0 };
+            if events > MAX_EVENTS {
+                return Err(invalid_data(format!("The circuit is still changing at tick {} without time moving on, it has a loop with no delay", time)));
+            }
+            self.time = time;
+            // A gate's inputs changing twice in one tick only counts the last time, so there are
+            // no pulses that take no time at all
+            if self.pending[gate].get(&time) != Some(&order) {
+                continue;
+            }
+            self.pending[gate].remove(&time);
+            if self.states[gate] == state {
+                continue;
+            }
+            self.states[gate] = state;
+            changes.push((time, gate, state));
+            for reader in 0..self.fanout[gate].len() {
+                self.schedule(self.fanout[gate][reader]);
+            }
+        }
+        self.time = self.time.max(until);
+        Ok(changes)
+    }
+    pub fn settled(&self) -> bool {
+        self.queue.is_empty()
+    }
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+    pub fn gate(&self, gate: usize) -> &Gate {
+        &self.gates[gate]
+    }
+    pub fn state(&self, gate: usize) -> bool {
+        self.states[gate]
+    }
+    // The gates a gate reads from, by index
+    pub fn reads(&self, gate: usize) -> &[usize] {
+        &self.inputs[gate]
+    }
+    pub fn gate_count(&self) -> usize {
+        self.gates.len()
+    }
+    // The longest anything could take to settle without loops, every delay one after the other
+    pub fn total_delay(&self) -> u64 {
+        self.gates.iter().map(|g| g.delay() as u64).sum()
+    }
+    // Flips the state of an Input now, given its index in the flattened circuit
+    pub fn toggle(&mut self, gate: usize) {
+        self.states[gate] = !self.states[gate];
+        for reader in 0..self.fanout[gate].len() {
+            self.schedule(self.fanout[gate][reader]);
+        }
+    }
+    pub fn write_back(&self, circuit: &mut Circuit) {
+        circuit.unflatten_states(&self.states, &mut 0);
+    }
+}
+
+// Lets the circuit settle, flips the given Inputs all at once and reports how the Outputs get to
+// their new states over the next `ticks`, leaving the circuit in the states it ended up in
+pub fn run(circuit: &mut Circuit, ticks: u64, toggles: &[u32], out: &mut impl Write) -> std::io::Result<()> {
+    let mut flips = Vec::new();
+    for id in toggles {
+        match circuit.flat_index(*id) {
+            Some(i) if circuit.gates[i].gate_type == GateType::Input => flips.push(i),
+            _ => return Err(invalid_data(format!("Component {} is not an Input of this circuit", id))),
+        }
+    }
+    let mut events = Events::new(circuit);
+    events.run(ticks)?;
+    if !events.settled() {
+        return Err(invalid_data(format!("The circuit is still changing after {} tick(s), before any Inputs were flipped", ticks)));
+    }
+    let start = events.time();
+    for flip in flips {
+        events.toggle(flip);
+    }
+    let changes = events.run(start + ticks)?;
+    events.write_back(circuit);
+
+    let outputs: Vec<(usize, String)> = circuit.flat_indexes_of(GateType::Output).into_iter()
+        .map(|i| (i, circuit.flat_short_name(&circuit.gates[i], i)))
+        .collect();
+    writeln!(out, "{} change(s) over {} tick(s)", changes.len(), ticks)?;
+    for (time, gate, state) in changes.iter() {
+        if let Some((_, name)) = outputs.iter().find(|o| o.0 == *gate) {
+            writeln!(out, "{:>6} {} {}", time - start, name, if *state {1} else {0})?;
+        }
+    }
+    for (gate, name) in outputs.iter() {
+        let count = changes.iter().filter(|c| c.1 == *gate).count();
+        if count > 1 {
+            writeln!(out, "Output {} changed {} times on its way to {}, a glitch", name, count, if events.states[*gate] {1} else {0})?;
+        }
+    }
+    match changes.last() {
+        _ if !events.settled() => writeln!(out, "Still changing after {} tick(s)", ticks),
+        Some((time, ..)) => writeln!(out, "Settled at tick {}", time - start),
+        Option::None => writeln!(out, "Nothing changed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::run;
+
+    // A flips Z straight away and C a tick later, so the And is worked out twice for tick 3 while
+    // it already has an event for tick 4. Both of the first two times it should stay at 0.
+    fn late_and() -> Circuit {
+        let mut circuit = Circuit::new(1);
+        run(&mut circuit, &mut Vec::new(), &["INPUT; A", "NOT 0 DELAY 0; Z", "NOT 0 DELAY 1; C", "AND 0 1 2 DELAY 3; G", "OUTPUT 3; Y"]);
+        circuit
+    }
+
+    #[test]
+    fn two_events_in_one_tick_only_keep_the_last() {
+        let circuit = late_and();
+        let mut events = Events::new(&circuit);
+        events.run(100).unwrap();
+        events.toggle(0);
+        let changes = events.run(200).unwrap();
+        assert_eq!(changes, vec![(100, 1, false), (101, 2, false)]);
+        assert!(events.settled());
+    }
+
+    #[test]
+    fn run_follows_the_delays_of_each_path() {
+        let mut circuit = late_and();
+        let text = run(&mut circuit, &mut Vec::new(), &["RUN 20 0"]);
+        assert_eq!(text, "2 change(s) over 20 tick(s)\nSettled at tick 1\n");
+
+        // A goes straight into the And but takes 2 ticks through the Not, so Y is on for 2 ticks
+        let mut circuit = Circuit::new(2);
+        let text = run(&mut circuit, &mut Vec::new(), &["INPUT; A", "NOT 0 DELAY 2", "AND 0 1 DELAY 1", "OUTPUT 2; Y", "RUN 20 0"]);
+        assert!(text.ends_with("     1 Y 1\n     3 Y 0\nOutput Y changed 2 times on its way to 0, a glitch\nSettled at tick 3\n"), "{}", text);
+    }
+}
@@ -0,0 +1,142 @@
+use std::io::Write;
+
+use crate::{packed::{lane_bits, lane_mask, Fault, Packed, LANES}, vectors, Circuit};
+
+// Stuck-at fault simulation. Every gate output and input pin of the circuit, outside of its ICs,
+// is stuck at 0 and then at 1, and the test vectors are run against each one 64 at a time on the
+// packed simulator. A vector finds a fault when the Outputs aren't what they are without it.
+
+const MAX_SHOWN: usize = 8;
+
+pub fn faults(circuit: &Circuit, packed: &Packed) -> Vec<Fault> {
+    let mut faults = Vec::new();
+    // Only the circuit's own gates, which have the same index in the packed simulator
+    for gate in 0..circuit.gates.len() {
+        for pin in [Option::None].into_iter().chain((0..packed.gates()[gate].1.len()).map(Some)) {
+            for stuck in [false, true] {
+                faults.push(Fault { gate, pin, stuck });
+            }
+        }
+    }
+    faults
+}
+
+// `flat` is the circuit flattened, which the packed simulator runs
+pub fn describe(circuit: &Circuit, flat: &Circuit, fault: &Fault) -> String {
+    let stuck = if fault.stuck {1} else {0};
+    let name = |i: usize| circuit.flat_gate_name(&flat.gates[i], i);
+    match fault.pin {
+        // Flattened ids are indexes
+        Some(pin) => format!("Input {} of {}, from {}, stuck at {}", pin, name(fault.gate), name(flat.gates[fault.gate].inputs[pin].1 as usize), stuck),
+        Option::None => format!("Output of {} stuck at {}", name(fault.gate), stuck),
+    }
+}
+
+// The settled Outputs for each batch of up to 64 vectors
+pub fn run(packed: &mut Packed, batches: &[Vec<&[bool]>]) -> Vec<Vec<u64>> {
+    batches.iter().map(|batch| {
+        packed.load_lanes(batch);
+        packed.settle();
+        packed.outputs()
+    }).collect()
+}
+
+// Which of the faults are found by at least one of the vectors
+pub fn detected(packed: &mut Packed, faults: &[Fault], vectors: &[&[bool]]) -> Vec<bool> {
+    let batches: Vec<Vec<&[bool]>> = vectors.chunks(LANES as usize).map(|c| c.to_vec()).collect();
+    packed.set_fault(Option::None);
+    let good = run(packed, &batches);
+    let found = faults.iter().map(|fault| {
+        packed.set_fault(Some(*fault));
+        batches.iter().zip(good.iter()).any(|(batch, good)| {
+            packed.load_lanes(batch);
+            packed.settle();
+            let differ = packed.outputs().iter().zip(good.iter()).fold(0, |d, (a, b)| d | (a ^ b));
+            differ & lane_mask(batch.len() as u64) != 0
+        })
+    }).collect();
+    packed.set_fault(Option::None);
+    found
+}
+
+pub fn simulate(circuit: &Circuit, fp: &str, out: &mut impl Write) -> std::io::Result<()> {
+    let mut packed = Packed::new(circuit);
+    let vectors = vectors::read_vectors(fp, packed.input_count(), packed.output_count())?;
+    if vectors.is_empty() {
+        writeln!(out, "There are no vectors in {}", fp)?;
+        return Ok(());
+    }
+    let inputs: Vec<&[bool]> = vectors.iter().map(|v| v.inputs.as_slice()).collect();
+
+    // Vectors that say what the Outputs should be are checked against the circuit without faults
+    let batches: Vec<Vec<&[bool]>> = inputs.chunks(LANES as usize).map(|c| c.to_vec()).collect();
+    let good = run(&mut packed, &batches);
+    let mut wrong = 0;
+    for (i, vector) in vectors.iter().enumerate() {
+        let Some(expected) = &vector.outputs else { continue };
+        let given = lane_bits(&good[i / LANES as usize], (i % LANES as usize) as u64);
+        let expected: String = expected.iter().map(|b| if *b {'1'} else {'0'}).collect();
+        if given != expected {
+            wrong += 1;
+            if wrong <= MAX_SHOWN {
+                writeln!(out, "Vector {} gives {} where the file expects {}", i + 1, given, expected)?;
+            }
+        }
+    }
+    if wrong > 0 {
+        writeln!(out, "{} vector(s) don't give the Outputs the file expects", wrong)?;
+    }
+
+    let faults = faults(circuit, &packed);
+    let found = detected(&mut packed, &faults, &inputs);
+    let count = found.iter().filter(|f| **f).count();
+    writeln!(out, "Ran {} vector(s) against {} stuck-at fault(s)", vectors.len(), faults.len())?;
+    writeln!(out, "Fault coverage: {} of {} ({:.1}%)", count, faults.len(), 100.0 * count as f64 / faults.len().max(1) as f64)?;
+    if count < faults.len() {
+        let flat = circuit.flattened();
+        writeln!(out, "Never detected:")?;
+        for (fault, _) in faults.iter().zip(found.iter()).filter(|(_, f)| !**f) {
+            writeln!(out, "  {}", describe(circuit, &flat, fault))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::{run, temp_path}, Circuit};
+
+    fn and_gate() -> Circuit {
+        let mut circuit = Circuit::new(1);
+        run(&mut circuit, &mut Vec::new(), &["INPUT", "INPUT", "AND 0 1", "OUTPUT 2"]);
+        circuit
+    }
+
+    #[test]
+    fn one_vector_finds_only_the_stuck_at_0_faults_of_an_and() {
+        let fp = temp_path("faultsim_and.txt");
+        std::fs::write(&fp, "# A B | Y\n11 | 1\n").unwrap();
+        let text = run(&mut and_gate(), &mut Vec::new(), &[&format!("FAULTSIM {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        assert!(text.starts_with("Ran 1 vector(s) against 14 stuck-at fault(s)\nFault coverage: 7 of 14 (50.0%)\nNever detected:\n"), "{}", text);
+        assert_eq!(text.matches("stuck at 1").count(), 7, "{}", text);
+        assert_eq!(text.matches("stuck at 0").count(), 0, "{}", text);
+    }
+
+    #[test]
+    fn faultsim_reads_back_a_saved_truth_table() {
+        let fp = temp_path("faultsim_truth.txt");
+        let text = run(&mut and_gate(), &mut Vec::new(), &[&format!("TRUTH {}", fp), &format!("FAULTSIM {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        assert!(text.ends_with("Ran 4 vector(s) against 14 stuck-at fault(s)\nFault coverage: 14 of 14 (100.0%)\n"), "{}", text);
+    }
+
+    #[test]
+    fn vectors_that_expect_the_wrong_outputs_are_shown() {
+        let fp = temp_path("faultsim_wrong.txt");
+        std::fs::write(&fp, "00 | 0\n10 | 1\n").unwrap();
+        let text = run(&mut and_gate(), &mut Vec::new(), &[&format!("FAULTSIM {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        assert!(text.starts_with("Vector 2 gives 0 where the file expects 1\n1 vector(s) don't give the Outputs the file expects\n"), "{}", text);
+    }
+}
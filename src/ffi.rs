@@ -0,0 +1,305 @@
+use std::{cell::RefCell, ffi::{c_char, CStr, CString}, ptr};
+
+use crate::{Circuit, GateType};
+
+// The C interface, built into the cdylib. Circuits are handed out as pointers that C can't see
+// inside of and have to be given back to simlo_circuit_free. Functions that fail return false,
+// -1 or NULL and leave a message for simlo_last_error. include/simlo.h is made from this file
+// by build.rs, with the comment above each function going along with it, so keep each
+// signature on one line. Build with SIMLO_HEADER=include/simlo.h to bring it up to date.
+
+pub struct SimloCircuit(Circuit);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(Option::None) };
+}
+
+fn fail(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+unsafe fn circuit<'a>(handle: *mut SimloCircuit) -> Option<&'a mut Circuit> {
+    match handle.as_mut() {
+        Some(handle) => Some(&mut handle.0),
+        Option::None => {
+            fail(String::from("The circuit is NULL"));
+            Option::None
+        }
+    }
+}
+
+unsafe fn text(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        fail(String::from("The string is NULL"));
+        return Option::None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s.to_string()),
+        Err(_) => {
+            fail(String::from("The string isn't UTF-8"));
+            Option::None
+        }
+    }
+}
+
+fn labelled(circuit: &Circuit, label: &str) -> Option<u32> {
+    let id = circuit.gates.iter().find(|g| g.label.as_deref() == Some(label)).map(|g| g.id);
+    if id.is_none() {
+        fail(format!("No component is labelled \"{}\"", label));
+    }
+    id
+}
+
+fn state_of(circuit: &Circuit, id: u32) -> i32 {
+    match circuit.gates.iter().find(|g| g.id == id) {
+        Some(gate) => gate.state as i32,
+        Option::None => {
+            fail(format!("Component {} does not exist", id));
+            -1
+        }
+    }
+}
+
+fn set(circuit: &mut Circuit, id: u32, state: bool) -> bool {
+    let set = circuit.set_component(id, state);
+    if !set {
+        fail(format!("Component {} does not exist", id));
+    }
+    set
+}
+
+// The nth gate of a type, counting in the order they are in the circuit
+fn nth(circuit: &Circuit, gate_type: GateType, index: usize, id: *mut u32, state: *mut bool) -> bool {
+    let Some(gate) = circuit.gates.iter().filter(|g| g.gate_type == gate_type).nth(index) else {
+        fail(format!("There is no {:?} {}", gate_type, index));
+        return false;
+    };
+    // SAFETY: the caller gives NULL or somewhere to write to
+    unsafe {
+        if let Some(id) = id.as_mut() {
+            *id = gate.id;
+        }
+        if let Some(state) = state.as_mut() {
+            *state = gate.state;
+        }
+    }
+    true
+}
+
+// The message from the last call on this thread that failed, or NULL. It stays valid until the next call that fails.
+#[no_mangle]
+pub extern "C" fn simlo_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|e| e.as_ptr()).unwrap_or(ptr::null()))
+}
+
+// A new empty circuit.
+#[no_mangle]
+pub extern "C" fn simlo_circuit_new() -> *mut SimloCircuit {
+    Box::into_raw(Box::new(SimloCircuit(Circuit::new(1))))
+}
+
+// Loads a circuit from a .lo file, or gives NULL if it couldn't.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_circuit_load(path: *const c_char) -> *mut SimloCircuit {
+    let Some(path) = text(path) else { return ptr::null_mut() };
+    let mut circuit = Circuit::new(1);
+    match circuit.load_from_file(&path) {
+        Ok(()) => Box::into_raw(Box::new(SimloCircuit(circuit))),
+        Err(e) => {
+            fail(format!("Failed to load circuit from {}\n{}", path, e));
+            ptr::null_mut()
+        }
+    }
+}
+
+// Saves the circuit to a .lo file.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_circuit_save(circuit: *mut SimloCircuit, path: *const c_char) -> bool {
+    let (Some(circuit), Some(path)) = (self::circuit(circuit), text(path)) else { return false };
+    match circuit.save_to_file(&path) {
+        Ok(()) => true,
+        Err(e) => {
+            fail(format!("Failed to save circuit to {}\n{}", path, e));
+            false
+        }
+    }
+}
+
+// Frees a circuit from simlo_circuit_new or simlo_circuit_load. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_circuit_free(circuit: *mut SimloCircuit) {
+    if !circuit.is_null() {
+        drop(Box::from_raw(circuit));
+    }
+}
+
+// How many gates are in the circuit, not counting the ones inside ICs.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_gate_count(circuit: *mut SimloCircuit) -> usize {
+    self::circuit(circuit).map(|c| c.gates.len()).unwrap_or(0)
+}
+
+// Finds the id of the first component with the label.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_find_label(circuit: *mut SimloCircuit, label: *const c_char, id: *mut u32) -> bool {
+    let (Some(circuit), Some(label)) = (self::circuit(circuit), text(label)) else { return false };
+    match (labelled(circuit, &label), id.as_mut()) {
+        (Some(found), Some(id)) => {
+            *id = found;
+            true
+        }
+        (found, _) => found.is_some(),
+    }
+}
+
+// Sets the state of a component, which is how inputs are switched.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_set(circuit: *mut SimloCircuit, id: u32, state: bool) -> bool {
+    let Some(circuit) = self::circuit(circuit) else { return false };
+    set(circuit, id, state)
+}
+
+// Sets the state of the first component with the label.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_set_label(circuit: *mut SimloCircuit, label: *const c_char, state: bool) -> bool {
+    let (Some(circuit), Some(label)) = (self::circuit(circuit), text(label)) else { return false };
+    match labelled(circuit, &label) {
+        Some(id) => set(circuit, id, state),
+        Option::None => false,
+    }
+}
+
+// The state of a component as 1 or 0, or -1 if it doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_get(circuit: *mut SimloCircuit, id: u32) -> i32 {
+    let Some(circuit) = self::circuit(circuit) else { return -1 };
+    state_of(circuit, id)
+}
+
+// The state of the first component with the label as 1 or 0, or -1 if there isn't one.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_get_label(circuit: *mut SimloCircuit, label: *const c_char) -> i32 {
+    let (Some(circuit), Some(label)) = (self::circuit(circuit), text(label)) else { return -1 };
+    match labelled(circuit, &label) {
+        Some(id) => state_of(circuit, id),
+        Option::None => -1,
+    }
+}
+
+// Steps the circuit the given number of times.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_step(circuit: *mut SimloCircuit, count: u32) {
+    if let Some(circuit) = self::circuit(circuit) {
+        for _ in 0..count {
+            circuit.step();
+        }
+    }
+}
+
+// Steps until nothing changes and gives the number of steps taken, or -1 if it was still changing after limit steps.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_settle(circuit: *mut SimloCircuit, limit: u32) -> i64 {
+    let Some(circuit) = self::circuit(circuit) else { return -1 };
+    match circuit.settle(limit) {
+        Some(steps) => steps as i64,
+        Option::None => {
+            fail(format!("The circuit was still changing after {} steps", limit));
+            -1
+        }
+    }
+}
+
+// How many Input gates the circuit has.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_input_count(circuit: *mut SimloCircuit) -> usize {
+    self::circuit(circuit).map(|c| c.gates.iter().filter(|g| g.gate_type == GateType::Input).count()).unwrap_or(0)
+}
+
+// The id and state of the nth Input, in the order they are in the circuit. id or state can be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_input(circuit: *mut SimloCircuit, index: usize, id: *mut u32, state: *mut bool) -> bool {
+    let Some(circuit) = self::circuit(circuit) else { return false };
+    nth(circuit, GateType::Input, index, id, state)
+}
+
+// How many Output gates the circuit has.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_output_count(circuit: *mut SimloCircuit) -> usize {
+    self::circuit(circuit).map(|c| c.gates.iter().filter(|g| g.gate_type == GateType::Output).count()).unwrap_or(0)
+}
+
+// The id and state of the nth Output, in the order they are in the circuit. id or state can be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn simlo_output(circuit: *mut SimloCircuit, index: usize, id: *mut u32, state: *mut bool) -> bool {
+    let Some(circuit) = self::circuit(circuit) else { return false };
+    nth(circuit, GateType::Output, index, id, state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{half_adder, temp_path};
+
+    fn last_error() -> String {
+        // SAFETY: simlo_last_error gives NULL or a string that lives until the next failure
+        unsafe { simlo_last_error().as_ref().map(|e| CStr::from_ptr(e).to_string_lossy().to_string()).unwrap_or_default() }
+    }
+
+    #[test]
+    fn circuits_are_made_stepped_and_freed_through_pointers() {
+        let handle = simlo_circuit_new();
+        // SAFETY: every pointer is the one simlo_circuit_new gave or a CString kept alive over the call
+        unsafe {
+            assert_eq!(simlo_gate_count(handle), 0);
+            // There is no C function to add gates, so the half adder goes in from this side
+            (*handle).0 = half_adder(1);
+            assert!(simlo_set(handle, 0, true));
+            assert!(simlo_set(handle, 1, true));
+            assert_eq!(simlo_get(handle, 3), 0);
+            simlo_step(handle, 1);
+            assert_eq!((simlo_get(handle, 2), simlo_get(handle, 3)), (0, 1));
+            simlo_step(handle, 1);
+            assert_eq!((simlo_get(handle, 4), simlo_get(handle, 5)), (0, 1));
+
+            let fp = temp_path("ffi.lo");
+            let path = CString::new(fp.clone()).unwrap();
+            assert!(simlo_circuit_save(handle, path.as_ptr()));
+            simlo_circuit_free(handle);
+
+            let loaded = simlo_circuit_load(path.as_ptr());
+            std::fs::remove_file(&fp).unwrap();
+            assert!(!loaded.is_null(), "{}", last_error());
+            assert!(simlo_set_label(loaded, c"B".as_ptr(), false));
+            assert_eq!(simlo_settle(loaded, 10), 3); // Two to change and one that changes nothing
+            assert_eq!((simlo_get_label(loaded, c"S".as_ptr()), simlo_get_label(loaded, c"C".as_ptr())), (1, 0));
+            let (mut id, mut state) = (0, false);
+            assert!(simlo_output(loaded, 1, &mut id, &mut state));
+            assert_eq!((simlo_input_count(loaded), simlo_output_count(loaded), id, state), (2, 2, 5, false));
+            simlo_circuit_free(loaded);
+        }
+    }
+
+    #[test]
+    fn failures_leave_a_message() {
+        // SAFETY: NULL is what is being tested, and everything else is from simlo_circuit_new or a C string literal
+        unsafe {
+            assert_eq!(simlo_get(ptr::null_mut(), 0), -1);
+            assert_eq!(last_error(), "The circuit is NULL");
+            simlo_circuit_free(ptr::null_mut());
+
+            let handle = simlo_circuit_new();
+            assert!(!simlo_set(handle, 7, true));
+            assert_eq!(last_error(), "Component 7 does not exist");
+            assert_eq!(simlo_get_label(handle, c"A".as_ptr()), -1);
+            assert_eq!(last_error(), "No component is labelled \"A\"");
+            assert!(!simlo_find_label(handle, ptr::null(), ptr::null_mut()));
+            assert_eq!(last_error(), "The string is NULL");
+            assert!(!simlo_input(handle, 0, ptr::null_mut(), ptr::null_mut()));
+            assert_eq!(last_error(), "There is no Input 0");
+            assert!(simlo_circuit_load(c"/simlo/no/such/file.lo".as_ptr()).is_null());
+            assert!(last_error().starts_with("Failed to load circuit from /simlo/no/such/file.lo\n"), "{}", last_error());
+            simlo_circuit_free(handle);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::Write;
+
+use crate::{event::Events, invalid_data, Circuit, GateType};
+
+// Looks for glitches on the Outputs by running the circuit in time, with its gate delays, for a
+// change of each Input from every input vector. An Output that should stay put but flicks has a
+// static hazard, static-1 if it should stay on and static-0 if off, and one that should change
+// once but goes back and forth on the way has a dynamic hazard. A glitch starts at a gate where
+// paths from the Input meet again and its inputs change at different times.
+
+// Every vector is tried for every Input, so this is kept small
+const MAX_INPUTS: usize = 12;
+const MAX_SHOWN: usize = 16;
+
+const HAZARDS: [&str; 3] = ["static-1", "static-0", "dynamic"];
+
+pub fn report(circuit: &Circuit, out: &mut impl Write) -> std::io::Result<()> {
+    let inputs = circuit.flat_indexes_of(GateType::Input);
+    let outputs = circuit.flat_indexes_of(GateType::Output);
+    if inputs.len() > MAX_INPUTS {
+        return Err(invalid_data(format!("The circuit has {} Inputs, which is more than the {} that can be tried", inputs.len(), MAX_INPUTS)));
+    }
+    let name = |i: usize| circuit.flat_short_name(&circuit.gates[i], i);
+
+    let mut events = Events::new(circuit);
+    let limit = events.total_delay() + 1;
+    let mut counts = vec![[0; 3]; outputs.len()];
+    let (mut found, mut unsettled, mut tried) = (0, 0, 0);
+    for vector in 0..1u64 << inputs.len() {
+        for input in inputs.iter() {
+            // The first Input is the highest bit, like the left column of a truth table
+            for (bit, gate) in inputs.iter().enumerate() {
+                if events.state(*gate) != ((vector >> (inputs.len() - 1 - bit)) & 1 == 1) {
+                    events.toggle(*gate);
+                }
+            }
+            events.run(events.time() + limit)?;
+            let start = events.time();
+            let before: Vec<bool> = outputs.iter().map(|o| events.state(*o)).collect();
+            events.toggle(*input);
+            let changes = events.run(start + limit)?;
+            tried += 1;
+            if !events.settled() {
+                unsettled += 1;
+                continue;
+            }
+
+            for (o, output) in outputs.iter().enumerate() {
+                let flips: Vec<u64> = changes.iter().filter(|c| c.1 == *output).map(|c| c.0 - start).collect();
+                let hazard = match flips.len() {
+                    0 | 1 => continue,
+                    n if n % 2 == 0 && before[o] => 0,
+                    n if n % 2 == 0 => 1,
+                    _ => 2,
+                };
+                counts[o][hazard] += 1;
+                found += 1;
+                if found > MAX_SHOWN {
+                    continue;
+                }
+
+                // Where it starts: gates that flicked because more than one of their inputs changed
+                let mut changed = vec![0; events.gate_count()];
+                for change in changes.iter() {
+                    changed[change.1] += 1;
+                }
+                let reconverging: Vec<String> = (0..events.gate_count())
+                    .filter(|g| changed[*g] > 1 && events.reads(*g).iter().filter(|r| changed[**r] > 0).count() > 1)
+                    .map(|g| circuit.flat_gate_name(events.gate(g), g))
+                    .collect();
+                let others: Vec<String> = inputs.iter().filter(|i| *i != input).map(|i| format!("{}={}", name(*i), if events.state(*i) {1} else {0})).collect();
+                write!(out, "A {} hazard on {} when {} {}", HAZARDS[hazard], name(*output), name(*input), if events.state(*input) {"rises"} else {"falls"})?;
+                if !others.is_empty() {
+                    write!(out, " with {}", others.join(" "))?;
+                }
+                writeln!(out, ", reconverging at {}", reconverging.join(", "))?;
+                writeln!(out, "    {} {}  (ticks 0 to {})", name(*output), waveform(before[o], &flips), flips.last().unwrap_or(&0) + 1)?;
+            }
+        }
+    }
+
+    if found > MAX_SHOWN {
+        writeln!(out, "... and {} more", found - MAX_SHOWN)?;
+    }
+    if found == 0 {
+        writeln!(out, "No Output glitched for any of the {} single Input change(s)", tried)?;
+    }
+    for (o, output) in outputs.iter().enumerate() {
+        if counts[o].iter().any(|c| *c > 0) {
+            writeln!(out, "{}: {} static-1, {} static-0 and {} dynamic hazard(s)", name(*output), counts[o][0], counts[o][1], counts[o][2])?;
+        }
+    }
+    if unsettled > 0 {
+        writeln!(out, "{} of the {} change(s) never settled and were left out", unsettled, tried)?;
+    }
+    Ok(())
+}
+
+// The state of a gate tick by tick, from how it started and the ticks it flipped at
+fn waveform(start: bool, flips: &[u64]) -> String {
+    let end = flips.last().copied().unwrap_or(0) + 1;
+    (0..=end).map(|tick| {
+        let on = start != (flips.iter().filter(|f| **f <= tick).count() % 2 == 1);
+        if on { '‾' } else { '_' }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::run, Circuit};
+
+    #[test]
+    fn a_mux_glitches_until_it_has_the_consensus_term() {
+        // Y = A.S + B.!S, where S falling turns one And off a tick before the Not turns the other on
+        let mut circuit = Circuit::new(1);
+        let text = run(&mut circuit, &mut Vec::new(), &["INPUT; A", "INPUT; B", "INPUT; S", "NOT 2", "AND 0 2", "AND 1 3", "OR 4 5", "OUTPUT 6; Y", "HAZARDS"]);
+        assert_eq!(text, "\
+A static-1 hazard on Y when S falls with A=1 B=1, reconverging at Or 6
+    Y ‾‾‾‾_‾‾  (ticks 0 to 6)
+Y: 1 static-1, 0 static-0 and 0 dynamic hazard(s)
+");
+
+        // A.B holds Y on while S changes
+        let text = run(&mut circuit, &mut Vec::new(), &["AND 0 1", "EDIT 6 OR 4 5 8", "HAZARDS"]);
+        assert_eq!(text, "No Output glitched for any of the 24 single Input change(s)\n");
+    }
+}
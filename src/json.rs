@@ -0,0 +1,635 @@
+use std::{fs::File, io::{Read, Write}};
+
+use crate::{invalid_data, logic4::Level, parse_gate_name, Circuit, Gate, IC};
+
+// Circuits are written as objects holding their gates and ICs, with each gate on its own line:
+//   {"id": 1, "name": "Half Adder", "id_counter": 4, "four_valued": false, "gates": [
+//     {"id": 2, "type": "Xor", "label": "Sum", "state": false, "inputs": [0, 1]}, ...], "ics": [
+//     {"circuit": {...}, "inputs": [{"external": 0, "internal": 0}, ...], "outputs": [...]}]}
+// Gates that have a level other than X, from SET or four valued logic, have a "level" too.
+// Where each input is looked for is found again from the ids, so it is only written, as "indexes"
+// or an "external_index" and "internal_index" on a port, when it isn't there, like `@` in a `.lo`.
+// A catalogue is `{"circuits": [...]}`. Snapshots only keep the states, levels and logic mode,
+// so they can be put back onto the same circuit to carry on a simulation where it was left.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => Option::None,
+        }
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => Option::None,
+        }
+    }
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Json::Number(n) if n.fract() == 0.0 && *n >= 0.0 && *n <= u32::MAX as f64 => Some(*n as u32),
+            _ => Option::None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => Option::None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => Option::None,
+        }
+    }
+
+    // Objects and arrays that only hold plain values go on one line, anything else is spread out
+    pub fn write(&self, buf: &mut String, indent: usize) {
+        match self {
+            Json::Null => buf.push_str("null"),
+            Json::Bool(b) => buf.push_str(if *b { "true" } else { "false" }),
+            Json::Number(n) => buf.push_str(&n.to_string()),
+            Json::String(s) => write_string(s, buf),
+            Json::Array(items) => {
+                if items.is_empty() {
+                    buf.push_str("[]");
+                    return;
+                }
+                let flat = self.is_flat();
+                buf.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        buf.push(',');
+                        if flat { buf.push(' ') }
+                    }
+                    if !flat { new_line(buf, indent + 1) }
+                    item.write(buf, indent + 1);
+                }
+                if !flat { new_line(buf, indent) }
+                buf.push(']');
+            }
+            Json::Object(fields) => {
+                if fields.is_empty() {
+                    buf.push_str("{}");
+                    return;
+                }
+                let flat = self.is_flat();
+                buf.push('{');
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        buf.push(',');
+                        if flat { buf.push(' ') }
+                    }
+                    if !flat { new_line(buf, indent + 1) }
+                    write_string(key, buf);
+                    buf.push_str(": ");
+                    value.write(buf, indent + 1);
+                }
+                if !flat { new_line(buf, indent) }
+                buf.push('}');
+            }
+        }
+    }
+    // Everything on one line, for protocols that send one value per line
+    pub fn compact(&self) -> String {
+        match self {
+            Json::Array(items) => format!("[{}]", items.iter().map(|i| i.compact()).collect::<Vec<String>>().join(",")),
+            Json::Object(fields) => {
+                let fields: Vec<String> = fields.iter().map(|(k, v)| {
+                    let mut key = String::new();
+                    write_string(k, &mut key);
+                    format!("{}:{}", key, v.compact())
+                }).collect();
+                format!("{{{}}}", fields.join(","))
+            }
+            _ => self.to_string(),
+        }
+    }
+    fn is_flat(&self) -> bool {
+        match self {
+            Json::Array(items) => items.iter().all(|i| !matches!(i, Json::Array(_) | Json::Object(_))),
+            Json::Object(fields) => fields.iter().all(|(_, v)| match v {
+                Json::Object(_) => false,
+                Json::Array(_) => v.is_flat(),
+                _ => true,
+            }),
+            _ => true,
+        }
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = String::new();
+        self.write(&mut buf, 0);
+        write!(f, "{}", buf)
+    }
+}
+
+fn new_line(buf: &mut String, indent: usize) {
+    buf.push('\n');
+    for _ in 0..indent {
+        buf.push_str("  ");
+    }
+}
+
+fn write_string(s: &str, buf: &mut String) {
+    buf.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            c if (c as u32) < 0x20 => buf.push_str(&format!("\\u{:04x}", c as u32)),
+            c => buf.push(c),
+        }
+    }
+    buf.push('"');
+}
+
+pub fn parse(text: &str) -> std::io::Result<Json> {
+    let mut parser = Parser { chars: text.chars().collect(), position: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position < parser.chars.len() {
+        return Err(parser.error("Expected the end of the file"));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> std::io::Error {
+        let line = self.chars[..self.position.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1;
+        invalid_data(format!("Line {}: {}", line, message))
+    }
+    fn skip_whitespace(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.get(self.position).copied();
+        self.position += 1;
+        c
+    }
+    fn expect(&mut self, word: &str) -> std::io::Result<()> {
+        for c in word.chars() {
+            if self.next() != Some(c) {
+                return Err(self.error(&format!("Expected \"{}\"", word)));
+            }
+        }
+        Ok(())
+    }
+    fn value(&mut self) -> std::io::Result<Json> {
+        self.skip_whitespace();
+        match self.chars.get(self.position) {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.position) == Some(&']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => continue,
+                        Some(']') => return Ok(Json::Array(items)),
+                        _ => return Err(self.error("Expected \",\" or \"]\"")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.get(self.position) == Some(&'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.get(self.position) != Some(&'"') {
+                        return Err(self.error("Expected a key"));
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    if self.next() != Some(':') {
+                        return Err(self.error("Expected \":\""));
+                    }
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => continue,
+                        Some('}') => return Ok(Json::Object(fields)),
+                        _ => return Err(self.error("Expected \",\" or \"}\"")),
+                    }
+                }
+            }
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let start = self.position;
+                while self.position < self.chars.len() && matches!(self.chars[self.position], '-' | '+' | '.' | 'e' | 'E' | '0'..='9') {
+                    self.position += 1;
+                }
+                let text: String = self.chars[start..self.position].iter().collect();
+                text.parse::<f64>().map(Json::Number).map_err(|_| self.error(&format!("\"{}\" is not a number", text)))
+            }
+            Some(_) => Err(self.error("Expected a value")),
+            Option::None => Err(self.error("Expected a value but the file ended")),
+        }
+    }
+    fn string(&mut self) -> std::io::Result<String> {
+        self.position += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut code = self.hex()?;
+                        // Characters outside the first plane come as a pair of surrogates
+                        if (0xd800..0xdc00).contains(&code) && self.chars.get(self.position) == Some(&'\\') {
+                            self.position += 1;
+                            self.expect("u")?;
+                            let low = self.hex()?;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                        }
+                        s.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    _ => return Err(self.error("Unknown escape in string")),
+                },
+                Some(c) => s.push(c),
+                Option::None => return Err(self.error("String is never closed")),
+            }
+        }
+    }
+    fn hex(&mut self) -> std::io::Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16)).ok_or_else(|| self.error("Expected 4 hex digits"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+}
+
+fn read_file(fp: &str) -> std::io::Result<Json> {
+    let mut text = String::new();
+    File::open(fp)?.read_to_string(&mut text)?;
+    parse(&text)
+}
+
+fn write_file(value: &Json, fp: &str) -> std::io::Result<()> {
+    let mut file = File::create(fp)?;
+    writeln!(file, "{}", value)
+}
+
+pub fn circuit_to_json(circuit: &Circuit) -> Json {
+    let gates = circuit.gates.iter().enumerate().map(|(index, g)| {
+        let mut fields = vec![
+            ("id".to_string(), Json::Number(g.id as f64)),
+            ("type".to_string(), Json::String(format!("{:?}", g.gate_type))),
+            ("label".to_string(), g.label.clone().map(Json::String).unwrap_or(Json::Null)),
+            ("state".to_string(), Json::Bool(g.state)),
+            ("inputs".to_string(), Json::Array(g.inputs.iter().map(|i| Json::Number(i.1 as f64)).collect())),
+        ];
+        // Only Wires that are pulled have a pull
+        if let Some(pull) = g.pull {
+            fields.push(("pull".to_string(), Json::String(String::from(if pull { "up" } else { "down" }))));
+        }
+        if let Some(delay) = g.delay {
+            fields.push(("delay".to_string(), Json::Number(delay as f64)));
+        }
+        if g.level != Level::X {
+            fields.push(("level".to_string(), Json::String(g.level.to_string())));
+        }
+        if g.inputs.iter().any(|i| i.0 != circuit.index_of(i.1, index)) {
+            fields.push(("indexes".to_string(), Json::Array(g.inputs.iter().map(|i| Json::Number(i.0 as f64)).collect())));
+        }
+        Json::Object(fields)
+    }).collect();
+    let ports = |ports: &[crate::Port], inner: &Circuit| Json::Array(ports.iter().map(|p| {
+        let mut fields = vec![
+            ("external".to_string(), Json::Number(p[0].1 as f64)),
+            ("internal".to_string(), Json::Number(p[1].1 as f64)),
+        ];
+        if p[0].0 != circuit.index_of(p[0].1, 0) {
+            fields.push(("external_index".to_string(), Json::Number(p[0].0 as f64)));
+        }
+        if p[1].0 != inner.index_of(p[1].1, 0) {
+            fields.push(("internal_index".to_string(), Json::Number(p[1].0 as f64)));
+        }
+        Json::Object(fields)
+    }).collect());
+    let ics = circuit.intergrated_circuits.iter().map(|ic| Json::Object(vec![
+        ("circuit".to_string(), circuit_to_json(&ic.circuit)),
+        ("inputs".to_string(), ports(&ic.inputs, &ic.circuit)),
+        ("outputs".to_string(), ports(&ic.outputs, &ic.circuit)),
+    ])).collect();
+    Json::Object(vec![
+        ("id".to_string(), Json::Number(circuit.id as f64)),
+        ("name".to_string(), circuit.name.clone().map(Json::String).unwrap_or(Json::Null)),
+        ("id_counter".to_string(), Json::Number(circuit.id_counter as f64)),
+        ("four_valued".to_string(), Json::Bool(circuit.four_valued)),
+        ("gates".to_string(), Json::Array(gates)),
+        ("ics".to_string(), Json::Array(ics)),
+    ])
+}
+
+pub fn field<'a>(value: &'a Json, key: &str) -> std::io::Result<&'a Json> {
+    value.get(key).ok_or_else(|| invalid_data(format!("Missing \"{}\" in {}", key, value)))
+}
+
+pub fn number(value: &Json, key: &str) -> std::io::Result<u32> {
+    field(value, key)?.as_u32().ok_or_else(|| invalid_data(format!("\"{}\" should be a whole number in {}", key, value)))
+}
+
+pub fn array<'a>(value: &'a Json, key: &str) -> std::io::Result<&'a [Json]> {
+    match value.get(key) {
+        Some(Json::Null) | Option::None => Ok(&[]),
+        Some(list) => list.as_array().ok_or_else(|| invalid_data(format!("\"{}\" should be a list in {}", key, value))),
+    }
+}
+
+pub fn text(value: &Json, key: &str) -> std::io::Result<Option<String>> {
+    match value.get(key) {
+        Some(Json::Null) | Option::None => Ok(Option::None),
+        Some(Json::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(invalid_data(format!("\"{}\" should be a string in {}", key, value))),
+    }
+}
+
+pub fn circuit_from_json(value: &Json) -> std::io::Result<Circuit> {
+    let mut circuit = Circuit::new(number(value, "id")?);
+    circuit.name = text(value, "name")?;
+    circuit.four_valued = value.get("four_valued").and_then(|f| f.as_bool()).unwrap_or(false);
+
+    let mut indexes = Vec::new();
+    for gate in array(value, "gates")? {
+        let type_name = field(gate, "type")?.as_str().unwrap_or_default();
+        let gate_type = parse_gate_name(type_name).ok_or_else(|| invalid_data(format!("\"{}\" is not a gate", type_name)))?;
+        let mut inputs = Vec::new();
+        for input in array(gate, "inputs")? {
+            inputs.push((0, input.as_u32().ok_or_else(|| invalid_data(format!("{} is not a gate id", input)))?));
+        }
+        let mut new_gate = Gate::new(gate_type, number(gate, "id")?, inputs, text(gate, "label")?);
+        new_gate.state = gate.get("state").and_then(|s| s.as_bool()).unwrap_or(false);
+        new_gate.pull = match text(gate, "pull")?.as_deref() {
+            Some("up") => Some(true),
+            Some("down") => Some(false),
+            Some(pull) => return Err(invalid_data(format!("\"{}\" is not a pull, it has to be up or down", pull))),
+            Option::None => Option::None,
+        };
+        new_gate.delay = match gate.get("delay") {
+            Some(Json::Null) | Option::None => Option::None,
+            Some(delay) => Some(delay.as_u32().ok_or_else(|| invalid_data(format!("{} is not a delay", delay)))?),
+        };
+        new_gate.level = level(gate)?;
+        if circuit.gates.iter().any(|g| g.id == new_gate.id) {
+            return Err(invalid_data(format!("Gate {} is in the circuit more than once", new_gate.id)));
+        }
+        indexes.push(match gate.get("indexes") {
+            Some(_) => {
+                let given = array(gate, "indexes")?.iter().map(|i| i.as_u32().map(|i| i as usize)).collect::<Option<Vec<usize>>>();
+                match given {
+                    Some(given) if given.len() == new_gate.inputs.len() => Some(given),
+                    _ => return Err(invalid_data(format!("Gate {} needs an index for each of its inputs", new_gate.id))),
+                }
+            }
+            Option::None => Option::None,
+        });
+        circuit.gates.push(new_gate);
+    }
+    for (gate_index, given) in indexes.into_iter().enumerate() {
+        for input_index in 0..circuit.gates[gate_index].inputs.len() {
+            let index = match &given {
+                Some(given) => given[input_index],
+                Option::None => circuit.index_of(circuit.gates[gate_index].inputs[input_index].1, gate_index),
+            };
+            circuit.gates[gate_index].inputs[input_index].0 = index;
+        }
+    }
+    let largest_id = circuit.gates.iter().map(|g| g.id + 1).max().unwrap_or(0);
+    circuit.id_counter = match value.get("id_counter") {
+        Some(_) => number(value, "id_counter")?.max(largest_id),
+        Option::None => largest_id,
+    };
+
+    for ic in array(value, "ics")? {
+        let inner = circuit_from_json(field(ic, "circuit")?)?;
+        let mut lists = Vec::new();
+        for key in ["inputs", "outputs"] {
+            let mut ports = Vec::new();
+            for port in array(ic, key)? {
+                let (external, internal) = (number(port, "external")?, number(port, "internal")?);
+                let external_index = match port.get("external_index") {
+                    Some(_) => number(port, "external_index")? as usize,
+                    Option::None => circuit.index_of(external, 0),
+                };
+                let internal_index = match port.get("internal_index") {
+                    Some(_) => number(port, "internal_index")? as usize,
+                    Option::None => inner.index_of(internal, 0),
+                };
+                ports.push([(external_index, external), (internal_index, internal)]);
+            }
+            lists.push(ports);
+        }
+        let outputs = lists.pop().unwrap_or_default();
+        let inputs = lists.pop().unwrap_or_default();
+        circuit.intergrated_circuits.push(IC::new(inner, inputs, outputs));
+    }
+    Ok(circuit)
+}
+
+pub fn export_json(circuit: &Circuit, fp: &str) -> std::io::Result<()> {
+    write_file(&circuit_to_json(circuit), fp)
+}
+
+pub fn export_catalogue_json(catalogue: &[Circuit], fp: &str) -> std::io::Result<()> {
+    write_file(&Json::Object(vec![("circuits".to_string(), Json::Array(catalogue.iter().map(circuit_to_json).collect()))]), fp)
+}
+
+// Takes either one circuit or a whole catalogue. The circuits are given new catalogue ids so
+// they don't clash with what is already there.
+pub fn import_json(fp: &str, id: &mut u32, catalogue: &mut Vec<Circuit>) -> std::io::Result<Vec<u32>> {
+    let value = read_file(fp)?;
+    let mut circuits = Vec::new();
+    if value.get("circuits").is_some() {
+        for circuit in array(&value, "circuits")? {
+            circuits.push(circuit_from_json(circuit)?);
+        }
+    } else {
+        circuits.push(circuit_from_json(&value)?);
+    }
+
+    let mut ids = Vec::new();
+    for mut circuit in circuits {
+        *id += 1;
+        circuit.id = *id;
+        ids.push(circuit.id);
+        catalogue.push(circuit);
+    }
+    Ok(ids)
+}
+
+// A gate's level, which is X when it isn't there
+fn level(gate: &Json) -> std::io::Result<Level> {
+    match text(gate, "level")? {
+        Some(level) => Level::parse(&level).ok_or_else(|| invalid_data(format!("\"{}\" is not a level, it has to be 0, 1, X or Z", level))),
+        Option::None => Ok(Level::X),
+    }
+}
+
+fn snapshot(circuit: &Circuit) -> Json {
+    let states = circuit.gates.iter().map(|g| {
+        let mut fields = vec![
+            ("id".to_string(), Json::Number(g.id as f64)),
+            ("state".to_string(), Json::Bool(g.state)),
+        ];
+        if g.level != Level::X {
+            fields.push(("level".to_string(), Json::String(g.level.to_string())));
+        }
+        Json::Object(fields)
+    }).collect();
+    Json::Object(vec![
+        ("name".to_string(), circuit.name.clone().map(Json::String).unwrap_or(Json::Null)),
+        ("four_valued".to_string(), Json::Bool(circuit.four_valued)),
+        ("states".to_string(), Json::Array(states)),
+        ("ics".to_string(), Json::Array(circuit.intergrated_circuits.iter().map(|ic| snapshot(&ic.circuit)).collect())),
+    ])
+}
+
+fn restore(circuit: &mut Circuit, value: &Json) -> std::io::Result<()> {
+    for gate in array(value, "states")? {
+        let id = number(gate, "id")?;
+        let state = field(gate, "state")?.as_bool().ok_or_else(|| invalid_data(format!("\"state\" should be true or false in {}", gate)))?;
+        if !circuit.set_component(id, state) {
+            return Err(invalid_data(format!("Gate {} isn't in the circuit", id)));
+        }
+        // Setting it left the level following the state, which isn't always what was saved
+        if let Some(g) = circuit.gates.iter_mut().find(|g| g.id == id) {
+            g.level = level(gate)?;
+        }
+    }
+    circuit.four_valued = value.get("four_valued").and_then(|f| f.as_bool()).unwrap_or(false);
+    let ics = array(value, "ics")?;
+    if ics.len() != circuit.intergrated_circuits.len() {
+        return Err(invalid_data(format!("The snapshot has {} IC(s) but the circuit has {}", ics.len(), circuit.intergrated_circuits.len())));
+    }
+    for (ic, value) in circuit.intergrated_circuits.iter_mut().zip(ics) {
+        restore(&mut ic.circuit, value)?;
+    }
+    Ok(())
+}
+
+pub fn save_snapshot(circuit: &Circuit, fp: &str) -> std::io::Result<()> {
+    write_file(&snapshot(circuit), fp)
+}
+
+// Nothing is changed unless every state in the snapshot has somewhere to go
+pub fn restore_snapshot(circuit: &mut Circuit, fp: &str) -> std::io::Result<()> {
+    let value = read_file(fp)?;
+    let mut restored = circuit.clone();
+    restore(&mut restored, &value)?;
+    *circuit = restored;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{nested, temp_path};
+
+    // Through the text, the way EXPORT JSON and IMPORT JSON go
+    fn through_text(circuit: &Circuit) -> Circuit {
+        circuit_from_json(&parse(&circuit_to_json(circuit).to_string()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn circuits_come_back_from_json_the_same() {
+        let mut circuit = nested();
+        circuit.name = Some(String::from("A \"quoted\" name\n"));
+        circuit.set_component(0, true);
+        circuit.step();
+        assert_eq!(through_text(&circuit), circuit);
+
+        circuit.set_four_valued(true);
+        circuit.set_level(0, Level::Z);
+        circuit.step();
+        circuit.step();
+        assert_eq!(through_text(&circuit), circuit);
+    }
+
+    #[test]
+    fn a_snapshot_puts_the_states_back() {
+        let mut circuit = nested();
+        circuit.set_component(0, true);
+        circuit.step();
+        circuit.step();
+        let saved = circuit.clone();
+        let fp = temp_path("snapshot.json");
+        save_snapshot(&circuit, &fp).unwrap();
+
+        circuit.set_four_valued(true);
+        circuit.set_level(0, Level::Z);
+        circuit.step();
+        assert_ne!(circuit, saved);
+        restore_snapshot(&mut circuit, &fp).unwrap();
+        assert_eq!(circuit, saved);
+
+        // A snapshot of something else changes nothing
+        let mut other = Circuit::new(3);
+        other.add_component(crate::GateType::Input, Vec::new(), Option::None);
+        let before = other.clone();
+        let error = restore_snapshot(&mut other, &fp).unwrap_err();
+        assert_eq!(error.to_string(), "Gate 2 isn't in the circuit");
+        assert_eq!(other, before);
+        std::fs::remove_file(&fp).unwrap();
+    }
+
+    #[test]
+    fn bad_json_is_an_error() {
+        for (text, expected) in [
+            ("{\"id\": 1, \"gates\": [{\"id\": 0, \"type\": \"Flop\", \"inputs\": []}], \"ics\": []}", "\"Flop\" is not a gate"),
+            ("{\"id\": 1, \"gates\": [{\"id\": 0, \"type\": \"Wire\", \"inputs\": [], \"pull\": \"sideways\"}], \"ics\": []}", "\"sideways\" is not a pull, it has to be up or down"),
+            ("{\"id\": 1, \"gates\": [{\"id\": 0, \"type\": \"Input\", \"inputs\": [], \"level\": \"2\"}], \"ics\": []}", "\"2\" is not a level, it has to be 0, 1, X or Z"),
+            ("{\"id\": 1, \"gates\": [{\"id\": 0, \"type\": \"Not\", \"inputs\": [0], \"indexes\": []}], \"ics\": []}", "Gate 0 needs an index for each of its inputs"),
+            ("{\"id\": 1, \"gates\": [{\"id\": 0, \"type\": \"Input\", \"inputs\": []}, {\"id\": 0, \"type\": \"Not\", \"inputs\": [0]}], \"ics\": []}", "Gate 0 is in the circuit more than once"),
+        ] {
+            let error = parse(text).and_then(|value| circuit_from_json(&value)).map(|_| ()).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::Circuit;
+
+// Places the gates and ICs of a circuit in columns by how many gates they are after the Inputs,
+// for drawing the circuit out in 2D. ICs get a place of their own between the gates going into
+// them and the gates they drive.
+
+pub enum Part {
+    Gate(usize),        // Index in the circuit's gates
+    Ic(usize),          // Index in the circuit's ICs
+    Wire(usize, usize), // A wire passing through a column; Node it comes from; Output pin of that node
+}
+
+pub struct Node {
+    pub part: Part,
+    pub inputs: Vec<Option<(usize, usize)>>, // Node; Output pin of that node
+    pub column: usize,
+    pub row: usize,
+}
+
+pub struct Layout {
+    pub nodes: Vec<Node>,
+    pub columns: Vec<Vec<usize>>,
+}
+
+impl Layout {
+    pub fn new(circuit: &Circuit) -> Self {
+        let index: HashMap<u32, usize> = circuit.gates.iter().enumerate().map(|(i, g)| (g.id, i)).collect();
+        let mut nodes: Vec<Node> = circuit.gates.iter().enumerate().map(|(i, gate)| Node {
+            part: Part::Gate(i),
+            inputs: gate.inputs.iter().map(|input| index.get(&input.1).map(|n| (*n, 0))).collect(),
+            column: 0,
+            row: 0,
+        }).collect();
+
+        for (i, ic) in circuit.intergrated_circuits.iter().enumerate() {
+            let node = nodes.len();
+            nodes.push(Node {
+                part: Part::Ic(i),
+                inputs: ic.inputs.iter().map(|port| index.get(&port[0].1).map(|n| (*n, 0))).collect(),
+                column: 0,
+                row: 0,
+            });
+            for (pin, port) in ic.outputs.iter().enumerate() {
+                if let Some(gate) = index.get(&port[0].1) {
+                    nodes[*gate].inputs.push(Some((node, pin)));
+                }
+            }
+        }
+
+        let predecessors: Vec<Vec<usize>> = nodes.iter().map(|n| n.inputs.iter().flatten().map(|i| i.0).collect()).collect();
+        let depths = longest_paths(&predecessors);
+        let mut columns: Vec<Vec<usize>> = vec![Vec::new(); depths.iter().max().map(|d| d + 1).unwrap_or(0)];
+        for (node, depth) in depths.iter().enumerate() {
+            nodes[node].column = *depth;
+            columns[*depth].push(node);
+        }
+
+        // Wires that skip over columns get a place in each column they go through so that they
+        // have room to pass the gates there
+        let mut predecessors = predecessors;
+        for node in 0..nodes.len() {
+            for pin in 0..nodes[node].inputs.len() {
+                let Some((source, source_pin)) = nodes[node].inputs[pin] else { continue };
+                let mut previous = (source, source_pin);
+                let (start, end) = (nodes[source].column + 1, nodes[node].column);
+                for (column, passing) in columns.iter_mut().enumerate().take(end).skip(start) {
+                    let wire = nodes.len();
+                    nodes.push(Node { part: Part::Wire(source, source_pin), inputs: vec![Some(previous)], column, row: 0 });
+                    predecessors.push(vec![previous.0]);
+                    passing.push(wire);
+                    previous = (wire, 0);
+                }
+                if previous.0 != source {
+                    nodes[node].inputs[pin] = Some(previous);
+                    predecessors[node] = nodes[node].inputs.iter().flatten().map(|i| i.0).collect();
+                }
+            }
+        }
+
+        // Each column is sorted by the average row of what goes into it, to uncross the wires a bit
+        for (column, order_in_column) in columns.iter_mut().enumerate() {
+            if column > 0 {
+                let mut order: Vec<(f32, usize)> = order_in_column.iter().map(|node| {
+                    let rows: Vec<f32> = predecessors[*node].iter().filter(|p| nodes[**p].column < column).map(|p| nodes[*p].row as f32).collect();
+                    let average = if rows.is_empty() {f32::MAX} else {rows.iter().sum::<f32>() / rows.len() as f32};
+                    (average, *node)
+                }).collect();
+                order.sort_by(|a, b| a.0.total_cmp(&b.0));
+                *order_in_column = order.into_iter().map(|o| o.1).collect();
+            }
+            for (row, node) in order_in_column.iter().enumerate() {
+                nodes[*node].row = row;
+            }
+        }
+
+        Self { nodes, columns }
+    }
+}
+
+// The longest chain of predecessors behind each node. Loops are broken at the wire that goes back
+// to a node that is still being worked out, so flip flops don't go on forever.
+pub fn longest_paths(predecessors: &[Vec<usize>]) -> Vec<usize> {
+    search(predecessors, &vec![1; predecessors.len()]).0.iter().map(|d| *d as usize - 1).collect()
+}
+
+// The longest time to get through to each node when every node takes its weight to pass a change
+// on, counting its own weight, along with the predecessor that path comes through. Loops are
+// broken the same way as `longest_paths`.
+pub fn slowest_paths(predecessors: &[Vec<usize>], weights: &[u64]) -> (Vec<u64>, Vec<Option<usize>>) {
+    let (arrivals, slowest, _) = search(predecessors, weights);
+    (arrivals, slowest)
+}
+
+// The nodes that loops get broken at by `longest_paths`. Reading these as they were the step
+// before, instead of as they are now, leaves no loops.
+pub fn feedback(predecessors: &[Vec<usize>]) -> Vec<bool> {
+    search(predecessors, &vec![1; predecessors.len()]).2
+}
+
+// What each gate reads from, by index. Gates driven by an IC read from every gate going into the
+// IC instead of their own inputs, as the IC could connect any of them through.
+pub fn gate_predecessors(circuit: &Circuit) -> Vec<Vec<usize>> {
+    let index: HashMap<u32, usize> = circuit.gates.iter().enumerate().map(|(i, g)| (g.id, i)).collect();
+    let mut predecessors: Vec<Vec<usize>> = circuit.gates.iter()
+        .map(|g| g.inputs.iter().filter_map(|i| index.get(&i.1).copied()).collect())
+        .collect();
+    for ic in circuit.intergrated_circuits.iter() {
+        let inputs: Vec<usize> = ic.inputs.iter().filter_map(|p| index.get(&p[0].1).copied()).collect();
+        for port in ic.outputs.iter() {
+            if let Some(gate) = index.get(&port[0].1) {
+                predecessors[*gate] = inputs.clone();
+            }
+        }
+    }
+    predecessors
+}
+
+fn search(predecessors: &[Vec<usize>], weights: &[u64]) -> (Vec<u64>, Vec<Option<usize>>, Vec<bool>) {
+    const NEW: u8 = 0;
+    const VISITING: u8 = 1;
+    const DONE: u8 = 2;
+
+    let mut depths = vec![0; predecessors.len()];
+    let mut slowest = vec![Option::None; predecessors.len()];
+    let mut feedback = vec![false; predecessors.len()];
+    let mut status = vec![NEW; predecessors.len()];
+    for start in 0..predecessors.len() {
+        if status[start] != NEW {
+            continue;
+        }
+        status[start] = VISITING;
+        let mut stack = vec![(start, 0)]; // Node; Next predecessor to look at
+        while let Some((node, next)) = stack.last().copied() {
+            if next < predecessors[node].len() {
+                stack.last_mut().unwrap().1 += 1;
+                let predecessor = predecessors[node][next];
+                if status[predecessor] == NEW {
+                    status[predecessor] = VISITING;
+                    stack.push((predecessor, 0));
+                } else if status[predecessor] == VISITING {
+                    feedback[predecessor] = true;
+                }
+                continue;
+            }
+            slowest[node] = predecessors[node].iter()
+                .filter(|p| status[**p] == DONE)
+                .copied()
+                .reduce(|a, b| if depths[b] > depths[a] { b } else { a });
+            depths[node] = weights[node] + slowest[node].map(|p| depths[p]).unwrap_or(0);
+            status[node] = DONE;
+            stack.pop();
+        }
+    }
+    (depths, slowest, feedback)
+}
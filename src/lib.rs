@@ -0,0 +1,1928 @@
+use logic4::Level;
+use std::{collections::HashMap, fmt::Display, fs::File, io::{Read, Write}, sync::atomic::{AtomicUsize, Ordering}};
+
+mod aiger;
+mod atpg;
+mod blif;
+mod dot;
+mod event;
+mod fault;
+mod hazard;
+mod ffi;
+mod json;
+mod layout;
+mod library;
+mod logic4;
+mod logisim;
+mod packed;
+mod parallel;
+mod power;
+mod schematic;
+mod server;
+mod stats;
+mod svg;
+mod tape;
+mod timing;
+mod tui;
+mod vectors;
+mod verilog;
+
+const ORCODE: &str = "OR";
+const ANDCODE: &str = "AND";
+const NOTCODE: &str = "NOT";
+const NORCODE: &str = "NOR";
+const XORCODE: &str = "XOR";
+const NANDCODE: &str = "NAND";
+const NXORCODE: &str = "NXOR";
+const INPUTCODE: &str = "INPUT";
+const OUTPUTCODE: &str = "OUTPUT";
+const BUFFERCODE: &str = "BUFFER";
+const TRISTATECODE: &str = "TRISTATE";
+const WIRECODE: &str = "WIRE";
+
+const DELETECOMPONENTCODE: &str = "DEL";
+const MANUALSETSTATECODE: &str = "SET";
+const NEWCIRCUITCODE: &str = "NEW";
+const COMPILECIRCUITCODE: &str = "COMPILE";
+const IMPORTCIRCUITCODE: &str = "IMPORT";
+const EDITCOMPONENTCODE: &str = "EDIT";
+const NAMECIRCUITCODE: &str = "NAME";
+const SAVECIRCUITCODE: &str = "SAVE";
+const LOADCIRCUITCODE: &str = "LOAD";
+const LOADICCODE: &str = "IC";
+const SAVELIBRARYCODE: &str = "SAVELIB";
+const LOADLIBRARYCODE: &str = "LOADLIB";
+const SCHEMATICCODE: &str = "SCHEMATIC";
+const EXPORTCODE: &str = "EXPORT";
+const SNAPSHOTCODE: &str = "SNAPSHOT";
+const RESTORECODE: &str = "RESTORE";
+const TRUTHCODE: &str = "TRUTH";
+const EQUIVCODE: &str = "EQUIV";
+const BENCHCODE: &str = "BENCH";
+const STEPCODE: &str = "STEP";
+const LOGICCODE: &str = "LOGIC";
+const PULLCODE: &str = "PULL";
+const NETSCODE: &str = "NETS";
+const DELAYCODE: &str = "DELAY";
+const RUNCODE: &str = "RUN";
+const TIMINGCODE: &str = "TIMING";
+const HAZARDSCODE: &str = "HAZARDS";
+const FAULTSIMCODE: &str = "FAULTSIM";
+const ATPGCODE: &str = "ATPG";
+const POWERCODE: &str = "POWER";
+const RESETCODE: &str = "RESET";
+const STATSCODE: &str = "STATS";
+
+const VERILOGCODE: &str = "VERILOG";
+const LOGISIMCODE: &str = "LOGISIM";
+const BLIFCODE: &str = "BLIF";
+const AIGERCODE: &str = "AIGER";
+const JSONCODE: &str = "JSON";
+const DOTCODE: &str = "DOT";
+const SVGCODE: &str = "SVG";
+const STATECODE: &str = "STATE";
+const CATALOGUECODE: &str = "CATALOGUE";
+const TAPECODE: &str = "TAPE";
+
+const THREADSFLAG: &str = "--threads";
+
+const ENDPOINT: u8 = b';';
+const WHITESPACE: u8 = b' ';
+
+// How many threads STEP shares the gates between, set with `--threads n`
+static THREADS: AtomicUsize = AtomicUsize::new(1);
+
+// Everything the `simlo` binary does, given its arguments without the program name
+pub fn cli(mut args: Vec<String>) {
+    if args.first().map(|a| a.as_str()) == Some(THREADSFLAG) {
+        match args.get(1).map(|n| n.parse::<usize>()) {
+            Some(Ok(threads)) if threads > 0 => THREADS.store(threads, Ordering::Relaxed),
+            _ => {
+                println!("{} needs a number of threads", THREADSFLAG);
+                return;
+            }
+        }
+        args.drain(0..2);
+    }
+    if args.first().map(|a| a.as_str()) == Some("tui") {
+        if let Err(e) = tui::run(args.get(1).map(|a| a.as_str())) {
+            println!("{}", e);
+        }
+        return;
+    }
+    if args.first().map(|a| a.as_str()) == Some("serve") {
+        if let Err(e) = server::run(&args[1..]) {
+            println!("{}", e);
+        }
+        return;
+    }
+    create_circuit(&mut 0, &mut Vec::new());
+}
+
+fn create_circuit(id: &mut u32, catalogue: &mut Vec<Circuit>) {
+
+    *id += 1;
+
+    let mut circuit = Circuit::new(*id);
+
+    'game: loop {
+        circuit.step();
+
+        let command = input(&format!("{}>", circuit.id));
+        if command == "HLT" {
+            break 'game;
+        }
+        if command == SCHEMATICCODE {
+            schematic::show_schematic(&circuit);
+        }
+        if command.split(' ').next() == Some(NEWCIRCUITCODE) {
+            println!("New circuit");
+            create_circuit(id, catalogue);
+            continue;
+        }
+        if let Err(e) = run_command(command, &mut circuit, id, catalogue, &mut std::io::stdout()) {
+            println!("{}", e);
+        }
+    }
+}
+
+// Runs any command that doesn't take over the terminal, writing what it has to say to `out`
+fn run_command(command: String, circuit: &mut Circuit, id: &mut u32, catalogue: &mut Vec<Circuit>, out: &mut impl Write) -> std::io::Result<()> {
+    if command == "DISPLAY" {
+        circuit.display(out)?;
+    }
+    if command == "DISPLIO" {
+        circuit.displio(out)?;
+    }
+    if command == CATALOGUECODE {
+        for c in catalogue.iter() {
+            if let Some(name) = &c.name {
+                writeln!(out, "[{}] {} - {} Gates(s)", c.id, name, c.gates.len())?
+            }
+            else {
+                writeln!(out, "[{}] - {} Gate(s)", c.id, c.gates.len())?
+            }
+        }
+    }
+    if command == "HELP" {
+        writeln!(out, "DEL [id..]                             - Deletes the given components")?;
+        writeln!(out, "SET [id..] (TRUE/FALSE)                - Sets the state of the given components")?;
+        writeln!(out, "SET [id..] (0/1/X/Z)                   - Sets the level of the given components in four valued logic")?;
+        writeln!(out, "LOGIC (2/4)                            - Switches between two valued and four valued (0, 1, X, Z) logic")?;
+        writeln!(out, "TRISTATE [data] [enable]; [Label]      - Adds a gate that drives its data while enabled and lets go otherwise")?;
+        writeln!(out, "WIRE [id..]; [Label]                   - Adds a net that every given gate drives, usually Tristates")?;
+        writeln!(out, "PULL [id] [UP/DOWN/NONE]               - Sets what a Wire is when nothing is driving it")?;
+        writeln!(out, "NETS                                   - Shows every Wire with its drivers and any contention between them")?;
+        writeln!(out, "NEW                                    - Starts a new circuit")?;
+        writeln!(out, "COMPILE                                - Adds the circuit to the catalogue")?;
+        writeln!(out, "COMPILE TAPE (runs)                    - Compiles the circuit into a levelled tape and runs it, once unless told otherwise")?;
+        writeln!(out, "STEP (steps)                           - Steps the circuit, on as many threads as --threads gave")?;
+        writeln!(out, "BENCH (steps)                          - Times stepping the circuit against running its compiled tape")?;
+        writeln!(out, "IMPORT [id]                            - Adds a circuit to the current circuit")?;
+        writeln!(out, "EDIT [id] [Gate Type] [id...]; [Label] - Swaps components with a new one")?;
+        writeln!(out, "[Gate Type] [id..] DELAY [ticks]       - Gives a new or edited gate a delay other than the default for its type")?;
+        writeln!(out, "STATS (id)                             - Shows gate and transistor counts, fan-in, fan-out, depth, ICs and unused gates")?;
+        writeln!(out, "TIMING                                 - Shows how long each Output takes to settle, the critical path and the depth of the gates")?;
+        writeln!(out, "FAULTSIM [file path]                   - Runs the test vectors in a file against every stuck-at fault and shows the coverage")?;
+        writeln!(out, "ATPG [file path]                       - Makes test vectors that find every stuck-at fault that can be found and saves them")?;
+        writeln!(out, "POWER (RESET)                          - Shows the gates that toggled most and the power they used, or starts counting again")?;
+        writeln!(out, "POWER [Gate Type] [weight]             - Sets how much a type of gate weighs in the power, like its capacitance")?;
+        writeln!(out, "HAZARDS (id)                           - Flips each Input from every vector and shows the Outputs that glitch, for this or a catalogue circuit")?;
+        writeln!(out, "RUN (ticks) (Input id..)               - Settles the circuit with gate delays, flips the Inputs and shows the Outputs change in time")?;
+        writeln!(out, "NAME [name]                            - Sets the name of the circuit")?;
+        writeln!(out, "SAVE [file path]                       - Saves a circuit to the given location")?;
+        writeln!(out, "LOAD [file path]                       - Loads the circuit from a file into the catalogue")?;
+        writeln!(out, "SAVELIB [file path]                    - Saves every named circuit in the catalogue to a library file")?;
+        writeln!(out, "LOADLIB [file path]                    - Loads every circuit in a library file into the catalogue")?;
+        writeln!(out, "IMPORT VERILOG [file path]             - Adds the modules in a gate level Verilog file to the catalogue")?;
+        writeln!(out, "IMPORT LOGISIM [file path]             - Adds the circuits in a Logisim .circ file to the catalogue")?;
+        writeln!(out, "IMPORT BLIF [file path]                - Adds the models in a BLIF file to the catalogue")?;
+        writeln!(out, "IMPORT AIGER [file path]               - Adds an ASCII or binary AIGER and-inverter graph to the catalogue")?;
+        writeln!(out, "IMPORT JSON [file path]                - Adds a JSON circuit or catalogue to the catalogue")?;
+        writeln!(out, "EXPORT VERILOG [file path]             - Writes the circuit as a structural Verilog module")?;
+        writeln!(out, "EXPORT DOT (STATE) [file path]         - Writes the gates as a Graphviz graph, coloured by state with STATE")?;
+        writeln!(out, "EXPORT SVG [file path]                 - Draws the circuit as a schematic")?;
+        writeln!(out, "EXPORT BLIF [file path]                - Writes the circuit as BLIF models for logic synthesis tools")?;
+        writeln!(out, "EXPORT AIGER [file path]               - Writes the circuit as an and-inverter graph, in ASCII for .aag files")?;
+        writeln!(out, "EXPORT JSON (CATALOGUE) [file path]    - Writes the circuit, or the whole catalogue, as JSON with gate states")?;
+        writeln!(out, "SNAPSHOT [file path]                   - Saves the state of every gate so the simulation can be carried on later")?;
+        writeln!(out, "RESTORE [file path]                    - Puts the states from a snapshot back onto the circuit")?;
+        writeln!(out, "TRUTH (file path)                      - Shows or saves the truth table, working through 64 input vectors at a time")?;
+        writeln!(out, "EQUIV [id]                             - Checks every input vector gives the same outputs as a circuit in the catalogue")?;
+        writeln!(out, "HLT                                    - Quits the current circuit and goes back to the previous one")?;
+        writeln!(out, "DISPLAY                                - Shows the status of all the gates in the circuit")?;
+        writeln!(out, "DISPLIO                                - Shows the status of all the input and output components in the circuit")?;
+        writeln!(out, "CATALOGUE                              - Shows the circuits in the catalogue")?;
+        writeln!(out, "SCHEMATIC                              - Draws the circuit in the terminal")?;
+    }
+
+
+    // New component format is [Gate Type] [Inputs]; [LABEL]
+    let mut buffer = Reader::new(command);
+    let mut char = buffer.pop();
+    let mut word: Vec<u8> = Vec::new();
+    
+    let mut sentence: Vec<Vec<u8>> = Vec::new();
+    let mut note: Vec<Vec<u8>> = Vec::new();
+
+    let mut note_mode = false;
+
+    while let Some(c) = char {
+        match c {
+            ENDPOINT    => if note_mode {word.push(c)} else {sentence.push(word.clone()); word.clear(); note_mode = true},
+            WHITESPACE  => { if !word.is_empty() { if !note_mode {sentence.push(word.clone())} else {note.push(word.clone());}; word.clear() }},
+            _           => word.push(c),
+        }
+        char = buffer.pop();
+    }
+
+    if !note_mode {sentence.push(word.clone())} else {note.push(word.clone());}; word.clear();
+
+    if !sentence.is_empty() {
+        let word = sentence.remove(0);
+        if let Ok(t) = String::from_utf8(word) {
+
+            if t == MANUALSETSTATECODE {
+                let mut stuff_to_set = Vec::new();
+                for word in sentence[0..(sentence.len()-1)].iter() {
+                    if let Ok(s) = String::from_utf8(word.to_vec()) {
+                        if let Ok(id) = s.parse::<u32>() {
+                            stuff_to_set.push(id);
+                        }
+                    }
+                }
+                let states = String::from_utf8(sentence.last().unwrap().to_vec());
+
+                if let Ok(s) = states {
+                    if circuit.four_valued {
+                        let Some(level) = Level::parse(&s) else {
+                            writeln!(out, "Invalid state: \"{}\"", s)?;
+                            return Ok(());
+                        };
+                        for id in stuff_to_set {
+                            if !circuit.set_level(id, level) {
+                                writeln!(out, "Component {} does not exist", id)?;
+                            }
+                            else {
+                                writeln!(out, "Set {} to {}", id, level)?;
+                            }
+                        }
+                        return Ok(());
+                    }
+                    let state = if [String::from("TRUE"), String::from("ON")].contains(&s) {
+                        Some(true)
+                    } else if [String::from("FALSE"), String::from("OFF")].contains(&s) {
+                        Some(false)
+                    } else { writeln!(out, "Invalid state: \"{}\"", s)?; Option::None};
+                    if let Some(state) = state {
+                        for id in stuff_to_set {
+                            if !circuit.set_component(id, state) {
+                                writeln!(out, "Component {} does not exist", id)?;
+                            }
+                            else {
+                                writeln!(out, "Set {} to {}", id, state)?;
+                            }
+                        }
+                    } else {writeln!(out, "Invalid state\n")?}
+                } else {writeln!(out, "{:?}", states)?}
+
+            }
+            else if t == COMPILECIRCUITCODE && sentence.first().map(|w| w.as_slice()) == Some(TAPECODE.as_bytes()) {
+                let runs = match sentence.get(1).map(|w| String::from_utf8_lossy(w).parse::<u32>()) {
+                    Some(Ok(runs)) => runs,
+                    Some(Err(_)) => {
+                        writeln!(out, "The number of runs has to be a number")?;
+                        return Ok(());
+                    }
+                    Option::None => 1,
+                };
+                let mut tape = tape::Tape::compile(circuit);
+                for _ in 0..runs {
+                    tape.run();
+                }
+                tape.write_back(circuit);
+                writeln!(out, "Compiled into {}", tape.describe())?;
+                writeln!(out, "Ran the tape {} time(s)", runs)?;
+            }
+            else if t == STEPCODE {
+                let steps = match sentence.first().filter(|w| !w.is_empty()).map(|w| String::from_utf8_lossy(w).parse::<u32>()) {
+                    Some(Ok(steps)) => steps,
+                    Some(Err(_)) => {
+                        writeln!(out, "The number of steps has to be a number")?;
+                        return Ok(());
+                    }
+                    Option::None => 1,
+                };
+                // The threaded stepper only knows about two values
+                let threads = if circuit.four_valued { 1 } else { THREADS.load(Ordering::Relaxed) };
+                if threads > 1 {
+                    parallel::step(circuit, steps, threads);
+                } else {
+                    for _ in 0..steps {
+                        circuit.step();
+                    }
+                }
+                writeln!(out, "Stepped {} time(s) on {} thread(s)", steps, threads)?;
+                let fights = circuit.contention();
+                if !fights.is_empty() {
+                    writeln!(out, "Contention on Wire(s) {}, see NETS", fights.iter().map(|f| f.to_string()).collect::<Vec<String>>().join(", "))?;
+                }
+            }
+            else if t == LOGICCODE {
+                match sentence.first().map(|w| w.as_slice()) {
+                    Some(b"4") => {
+                        circuit.set_four_valued(true);
+                        writeln!(out, "Simulating with 0, 1, X and Z, every gate is X until it is worked out")?;
+                    }
+                    Some(b"2") => {
+                        circuit.set_four_valued(false);
+                        writeln!(out, "Simulating with 0 and 1")?;
+                    }
+                    _ => writeln!(out, "The circuit is using {} valued logic, use LOGIC 2 or LOGIC 4 to change it", if circuit.four_valued { 4 } else { 2 })?,
+                }
+            }
+            else if t == PULLCODE {
+                let id = sentence.first().and_then(|w| String::from_utf8_lossy(w).parse::<u32>().ok());
+                let pull = match sentence.get(1).map(|w| w.as_slice()) {
+                    Some(b"UP") => Some(true),
+                    Some(b"DOWN") => Some(false),
+                    Some(b"NONE") => Option::None,
+                    _ => {
+                        writeln!(out, "Enter a Wire and UP, DOWN or NONE")?;
+                        return Ok(());
+                    }
+                };
+                match id.and_then(|id| circuit.gates.iter_mut().find(|g| g.id == id)) {
+                    Some(gate) if gate.gate_type == GateType::Wire => {
+                        gate.pull = pull;
+                        writeln!(out, "Pulled {} {}", gate.id, String::from_utf8_lossy(&sentence[1]).to_lowercase())?;
+                    }
+                    Some(gate) => writeln!(out, "Component {} is not a Wire", gate.id)?,
+                    Option::None => writeln!(out, "Component does not exist")?,
+                }
+            }
+            else if t == RUNCODE {
+                let words: Vec<String> = sentence.iter().filter(|w| !w.is_empty()).map(|w| String::from_utf8_lossy(w).to_string()).collect();
+                let ticks = match words.first().map(|w| w.parse::<u64>()) {
+                    Some(Ok(ticks)) => ticks,
+                    Some(Err(_)) => {
+                        writeln!(out, "The number of ticks has to be a number")?;
+                        return Ok(());
+                    }
+                    Option::None => 100,
+                };
+                let Ok(toggles) = words.iter().skip(1).map(|w| w.parse::<u32>()).collect::<Result<Vec<u32>, _>>() else {
+                    writeln!(out, "The Inputs have to be gate ids")?;
+                    return Ok(());
+                };
+                if let Err(e) = event::run(circuit, ticks, &toggles, out) {
+                    writeln!(out, "{}", e)?;
+                }
+            }
+            else if t == FAULTSIMCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path for the test vectors")?;
+                        return Ok(());
+                    }
+                    if let Err(e) = fault::simulate(circuit, &fp, out) {
+                        writeln!(out, "Failed to run the vectors from {}\n{}", fp, e)?;
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == ATPGCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path to save the test vectors to")?;
+                        return Ok(());
+                    }
+                    if let Err(e) = atpg::generate(circuit, &fp, out) {
+                        writeln!(out, "Failed to make test vectors\n{}", e)?;
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == POWERCODE {
+                let words: Vec<String> = sentence.iter().map(|w| String::from_utf8_lossy(w).to_string()).collect();
+                match words.as_slice() {
+                    [] => power::report(circuit, out)?,
+                    [reset] if reset == RESETCODE => {
+                        circuit.clear_toggles();
+                        writeln!(out, "Counting toggles from now")?;
+                    }
+                    [gate_key, weight] => match (parse_gate(gate_key.clone(), Vec::new(), Vec::new()), weight.parse::<f64>()) {
+                        (Some((gate_type, ..)), Ok(weight)) if weight >= 0.0 => {
+                            power::set_weight(circuit, &gate_type, weight);
+                            writeln!(out, "{:?} gates now weigh {}", gate_type, weight)?;
+                        }
+                        (Option::None, _) => writeln!(out, "{} is not a type of gate", gate_key)?,
+                        _ => writeln!(out, "The weight has to be a number that isn't negative")?,
+                    },
+                    _ => writeln!(out, "Use POWER, POWER RESET or POWER [Gate Type] [weight]")?,
+                }
+            }
+            else if t == HAZARDSCODE {
+                let other = sentence.first()
+                    .filter(|w| !w.is_empty())
+                    .map(|w| String::from_utf8_lossy(w).parse::<u32>().ok().and_then(|id| catalogue.iter().find(|c| c.id == id)));
+                let result = match other {
+                    Some(Some(other)) => hazard::report(other, out),
+                    Some(Option::None) => {
+                        writeln!(out, "Enter the id of a circuit in the catalogue, or nothing for this one")?;
+                        return Ok(());
+                    }
+                    Option::None => hazard::report(circuit, out),
+                };
+                if let Err(e) = result {
+                    writeln!(out, "Failed to look for hazards\n{}", e)?;
+                }
+            }
+            else if t == STATSCODE {
+                let other = sentence.first()
+                    .filter(|w| !w.is_empty())
+                    .map(|w| String::from_utf8_lossy(w).parse::<u32>().ok().and_then(|id| catalogue.iter().find(|c| c.id == id)));
+                match other {
+                    Some(Some(other)) => stats::report(other, out)?,
+                    Some(Option::None) => writeln!(out, "Enter the id of a circuit in the catalogue, or nothing for this one")?,
+                    Option::None => stats::report(circuit, out)?,
+                }
+            }
+            else if t == TIMINGCODE {
+                timing::report(circuit, out)?;
+            }
+            else if t == NETSCODE {
+                circuit.display_nets(out)?;
+            }
+            else if t == BENCHCODE {
+                match sentence.first().filter(|w| !w.is_empty()).map(|w| String::from_utf8_lossy(w).parse::<u32>()) {
+                    Some(Ok(steps)) => tape::benchmark(circuit, steps, out)?,
+                    Some(Err(_)) => writeln!(out, "The number of steps has to be a number")?,
+                    Option::None => tape::benchmark(circuit, 1000, out)?,
+                }
+            }
+            else if t == COMPILECIRCUITCODE { 
+                circuit.normalize(); 
+                catalogue.push(circuit.clone()); 
+                writeln!(out, "Compiled Circuit")?
+            }
+            else if t == LOADCIRCUITCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path for the circuit")?;
+                        return Ok(());
+                    }
+                    
+                    *id += 1;
+                    let mut new_circuit = Circuit::new(*id);
+                    match new_circuit.load_from_file(&fp) {
+                        Ok(()) => {
+                            writeln!(out, "Loaded Circuit from {}", fp)?;
+                            catalogue.push(new_circuit);
+                        }
+                        Err(e) => {
+                            writeln!(out, "Failed to load circuit from {}\n{}", fp, e)?;
+                            *id -= 1;                            
+                        }
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == SAVECIRCUITCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path for the circuit")?;
+                        return Ok(());
+                    }
+                    match circuit.save_to_file(&fp) {
+                        Ok(()) => writeln!(out, "Saved Circuit to {}", fp)?,
+                        Err(e) => writeln!(out, "Failed to save circuit to {}\n{}", fp, e)?,
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == SAVELIBRARYCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path for the library")?;
+                        return Ok(());
+                    }
+                    match library::save_library(catalogue, &fp) {
+                        Ok((count, unnamed)) => {
+                            for id in unnamed {
+                                writeln!(out, "Circuit {} has no name so it was left out of the library", id)?;
+                            }
+                            writeln!(out, "Saved {} Circuit(s) to {}", count, fp)?;
+                        }
+                        Err(e) => writeln!(out, "Failed to save library to {}\n{}", fp, e)?,
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == LOADLIBRARYCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path for the library")?;
+                        return Ok(());
+                    }
+                    match library::load_library(&fp, id, catalogue) {
+                        Ok(ids) => writeln!(out, "Loaded {} Circuit(s) from {}", ids.len(), fp)?,
+                        Err(e) => writeln!(out, "Failed to load library from {}\n{}", fp, e)?,
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == EXPORTCODE {
+                if sentence.is_empty() {
+                    writeln!(out, "Enter a format to export to")?;
+                    return Ok(());
+                }
+                let format = String::from_utf8(sentence.remove(0)).unwrap_or_default();
+                let states = sentence.first().map(|w| w.as_slice()) == Some(STATECODE.as_bytes());
+                if states {
+                    sentence.remove(0);
+                }
+                let whole_catalogue = sentence.first().map(|w| w.as_slice()) == Some(CATALOGUECODE.as_bytes());
+                if whole_catalogue {
+                    sentence.remove(0);
+                }
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path to export to")?;
+                        return Ok(());
+                    }
+                    let result = if format == VERILOGCODE {
+                        verilog::export_verilog(circuit, &fp)
+                    } else if format == DOTCODE {
+                        dot::export_dot(circuit, &fp, states)
+                    } else if format == SVGCODE {
+                        svg::export_svg(circuit, &fp)
+                    } else if format == BLIFCODE {
+                        blif::export_blif(circuit, &fp)
+                    } else if format == AIGERCODE {
+                        aiger::export_aiger(circuit, &fp)
+                    } else if format == JSONCODE && whole_catalogue {
+                        match json::export_catalogue_json(catalogue, &fp) {
+                            Ok(()) => writeln!(out, "Exported {} Circuit(s) to {}", catalogue.len(), fp)?,
+                            Err(e) => writeln!(out, "Failed to export catalogue to {}\n{}", fp, e)?,
+                        }
+                        return Ok(());
+                    } else if format == JSONCODE {
+                        json::export_json(circuit, &fp)
+                    } else {
+                        writeln!(out, "Cannot export to \"{}\"", format)?;
+                        return Ok(());
+                    };
+                    match result {
+                        Ok(()) => writeln!(out, "Exported Circuit to {}", fp)?,
+                        Err(e) => writeln!(out, "Failed to export circuit to {}\n{}", fp, e)?,
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == SNAPSHOTCODE || t == RESTORECODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    if fp.is_empty() {
+                        writeln!(out, "Enter a file path for the snapshot")?;
+                        return Ok(());
+                    }
+                    if t == SNAPSHOTCODE {
+                        match json::save_snapshot(circuit, &fp) {
+                            Ok(()) => writeln!(out, "Saved snapshot to {}", fp)?,
+                            Err(e) => writeln!(out, "Failed to save snapshot to {}\n{}", fp, e)?,
+                        }
+                    } else {
+                        match json::restore_snapshot(circuit, &fp) {
+                            Ok(()) => writeln!(out, "Restored snapshot from {}", fp)?,
+                            Err(e) => writeln!(out, "Failed to restore snapshot from {}\n{}", fp, e)?,
+                        }
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == TRUTHCODE {
+                sentence.append(&mut note);
+                let fpbytes = sentence.join(&WHITESPACE);
+                if let Ok(fp) = String::from_utf8(fpbytes) {
+                    let fp = if fp.is_empty() { Option::None } else { Some(fp.as_str()) };
+                    match packed::truth_table(circuit, fp, out) {
+                        Ok(()) => if let Some(fp) = fp { writeln!(out, "Saved truth table to {}", fp)? },
+                        Err(e) => writeln!(out, "Failed to make the truth table\n{}", e)?,
+                    }
+                } else {
+                    writeln!(out, "Problem with path")?;
+                }
+            }
+            else if t == EQUIVCODE {
+                let other = sentence.first()
+                    .and_then(|w| String::from_utf8(w.to_vec()).ok())
+                    .and_then(|s| s.parse::<u32>().ok())
+                    .and_then(|id| catalogue.iter().find(|c| c.id == id));
+                match other {
+                    Some(other) => if let Err(e) = packed::equivalent(circuit, other, out) {
+                        writeln!(out, "Failed to compare the circuits\n{}", e)?;
+                    },
+                    Option::None => writeln!(out, "Enter the id of a circuit in the catalogue")?,
+                }
+            }
+            else if t == DELETECOMPONENTCODE { 
+                for word in sentence[0..sentence.len()].iter() {
+                    if let Ok(s) = String::from_utf8(word.to_vec()) {
+                        if let Ok(id) = s.parse::<u32>() {
+                            if !circuit.delete_component(id) {
+                                writeln!(out, "Component {} does not exist in this circuit", id)?;
+                            }
+                        }
+                    }
+                }
+                
+            }
+            else if t == EDITCOMPONENTCODE {
+                if sentence.len() < 2 {
+                    writeln!(out, "Not enough parameters")?;
+                    return Ok(());
+                }
+                let word = sentence.remove(0);
+                if let Ok(s) = String::from_utf8(word.to_vec()) {
+                    if let Ok(id) = s.parse::<u32>() {
+                        
+                        let gate_type_key = sentence.remove(0);
+                        if let Ok(token) = String::from_utf8(gate_type_key) {
+                            let new_gate = parse_gate(token, sentence, note);
+                            if let Some(gate) = new_gate {
+                                if circuit.edit_component(id, gate.0, gate.1, gate.2) {
+                                    if let Some(edited) = circuit.gates.iter_mut().find(|g| g.id == id) {
+                                        edited.delay = gate.3;
+                                    }
+                                }
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+                writeln!(out, "There is an issue with the command")?;
+            }
+            else if t == IMPORTCIRCUITCODE {
+                let format = sentence.first().and_then(|w| String::from_utf8(w.to_ascii_uppercase()).ok()).unwrap_or_default();
+                if [VERILOGCODE, LOGISIMCODE, BLIFCODE, AIGERCODE, JSONCODE].contains(&format.as_str()) {
+                    sentence.remove(0);
+                    sentence.append(&mut note);
+                    let fpbytes = sentence.join(&WHITESPACE);
+                    if let Ok(fp) = String::from_utf8(fpbytes) {
+                        if fp.is_empty() {
+                            writeln!(out, "Enter a file path to import from")?;
+                            return Ok(());
+                        }
+                        let imported = if format == VERILOGCODE {
+                            verilog::import_verilog(&fp, id, catalogue)
+                        } else if format == LOGISIMCODE {
+                            logisim::import_logisim(&fp, id, catalogue)
+                        } else if format == BLIFCODE {
+                            blif::import_blif(&fp, id, catalogue)
+                        } else if format == JSONCODE {
+                            json::import_json(&fp, id, catalogue)
+                        } else {
+                            aiger::import_aiger(&fp, id, catalogue)
+                        };
+                        match imported {
+                            Ok(ids) => writeln!(out, "Imported {} Circuit(s) from {}", ids.len(), fp)?,
+                            Err(e) => writeln!(out, "Failed to import from {}\n{}", fp, e)?,
+                        }
+                    } else {
+                        writeln!(out, "Problem with path")?;
+                    }
+                    return Ok(());
+                }
+                let word = sentence.remove(0);
+                if let Ok(s) = String::from_utf8(word.to_vec()) {
+                    if let Ok(id) = s.parse::<u32>() {
+                        
+                        for item in catalogue.iter() {
+                            if item.id == id {
+                                circuit.import_circuit(item.clone());
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            else if t == NAMECIRCUITCODE {
+                sentence.append(&mut note);
+                if let Ok(name) = String::from_utf8(sentence.join(&WHITESPACE)) {
+                    circuit.name = Some(name.clone());
+                    writeln!(out, "Changed name of current circuit to {}", name)?;
+                }
+            }
+            else if t == LOADICCODE {
+                let word = sentence.remove(0);
+                if let Ok(s) = String::from_utf8(word.to_vec()) {
+                    if let Ok(id) = s.parse::<u32>() {
+                        
+                        for item in catalogue.iter() {
+                            if item.id == id {
+                                circuit.add_intergrated_circuit(item.clone(), Vec::new(), Vec::new());
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            else {
+                let new_gate = parse_gate(t, sentence, note);
+                if let Some(gate) = new_gate {
+                    circuit.add_component(gate.0, gate.1, gate.2);
+                    if let Some(added) = circuit.gates.last_mut() {
+                        added.delay = gate.3;
+                    }
+                }
+            }
+        }
+    }
+
+
+
+    Ok(())
+}
+
+// The type, input ids, label and delay of a gate, given as `[Gate Type] [id..] (DELAY ticks); [Label]`
+fn parse_gate(gate_key: String, sentence: Vec<Vec<u8>>, note: Vec<Vec<u8>>) -> Option<NewGate> {
+    let mut gate_type: Option<GateType> = Option::None;
+
+    if gate_key == ANDCODE { gate_type = Some(GateType::And) }
+    else if gate_key == ORCODE { gate_type = Some(GateType::Or) }
+    else if gate_key == NOTCODE { gate_type = Some(GateType::Not) }
+    else if gate_key == NORCODE { gate_type = Some(GateType::Nor) }
+    else if gate_key == XORCODE { gate_type = Some(GateType::Xor) }
+    else if gate_key == NANDCODE { gate_type = Some(GateType::Nand) }
+    else if gate_key == NXORCODE { gate_type = Some(GateType::Nxor) }
+    else if gate_key == INPUTCODE { gate_type = Some(GateType::Input) }
+    else if gate_key == OUTPUTCODE { gate_type = Some(GateType::Output) }
+    else if gate_key == BUFFERCODE { gate_type = Some(GateType::Buffer) }
+    else if gate_key == TRISTATECODE { gate_type = Some(GateType::Tristate) }
+    else if gate_key == WIRECODE { gate_type = Some(GateType::Wire) }
+
+
+
+    if let Some(gate_type) = gate_type {
+        let mut inputs: Vec<u32> = Vec::new();
+        let mut delay = Option::None;
+        let mut sentence = sentence.clone();
+        while !sentence.is_empty() {
+            if let Ok(val) = String::from_utf8(sentence.remove(0)) {
+                if val == DELAYCODE && !sentence.is_empty() {
+                    delay = String::from_utf8_lossy(&sentence.remove(0)).parse::<u32>().ok();
+                }
+                else if let Ok(id) = val.parse::<u32>() {
+                    inputs.push(id);
+                }
+            }
+        }
+
+        let note: Option<String> = {
+            let note_bytes = note.join(&WHITESPACE);
+            if !note_bytes.is_empty() {
+                String::from_utf8(note_bytes).ok() } else { Option::None }
+        };
+
+        Some((gate_type, inputs, note, delay))
+    }
+    else {
+        Option::None
+    }
+}
+
+fn parse_gate_name(name: &str) -> Option<GateType> {
+    match name {
+        "Input" => Some(GateType::Input),
+        "Output" => Some(GateType::Output),
+        "Buffer" => Some(GateType::Buffer),
+        "Not" => Some(GateType::Not),
+        "And" => Some(GateType::And),
+        "Or" => Some(GateType::Or),
+        "Nand" => Some(GateType::Nand),
+        "Nor" => Some(GateType::Nor),
+        "Xor" => Some(GateType::Xor),
+        "Nxor" => Some(GateType::Nxor),
+        "Tristate" => Some(GateType::Tristate),
+        "Wire" => Some(GateType::Wire),
+        _ => Option::None,
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> std::io::Result<T> {
+    text.trim().parse::<T>().map_err(|_| invalid_data(format!("\"{}\" is not a number", text)))
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+// IC ports are written as `external id@index>internal id@index`
+fn write_ports(ports: &[Port]) -> String {
+    ports.iter().map(|p| format!("{}@{}>{}@{}", p[0].1, p[0].0, p[1].1, p[1].0)).collect::<Vec<String>>().join(", ")
+}
+
+fn read_ports(line: &str) -> std::io::Result<(Vec<Port>, Vec<Port>)> {
+    let mut lists = Vec::new();
+    for list in line.trim_start_matches('}').split(']') {
+        let Some(list) = list.trim().strip_prefix('[') else { continue };
+        let mut ports = Vec::new();
+        for port in list.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let mut pair = [(0, 0); 2];
+            let (external, internal) = port.split_once('>').ok_or_else(|| invalid_data(format!("\"{}\" is not an IC port", port)))?;
+            for (side, text) in [external, internal].iter().enumerate() {
+                let (id, index) = text.split_once('@').ok_or_else(|| invalid_data(format!("\"{}\" is not an IC port", port)))?;
+                pair[side] = (parse_number(index)?, parse_number(id)?);
+            }
+            ports.push(pair);
+        }
+        lists.push(ports);
+    }
+    if lists.len() != 2 {
+        return Err(invalid_data(format!("\"{}\" should have a list of inputs and a list of outputs", line)));
+    }
+    let outputs = lists.pop().unwrap_or_default();
+    let inputs = lists.pop().unwrap_or_default();
+    Ok((inputs, outputs))
+}
+
+struct Reader {
+    buf: String
+}
+
+impl Reader {
+    fn new(buf: String) -> Self {
+        Self { buf }
+    }
+    fn pop(&mut self) -> Option<u8> {
+        if !self.buf.is_empty() {
+            return Some(self.buf.remove(0) as u8)
+        }
+        Option::None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum GateType {
+    Input,
+    Output,
+    Buffer,
+    Not,
+    And,
+    Or,
+    Nand,
+    Nor,
+    Xor,
+    Nxor,
+    Tristate, // Drives its first input while its second is on, and lets go of its output otherwise
+    Wire,     // A net any number of gates can drive
+}
+
+// Every type of gate, for reports that go through them in order
+const GATE_TYPES: [GateType; 12] = [
+    GateType::Input,
+    GateType::Output,
+    GateType::Buffer,
+    GateType::Not,
+    GateType::And,
+    GateType::Or,
+    GateType::Nand,
+    GateType::Nor,
+    GateType::Xor,
+    GateType::Nxor,
+    GateType::Tristate,
+    GateType::Wire,
+];
+
+impl GateType {
+    // Where the type is in GATE_TYPES, for reports that keep a number for each type
+    fn index(&self) -> usize {
+        GATE_TYPES.iter().position(|t| t == self).expect("Every type of gate is in GATE_TYPES")
+    }
+    // Ticks from an input changing to the output following it, roughly going by how many
+    // transistors deep each gate is, when the gate hasn't been given a delay of its own
+    fn default_delay(&self) -> u32 {
+        match self {
+            GateType::Input | GateType::Output | GateType::Wire => 0,
+            GateType::Buffer | GateType::Not | GateType::Nand | GateType::Nor | GateType::Tristate => 1,
+            GateType::And | GateType::Or => 2,
+            GateType::Xor | GateType::Nxor => 3,
+        }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+struct Circuit {
+    id: u32,
+    name: Option<String>,
+    id_counter: u32,
+    gates: Vec<Gate>,
+    intergrated_circuits: Vec<IC>,
+    four_valued: bool, // Steps with the levels of the gates instead of their states
+    steps: u64, // Steps the gates have counted their toggles over, for POWER
+    weights: [f64; GATE_TYPES.len()], // What POWER weighs each type of gate by, in the order of GATE_TYPES
+}
+
+// Two circuits are the same when they are built the same and in the same state. How long they
+// have counted toggles for, like the toggles of their gates, and the weights POWER uses aren't
+// saved and don't change what they do, so they are left out.
+impl PartialEq for Circuit {
+    fn eq(&self, other: &Circuit) -> bool {
+        let Circuit { id, name, id_counter, gates, intergrated_circuits, four_valued, steps: _, weights: _ } = self;
+        *id == other.id && *name == other.name && *id_counter == other.id_counter && *gates == other.gates
+            && *intergrated_circuits == other.intergrated_circuits && *four_valued == other.four_valued
+    }
+}
+
+impl Circuit {
+    fn new(id: u32) -> Self {
+        Self { name: Option::None, id, id_counter: 0, gates: Vec::new(), intergrated_circuits: Vec::new(), four_valued: false, steps: 0, weights: power::WEIGHTS }
+    }
+    fn add_component(&mut self, gate_type: GateType, input_ids: Vec<u32>, label: Option<String>) {
+        let inputs = input_ids.iter().map(|id| {(self.gates.len(), *id)}).collect();
+        self.gates.push(Gate::new(gate_type, self.id_counter, inputs, label));
+        self.id_counter += 1;
+    }
+    fn step(&mut self) {
+        let previous_state = self.gates.clone();
+        for gate in self.gates.iter_mut() {
+            let mut inputs = vec![false; gate.inputs.len()];
+            let mut levels = vec![Level::Z; gate.inputs.len()]; // Nothing drives an input that can't be found
+            let mut updated_inputs = gate.inputs.clone();
+
+            for (i, data) in gate.inputs.iter().enumerate() {
+                for test_index in (0..=data.0.min(previous_state.len()-1)).rev() {
+                    if previous_state[test_index].id == data.1 {
+                        inputs[i] = previous_state[test_index].state;
+                        levels[i] = previous_state[test_index].level;
+                        updated_inputs[i] = (test_index, previous_state[test_index].id);
+                        break
+                    }
+                    else if test_index == 0 {
+                        updated_inputs[i] = (test_index, previous_state.len() as u32 - 1);
+                    }
+                }
+            }
+
+            if self.four_valued {
+                gate.level = logic4::resolve(&gate.gate_type, gate.level, &levels, gate.pull);
+                gate.state = gate.level == Level::One;
+            } else {
+                let new_state = gate.get_new_state(inputs);
+                gate.update_state(new_state);
+            }
+
+            gate.inputs = updated_inputs;
+        }
+        // ICs read their inputs from before this step and their outputs overwrite the gates outside
+        for ic in self.intergrated_circuits.iter_mut() {
+            // ICs added since the mode was changed are brought into it
+            if ic.circuit.four_valued != self.four_valued {
+                ic.circuit.set_four_valued(self.four_valued);
+            }
+            for port in ic.inputs.iter() {
+                if let Some(gate) = previous_state.iter().find(|g| g.id == port[0].1) {
+                    if self.four_valued {
+                        ic.circuit.set_level(port[1].1, gate.level);
+                    } else {
+                        ic.circuit.set_component(port[1].1, gate.state);
+                    }
+                }
+            }
+            ic.circuit.step();
+            for port in ic.outputs.iter() {
+                let state = ic.circuit.gates.iter().find(|g| g.id == port[1].1).map(|g| (g.state, g.level));
+                if let (Some((state, level)), Some(gate)) = (state, self.gates.iter_mut().find(|g| g.id == port[0].1)) {
+                    gate.state = state;
+                    if self.four_valued {
+                        gate.level = level;
+                    }
+                }
+            }
+        }
+        // Counted once the ICs are done, so gates they drive don't count going through the wrong state on the way
+        for (gate, before) in self.gates.iter_mut().zip(previous_state.iter()) {
+            if gate.state != before.state || gate.level != before.level {
+                gate.toggles += 1;
+            }
+        }
+        self.steps += 1;
+    }
+    // Starts counting toggles again from nothing
+    fn clear_toggles(&mut self) {
+        self.steps = 0;
+        for gate in self.gates.iter_mut() {
+            gate.toggles = 0;
+        }
+        for ic in self.intergrated_circuits.iter_mut() {
+            ic.circuit.clear_toggles();
+        }
+    }
+    // Steps until a step changes nothing, giving how many steps that took, or None if it was
+    // still changing after `limit` steps, like a circuit that oscillates
+    fn settle(&mut self, limit: u32) -> Option<u32> {
+        for steps in 1..=limit {
+            let before = self.clone();
+            self.step();
+            if *self == before {
+                return Some(steps);
+            }
+        }
+        Option::None
+    }
+    fn set_component(&mut self, id: u32, state: bool) -> bool {
+        for gate in self.gates.iter_mut() {
+            if gate.id == id {
+                // Only what the mode steps with, so an Input first set to what it already was doesn't count
+                if (self.four_valued && gate.level != Level::from_bool(state)) || (!self.four_valued && gate.state != state) {
+                    gate.toggles += 1;
+                }
+                gate.state = state;
+                gate.level = Level::from_bool(state);
+                return true
+            }
+        }
+        false
+    }
+    fn set_level(&mut self, id: u32, level: Level) -> bool {
+        for gate in self.gates.iter_mut() {
+            if gate.id == id {
+                if gate.level != level {
+                    gate.toggles += 1;
+                }
+                gate.level = level;
+                gate.state = level == Level::One;
+                return true
+            }
+        }
+        false
+    }
+    // Turning the four valued mode on makes every gate unknown until it is worked out again,
+    // apart from the Inputs that have been set
+    fn set_four_valued(&mut self, on: bool) {
+        self.four_valued = on;
+        if on {
+            for gate in self.gates.iter_mut() {
+                gate.level = match gate.gate_type {
+                    GateType::Input if gate.state => Level::One,
+                    GateType::Input if gate.level == Level::One => Level::Zero,
+                    GateType::Input => gate.level,
+                    _ => Level::X,
+                };
+            }
+        }
+        for ic in self.intergrated_circuits.iter_mut() {
+            ic.circuit.set_four_valued(on);
+        }
+    }
+    fn display(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for gate in self.gates.iter() {
+            self.display_gate(gate, out)?;
+        }
+        Ok(())
+    }
+    fn displio(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for gate in self.gates.iter() {
+            if [GateType::Input, GateType::Output].contains(&gate.gate_type) {
+                self.display_gate(gate, out)?;
+            }
+        }
+        Ok(())
+    }
+    // What each driver of a Wire is putting on it, with a driver that can't be found driving nothing
+    fn drivers(&self, wire: &Gate) -> Vec<(u32, Level)> {
+        wire.inputs.iter().map(|i| {
+            let level = self.gates.iter().find(|g| g.id == i.1).map(|g| if self.four_valued { g.level } else { Level::from_bool(g.state) });
+            (i.1, level.unwrap_or(Level::Z))
+        }).collect()
+    }
+    // Wires with one driver putting a 0 on them and another a 1. Two values can't tell a Tristate
+    // that has let go from one driving a 0, so these are only found in the four valued mode.
+    fn contention(&self) -> Vec<u32> {
+        if !self.four_valued {
+            return Vec::new();
+        }
+        self.gates.iter()
+            .filter(|g| g.gate_type == GateType::Wire)
+            .filter(|g| logic4::contention(&self.drivers(g).iter().map(|d| d.1).collect::<Vec<Level>>()))
+            .map(|g| g.id)
+            .collect()
+    }
+    fn display_nets(&self, out: &mut impl Write) -> std::io::Result<()> {
+        for gate in self.gates.iter().filter(|g| g.gate_type == GateType::Wire) {
+            let drivers = self.drivers(gate);
+            let pull = match gate.pull { Some(true) => "up", Some(false) => "down", Option::None => "none" };
+            let state: &dyn Display = if self.four_valued { &gate.level } else { &gate.state };
+            write!(out, "Wire:{} Pull:{} On:{} Drivers: {}", gate.id, pull, state, drivers.iter().map(|d| format!("{}={}", d.0, d.1)).collect::<Vec<String>>().join(", "))?;
+            if self.four_valued && logic4::contention(&drivers.iter().map(|d| d.1).collect::<Vec<Level>>()) {
+                write!(out, " CONTENTION")?;
+            }
+            match &gate.label {
+                Some(label) => writeln!(out, "\t{}", label)?,
+                Option::None => writeln!(out)?,
+            }
+        }
+        if !self.four_valued {
+            writeln!(out, "Contention can only be found in four valued logic, use LOGIC 4")?;
+        }
+        Ok(())
+    }
+    fn display_gate(&self, gate: &Gate, out: &mut impl Write) -> std::io::Result<()> {
+        if self.four_valued {
+            writeln!(out, "{}", Levelled(gate))
+        } else {
+            writeln!(out, "{}", gate)
+        }
+    }
+    fn delete_component(&mut self, id: u32) -> bool {
+        for (i, gate) in self.gates.iter().enumerate() {
+            if gate.id == id {
+                self.gates.remove(i);
+                return true
+            }
+        }
+        false
+    }
+    fn edit_component(&mut self, id: u32, gate_type: GateType, input_ids: Vec<u32>, label: Option<String>) -> bool {
+        for (i, gate) in self.gates.iter().enumerate() {
+            if gate.id == id {
+                // Pointing past the end would leave step never finding the inputs
+                let inputs = input_ids.iter().map(|id| (self.index_of(*id, i), *id)).collect();
+                let mut new_gate = Gate::new(gate_type, id, inputs, label);
+                if new_gate.gate_type == GateType::Wire {
+                    new_gate.pull = gate.pull;
+                }
+                self.gates[i] = new_gate;
+                return true
+            }
+        }
+        false
+    }
+    fn normalize(&mut self) {
+        let mut table: Vec<(u32, u32)> = self.gates.iter().map(|g| (g.id, 0)).collect();
+
+        for (next, gate) in self.gates.iter_mut().enumerate() {
+            table[next] = (gate.id, next as u32);
+            gate.id = next as u32;
+        }
+        for gate in self.gates.iter_mut() {
+            for (i, input) in gate.clone().inputs.iter().enumerate() {
+                for modifier in table.iter() {
+                    if input.1 == modifier.0 {
+                        gate.inputs[i] = (input.0, modifier.1);
+                    }
+                }
+            }
+        }
+    }
+    fn import_circuit(&mut self, other_circuit: Self) {
+        let gates: Vec<NewGate> = other_circuit.gates.iter().map(|gate| {
+            let gate_data = gate.data();
+            let inputs = gate_data.1.iter().map(|i| i+self.id_counter).collect();
+            (gate_data.0, inputs, gate_data.2, gate.delay)
+        }).collect();
+        for gate in gates {
+            self.add_component(gate.0, gate.1, gate.2);
+            if let Some(added) = self.gates.last_mut() {
+                added.delay = gate.3;
+            }
+        }
+    } 
+    fn save_to_file(&self, fp: &str) -> std::io::Result<()> {
+        let mut buf: Vec<u8> = Vec::new();
+        self.write_lo(&mut buf, false)?;
+        let mut file = File::create(fp)?;        
+        file.write_all(&buf)?;
+        Ok(())
+    }
+    // Gates are written as `id:Type[inputs]Label` with a `*` after the type when they are on,
+    // and a Wire that is pulled up or down has a `+` or `-` before that. A gate with a delay of
+    // its own has it straight after the type, like `And~3`. A level other than X comes last, like
+    // `Input*=1`, and a circuit stepping in four valued logic has a `%4` line.
+    // An input only gets its index written (`id@index`) when it isn't where loading would look
+    // for it anyway. ICs are written as a nested circuit between `{circuit id` and `}[inputs][outputs]`,
+    // or in a library as `@Name[inputs][outputs]` when the circuit has a name to be found by.
+    fn write_lo(&self, buf: &mut Vec<u8>, by_name: bool) -> std::io::Result<()> {
+        if let Some(name) = &self.name {
+            writeln!(buf, "#{}", name)?;
+        }
+        writeln!(buf, "${}", self.id_counter)?;
+        if self.four_valued {
+            writeln!(buf, "%4")?;
+        }
+
+        for (index, gate) in self.gates.iter().enumerate() {
+            let inputs: Vec<String> = gate.inputs.iter().map(|input| {
+                if input.0 == self.index_of(input.1, index) {format!("{}", input.1)} else {format!("{}@{}", input.1, input.0)}
+            }).collect();
+            let pull = match gate.pull { Some(true) => "+", Some(false) => "-", Option::None => "" };
+            let delay = gate.delay.map(|d| format!("~{}", d)).unwrap_or_default();
+            let level = if gate.level == Level::X { String::new() } else { format!("={}", gate.level) };
+            write!(buf, "{}:{:?}{}{}{}{}[{}]", gate.id, gate.gate_type, delay, pull, if gate.state {"*"} else {""}, level, inputs.join(", "))?;
+            if let Some(label) = &gate.label {
+                write!(buf, "{}", label)?;
+            }
+            writeln!(buf)?;
+        }
+
+        for ic in self.intergrated_circuits.iter() {
+            match &ic.circuit.name {
+                Some(name) if by_name => writeln!(buf, "@{}[{}][{}]", name, write_ports(&ic.inputs), write_ports(&ic.outputs))?,
+                _ => {
+                    writeln!(buf, "{{{}", ic.circuit.id)?;
+                    ic.circuit.write_lo(buf, false)?;
+                    writeln!(buf, "}}[{}][{}]", write_ports(&ic.inputs), write_ports(&ic.outputs))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn load_from_file(&mut self, fp: &str) -> std::io::Result<()> {
+        let mut file = File::open(fp)?;
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer)?;
+
+        let mut references = Vec::new();
+        if let Some(line) = self.read_lo(&mut buffer.lines(), &mut references)? {
+            return Err(invalid_data(format!("\"{}\" does not close an IC", line)));
+        }
+        if let Some((_, name)) = references.first() {
+            return Err(invalid_data(format!("The circuit uses \"{}\" from a library, use LOADLIB instead", name)));
+        }
+        Ok(())
+    }
+    // Reads lines until the end of the file or a `}` line, which is handed back so that the IC
+    // it closes can read its ports. ICs that refer to a library circuit by name are left empty
+    // and their index and name put in `references` for the library to fill in.
+    fn read_lo<'a>(&mut self, lines: &mut impl Iterator<Item = &'a str>, references: &mut Vec<(usize, String)>) -> std::io::Result<Option<&'a str>> {
+        let mut closing_line = Option::None;
+        let mut unplaced: Vec<(usize, usize)> = Vec::new(); // Gate index; Input index
+
+        while let Some(line) = lines.next() {
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('#') {
+                self.name = Some(name.to_string());
+            }
+            else if let Some(counter) = line.strip_prefix('$') {
+                self.id_counter = parse_number(counter)?;
+            }
+            else if line == "%4" {
+                self.four_valued = true;
+            }
+            else if let Some(circuit_id) = line.strip_prefix('{') {
+                let mut circuit = Circuit::new(parse_number(circuit_id)?);
+                let mut nested_references = Vec::new();
+                let Some(end) = circuit.read_lo(lines, &mut nested_references)? else {
+                    return Err(invalid_data(format!("IC {} is never closed", circuit.id)));
+                };
+                if let Some((_, name)) = nested_references.first() {
+                    return Err(invalid_data(format!("\"{}\" can only be used by name at the top of a library circuit", name)));
+                }
+                let (inputs, outputs) = read_ports(end)?;
+                self.intergrated_circuits.push(IC::new(circuit, inputs, outputs));
+            }
+            else if let Some(reference) = line.strip_prefix('@') {
+                let Some(open) = reference.find('[') else {
+                    return Err(invalid_data(format!("\"{}\" is missing its ports", line)));
+                };
+                let (inputs, outputs) = read_ports(&reference[open..])?;
+                references.push((self.intergrated_circuits.len(), reference[..open].to_string()));
+                self.intergrated_circuits.push(IC::new(Circuit::new(0), inputs, outputs));
+            }
+            else if line.starts_with('}') {
+                closing_line = Some(line);
+                break;
+            }
+            else {
+                let (Some(open), Some(close)) = (line.find('['), line.find(']')) else {
+                    return Err(invalid_data(format!("\"{}\" is not a gate", line)));
+                };
+                let head: String = line[..open].chars().filter(|c| *c != ' ').collect();
+                let (id, head) = match head.split_once(':') {
+                    Some((id, gate)) => (Some(parse_number(id)?), gate),
+                    None => (Option::None, head.as_str()),
+                };
+                let (head, level) = match head.split_once('=') {
+                    Some((head, level)) => (head, Level::parse(level).ok_or_else(|| invalid_data(format!("\"{}\" is not a level", level)))?),
+                    None => (head, Level::X),
+                };
+                let (gate, state) = match head.strip_suffix('*') {
+                    Some(gate) => (gate, true),
+                    None => (head, false),
+                };
+                let (gate, pull) = match (gate.strip_suffix('+'), gate.strip_suffix('-')) {
+                    (Some(gate), _) => (gate, Some(true)),
+                    (_, Some(gate)) => (gate, Some(false)),
+                    _ => (gate, Option::None),
+                };
+                let (gate, delay) = match gate.split_once('~') {
+                    Some((gate, delay)) => (gate, Some(parse_number(delay)?)),
+                    None => (gate, Option::None),
+                };
+                let Some(gate_type) = parse_gate_name(gate) else {
+                    return Err(invalid_data(format!("\"{}\" is not a gate type", gate)));
+                };
+
+                let mut inputs = Vec::new();
+                for input in line[open+1..close].split(',').map(|i| i.trim()).filter(|i| !i.is_empty()) {
+                    match input.split_once('@') {
+                        Some((id, index)) => inputs.push((parse_number(index)?, parse_number(id)?)),
+                        None => {
+                            unplaced.push((self.gates.len(), inputs.len()));
+                            inputs.push((0, parse_number(input)?));
+                        }
+                    }
+                }
+                let label = if close+1 < line.len() {Some(line[close+1..].to_string())} else {Option::None};
+
+                // Files without ids are numbered in order like they were typed in
+                let id = id.unwrap_or(self.id_counter);
+                self.id_counter = self.id_counter.max(id + 1);
+                let mut new_gate = Gate::new(gate_type, id, inputs, label);
+                new_gate.state = state;
+                new_gate.level = level;
+                new_gate.pull = pull;
+                new_gate.delay = delay;
+                self.gates.push(new_gate);
+            }
+        }
+
+        for (gate_index, input_index) in unplaced {
+            let index = self.index_of(self.gates[gate_index].inputs[input_index].1, gate_index);
+            self.gates[gate_index].inputs[input_index].0 = index;
+        }
+        Ok(closing_line)
+    }
+    // Where a loaded input will point: the gate with that id or, if there isn't one, the gate itself
+    fn index_of(&self, id: u32, own_index: usize) -> usize {
+        self.gates.iter().position(|g| g.id == id).unwrap_or(own_index)
+    }
+    // Gates outside of ICs keep their place when flattened, with this circuit's own gates first
+    // and in order and the gates from inside ICs after them. So a gate's index here is its index
+    // in the flattened circuit too, and these go between the two.
+    fn flat_index(&self, id: u32) -> Option<usize> {
+        self.gates.iter().position(|g| g.id == id)
+    }
+    fn flat_indexes_of(&self, gate_type: GateType) -> Vec<usize> {
+        (0..self.gates.len()).filter(|i| self.gates[*i].gate_type == gate_type).collect()
+    }
+    // Names a gate of the flattened circuit with the id it has here, or as being in an IC
+    fn flat_gate_name(&self, gate: &Gate, index: usize) -> String {
+        gate.name_with_id(&self.gates.get(index).map(|g| g.id.to_string()).unwrap_or(String::from("in an IC")))
+    }
+    // A gate of the flattened circuit by its label, or the id it has here
+    fn flat_short_name(&self, gate: &Gate, index: usize) -> String {
+        gate.label.clone().unwrap_or(self.gates.get(index).map(|g| g.id).unwrap_or(gate.id).to_string())
+    }
+    // The same circuit with the gates of every IC brought up into it, so there are no ICs left.
+    // Gates are given new ids, which are also their indexes. An IC's Inputs are set before it
+    // steps and the gates its Outputs drive are set after, so gates inside read straight through
+    // the Inputs and the gates driven from inside take on the gate that drives them. That way it
+    // steps exactly like the circuit with its ICs. Inputs of an IC that nothing goes into stay
+    // at the state they were in, and inputs that can't be found, like after a DEL, read an always
+    // off Buffer at the end the way stepping reads them as off.
+    fn flattened(&self) -> Circuit {
+        let mut gates: Vec<FlatGate> = Vec::new();
+        self.flatten_into(&mut gates);
+        let off = gates.len() as u32;
+        let mut missing = false;
+        for input in gates.iter_mut().flat_map(|g| g.1.iter_mut()).filter(|i| **i == MISSING) {
+            *input = off;
+            missing = true;
+        }
+        if missing {
+            gates.push((GateType::Buffer, Vec::new(), Option::None, false, 0));
+        }
+
+        let mut flat = Circuit::new(self.id);
+        flat.name = self.name.clone();
+        for (gate_type, inputs, label, state, delay) in gates {
+            let mut gate = Gate::new(gate_type, flat.id_counter, inputs.into_iter().map(|i| (i as usize, i)).collect(), label);
+            gate.state = state;
+            gate.delay = Some(delay);
+            flat.gates.push(gate);
+            flat.id_counter += 1;
+        }
+        flat
+    }
+    // Adds the gates of this circuit and its ICs, giving back the new id of each of its own gates
+    fn flatten_into(&self, gates: &mut Vec<FlatGate>) -> Vec<(u32, u32)> {
+        let ids: Vec<(u32, u32)> = self.gates.iter().enumerate().map(|(i, g)| (g.id, (gates.len() + i) as u32)).collect();
+        let new_ids: HashMap<u32, u32> = ids.iter().copied().collect();
+        let new_id = |ids: &HashMap<u32, u32>, id: u32| ids.get(&id).copied();
+        for gate in self.gates.iter() {
+            let inputs = gate.inputs.iter().map(|i| new_id(&new_ids, i.1).unwrap_or(MISSING)).collect();
+            gates.push((gate.gate_type.clone(), inputs, gate.label.clone(), gate.state, gate.delay()));
+        }
+
+        for ic in self.intergrated_circuits.iter() {
+            let start = gates.len();
+            let inner = ic.circuit.flatten_into(gates);
+            let inner_ids: HashMap<u32, u32> = inner.iter().copied().collect();
+            let mut bypasses = Vec::new();
+            for port in ic.inputs.iter() {
+                if let (Some(external), Some(internal)) = (new_id(&new_ids, port[0].1), new_id(&inner_ids, port[1].1)) {
+                    gates[internal as usize].0 = GateType::Buffer;
+                    gates[internal as usize].1 = vec![external];
+                    bypasses.push((internal, external));
+                }
+            }
+            for gate in gates[start..].iter_mut() {
+                for input in gate.1.iter_mut() {
+                    if let Some((_, external)) = bypasses.iter().find(|b| b.0 == *input) {
+                        *input = *external;
+                    }
+                }
+            }
+            for (_, internal) in inner.iter() {
+                match gates[*internal as usize] {
+                    (GateType::Output, ..) => gates[*internal as usize].0 = GateType::Buffer,
+                    (GateType::Input, _, _, false, _) => gates[*internal as usize].0 = GateType::Buffer,
+                    (GateType::Input, _, _, true, _) => {
+                        // A Buffer with nothing in is always off and a Not of that is always on
+                        let off = gates.len() as u32;
+                        gates.push((GateType::Buffer, Vec::new(), Option::None, false, 0));
+                        gates[*internal as usize].0 = GateType::Not;
+                        gates[*internal as usize].1 = vec![off];
+                    }
+                    _ => {}
+                }
+            }
+            for port in ic.outputs.iter() {
+                if let (Some(external), Some(internal)) = (new_id(&new_ids, port[0].1), new_id(&inner_ids, port[1].1)) {
+                    let (gate_type, inputs, delay) = (gates[internal as usize].0.clone(), gates[internal as usize].1.clone(), gates[internal as usize].4);
+                    let gate = &mut gates[external as usize];
+                    if gate.0 != GateType::Output || gate_type != GateType::Buffer {
+                        gate.0 = gate_type;
+                    }
+                    gate.1 = inputs;
+                    gate.4 = delay;
+                }
+            }
+        }
+        ids
+    }
+    // Takes the states of a flattened copy of this circuit back into it and its ICs, going
+    // through the gates in the same order `flatten_into` put them in
+    fn unflatten_states(&mut self, states: &[bool], next: &mut usize) {
+        for gate in self.gates.iter_mut() {
+            if let Some(state) = states.get(*next) {
+                gate.state = *state;
+            }
+            *next += 1;
+        }
+        for ic in self.intergrated_circuits.iter_mut() {
+            let inputs: Vec<(u32, bool)> = ic.circuit.gates.iter()
+                .filter(|g| g.gate_type == GateType::Input)
+                .map(|g| (g.id, g.state))
+                .collect();
+            ic.circuit.unflatten_states(states, next);
+            // Skips the always off Buffers that were added for Inputs of the IC left on
+            *next += inputs.iter().filter(|(id, on)| *on && !ic.inputs.iter().any(|p| p[1].1 == *id)).count();
+        }
+    }
+    // Adds toggles counted on the flattened circuit, in the same order as unflatten_states
+    fn unflatten_toggles(&mut self, toggles: &[u64], steps: u64, next: &mut usize) {
+        self.steps += steps;
+        for gate in self.gates.iter_mut() {
+            gate.toggles += toggles.get(*next).copied().unwrap_or(0);
+            *next += 1;
+        }
+        for ic in self.intergrated_circuits.iter_mut() {
+            let inputs: Vec<(u32, bool)> = ic.circuit.gates.iter()
+                .filter(|g| g.gate_type == GateType::Input)
+                .map(|g| (g.id, g.state))
+                .collect();
+            ic.circuit.unflatten_toggles(toggles, steps, next);
+            *next += inputs.iter().filter(|(id, on)| *on && !ic.inputs.iter().any(|p| p[1].1 == *id)).count();
+        }
+    }
+    fn add_intergrated_circuit(&mut self, circuit: Circuit, input_ids: Vec<u32>, output_ids: Vec<u32>) {
+        let mut complete_inputs = Vec::new();
+        let mut complete_outputs = Vec::new();
+        for (i, internal_id) in input_ids.iter().enumerate() {
+            self.add_component(
+                GateType::Buffer, 
+                Vec::new(), 
+                Some(if let Some(n) = circuit.name.clone() {format!("IC INPUT {} {}", i, n)} else {format!("IC INPUT {}", i)}));
+            let last_index = self.gates.len() - 1;
+            let external_id = self.gates[last_index].id;
+            complete_inputs.push([(self.gates.len(), external_id), (circuit.gates.len(), *internal_id)]);
+        }
+        for (i, internal_id) in output_ids.iter().enumerate() {
+            self.add_component(
+                GateType::Buffer, 
+                Vec::new(), 
+                Some(if let Some(n) = circuit.name.clone() {format!("IC OUTPUT {} FOR {}", i, n)} else {format!("IC OUTPUT {}", i)}));
+            let last_index = self.gates.len() - 1;
+            let external_id = self.gates[last_index].id;
+            complete_outputs.push([(self.gates.len(), external_id), (circuit.gates.len(), *internal_id)]);
+        }
+        let new_ic = IC::new(circuit, complete_inputs, complete_outputs);
+        self.intergrated_circuits.push(new_ic);
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Gate {
+    state: bool,
+    level: Level, // Only kept up to date in the four valued mode
+    pull: Option<bool>, // What a Wire is when nothing drives it, up or down
+    delay: Option<u32>, // Ticks the gate takes, if it isn't the default for its type
+    label: Option<String>,
+    gate_type: GateType,
+    id: u32,
+    inputs: Vec<(usize, u32)>, // Index; ID
+    toggles: u64, // Times the gate has changed since its circuit started counting
+}
+
+// Leaves out the toggles, the same as Circuit does its steps
+impl PartialEq for Gate {
+    fn eq(&self, other: &Gate) -> bool {
+        let Gate { state, level, pull, delay, label, gate_type, id, inputs, toggles: _ } = self;
+        *state == other.state && *level == other.level && *pull == other.pull && *delay == other.delay
+            && *label == other.label && *gate_type == other.gate_type && *id == other.id && *inputs == other.inputs
+    }
+}
+
+impl Gate {
+    fn new(gate_type: GateType, id: u32, inputs: Vec<(usize, u32)>, label: Option<String>) -> Self {
+        Self { state: false, level: Level::X, pull: Option::None, delay: Option::None, label, gate_type, id, inputs, toggles: 0 }
+    }
+    fn delay(&self) -> u32 {
+        self.delay.unwrap_or(self.gate_type.default_delay())
+    }
+    // `Type id Label`, the way reports name a gate
+    fn name(&self) -> String {
+        self.name_with_id(&self.id.to_string())
+    }
+    fn name_with_id(&self, id: &str) -> String {
+        match &self.label {
+            Some(label) => format!("{:?} {} {}", self.gate_type, id, label),
+            Option::None => format!("{:?} {}", self.gate_type, id),
+        }
+    }
+    fn get_new_state(&self, inputs: Vec<bool>) -> bool {
+        if inputs.is_empty() && self.gate_type != GateType::Input {return false}
+        match self.gate_type {
+            GateType::Input => self.state,
+            GateType::Output => inputs[0],
+            GateType::And  => !inputs.contains(&false),
+            GateType::Nand => inputs.contains(&false),
+            GateType::Nor  => !inputs.contains(&true),
+            GateType::Not  => !inputs[0],
+            // A missing second input is off, the same as in the other engines
+            GateType::Nxor => inputs[0] == (inputs.get(1) == Some(&true)),
+            GateType::Or   => inputs.contains(&true),
+            GateType::Xor  => inputs[0] != (inputs.get(1) == Some(&true)),
+            GateType::Buffer => inputs[0],
+            // Two values can't show a Tristate letting go, so it is off and Wires are the Or of their drivers
+            GateType::Tristate => inputs[0] && inputs.get(1) != Some(&false),
+            GateType::Wire => inputs.contains(&true),
+        }
+    }
+    fn update_state(&mut self, new_state: bool) {
+        self.state = new_state;
+    }
+    fn data(&self) -> (GateType, Vec<u32>, Option<String>) {
+        (self.gate_type.clone(), self.inputs.iter().map(|i| i.1).collect(), self.label.clone())
+    }
+}
+
+fn input(prompt: &str) -> String {
+    use std::io::{stdin,stdout,Write};
+    let mut s=String::new();
+    print!("{}", prompt);
+    let _=stdout().flush();
+    stdin().read_line(&mut s).expect("Did not enter a correct string");
+    if let Some('\n')=s.chars().next_back() {
+        s.pop();
+    }
+    if let Some('\r')=s.chars().next_back() {
+        s.pop();
+    }
+    s
+}
+
+impl Display for Gate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_with(f, &self.state)
+    }
+}
+
+// Shows a gate with its level instead of its state
+struct Levelled<'a>(&'a Gate);
+
+impl Display for Levelled<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.write_with(f, &self.0.level)
+    }
+}
+
+impl Gate {
+    fn write_with(&self, f: &mut std::fmt::Formatter<'_>, state: &dyn Display) -> std::fmt::Result {
+        match self.gate_type {
+            GateType::Input => {
+                if let Some(n) = &self.label {
+                    write!(f, "Input:{} On:{}\t{}", self.id, state, n)
+                } else {
+                    write!(f, "Input:{} On:{}", self.id, state)
+                }
+            }
+            GateType::Output => {
+                if !self.inputs.is_empty() {
+                    if let Some(n) = &self.label {
+                        write!(f, "Ouput:{} Source: {} On:{}\t{}", self.id, self.inputs[0].1, state, n)
+                    } else {write!(f, "Ouput:{} Source: {} On:{}", self.id, self.inputs[0].1, state)}
+                } else {write!(f, "Ouput:{} On:{}", self.id, state)}
+            }
+            _=> {
+                if let Some(n) = &self.label {
+                    write!(f, "{:?}{:?}:{} {}\t{}", self.gate_type, self.inputs.iter().map(|d| d.1).collect::<Vec<u32>>(), self.id, state, n)
+                }
+                else {
+                    write!(f, "{:?}{:?}:{} {}", self.gate_type, self.inputs.iter().map(|d| d.1).collect::<Vec<u32>>(), self.id, state)
+                }
+            }
+        }
+    }
+}
+
+type Port = [(usize, u32); 2]; // External, Internal
+
+type NewGate = (GateType, Vec<u32>, Option<String>, Option<u32>); // Type; Input ids; Label; Delay
+
+type FlatGate = (GateType, Vec<u32>, Option<String>, bool, u32); // Type; Input ids; Label; State; Delay
+
+// Where a flattened gate reads an input that isn't in its circuit, until flattened() gives it something to read
+const MISSING: u32 = u32::MAX;
+
+#[derive(Clone, Debug, PartialEq)]
+struct IC {
+    circuit: Circuit,
+    inputs: Vec<[(usize, u32); 2]>,    // External, Internal
+    outputs: Vec<[(usize, u32); 2]>,            // External, Internal
+}
+
+impl IC {
+    fn new (circuit: Circuit, inputs: Vec<[(usize, u32); 2]>, outputs: Vec<[(usize, u32); 2]>) -> Self {
+        Self { circuit, inputs, outputs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two Inputs into an Xor and an And, each going to an Output
+    pub fn half_adder(id: u32) -> Circuit {
+        let mut circuit = Circuit::new(id);
+        circuit.name = Some(String::from("half_adder"));
+        circuit.add_component(GateType::Input, Vec::new(), Some(String::from("A")));
+        circuit.add_component(GateType::Input, Vec::new(), Some(String::from("B")));
+        circuit.add_component(GateType::Xor, vec![0, 1], Option::None);
+        circuit.add_component(GateType::And, vec![0, 1], Option::None);
+        circuit.add_component(GateType::Output, vec![2], Some(String::from("S")));
+        circuit.add_component(GateType::Output, vec![3], Some(String::from("C")));
+        circuit
+    }
+
+    // A circuit with a gate taken out, holding an IC that holds another IC
+    pub fn nested() -> Circuit {
+        let mut middle = Circuit::new(2);
+        middle.name = Some(String::from("wrapped"));
+        middle.add_component(GateType::Input, Vec::new(), Option::None);
+        middle.add_component(GateType::Not, vec![0], Option::None);
+        middle.add_component(GateType::Output, Vec::new(), Option::None);
+        middle.add_intergrated_circuit(half_adder(1), vec![0, 1], vec![4]);
+        middle.edit_component(2, GateType::Output, vec![4], Option::None);
+
+        let mut circuit = Circuit::new(3);
+        circuit.add_component(GateType::Input, Vec::new(), Some(String::from("X")));
+        circuit.add_component(GateType::Nand, vec![0, 0], Option::None);
+        circuit.add_component(GateType::Or, vec![0, 1], Option::None);
+        circuit.add_component(GateType::Output, vec![2], Option::None);
+        circuit.delete_component(1);
+        circuit.add_intergrated_circuit(middle, vec![0], vec![2]);
+        circuit
+    }
+
+    // Runs commands the way the REPL does, stepping before each one, and gives back what they wrote
+    pub fn run(circuit: &mut Circuit, catalogue: &mut Vec<Circuit>, commands: &[&str]) -> String {
+        let mut id = catalogue.iter().map(|c| c.id).chain([circuit.id]).max().unwrap_or(0);
+        let mut out = Vec::new();
+        for command in commands {
+            circuit.step();
+            run_command(command.to_string(), circuit, &mut id, catalogue, &mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    // A file in the temporary directory that no other test uses
+    pub fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("simlo-{}-{}", std::process::id(), name)).to_string_lossy().to_string()
+    }
+
+    // SAVE and LOAD from the REPL, giving back the loaded circuit with the id of the one saved
+    fn saved_and_loaded_by_command(circuit: &mut Circuit, name: &str) -> Circuit {
+        let fp = temp_path(name);
+        let mut catalogue = Vec::new();
+        run(circuit, &mut catalogue, &[&format!("SAVE {}", fp)]);
+        let saved = circuit.clone();
+        let text = run(circuit, &mut catalogue, &[&format!("LOAD {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        let mut loaded = catalogue.pop().unwrap_or_else(|| panic!("{}", text));
+        loaded.id = saved.id;
+        assert_eq!(loaded, saved);
+        loaded
+    }
+
+    // Eight Inputs through every type of gate and a half adder IC, so it takes more than one
+    // batch of 64 vectors to go through them all
+    pub fn wide() -> Circuit {
+        let mut circuit = Circuit::new(4);
+        for i in 0..8 {
+            circuit.add_component(GateType::Input, Vec::new(), Some(format!("I{}", i)));
+        }
+        circuit.add_intergrated_circuit(half_adder(1), vec![0, 1], vec![4, 5]); // 8, 9 in; 10, 11 out
+        circuit.edit_component(8, GateType::Buffer, vec![0], Option::None);
+        circuit.edit_component(9, GateType::Buffer, vec![1], Option::None);
+        circuit.add_component(GateType::Nand, vec![2, 3, 4], Option::None); // 12
+        circuit.add_component(GateType::Nor, vec![5, 6], Option::None); // 13
+        circuit.add_component(GateType::Xor, vec![7, 10], Option::None); // 14
+        circuit.add_component(GateType::Nxor, vec![11, 12], Option::None); // 15
+        circuit.add_component(GateType::Or, vec![13, 14], Option::None); // 16
+        circuit.add_component(GateType::And, vec![15, 16, 3], Option::None); // 17
+        circuit.add_component(GateType::Not, vec![17], Option::None); // 18
+        circuit.add_component(GateType::Tristate, vec![2, 6], Option::None); // 19
+        circuit.add_component(GateType::Wire, vec![19, 13], Option::None); // 20
+        circuit.add_component(GateType::Buffer, vec![20], Option::None); // 21
+        for output in [10, 11, 16, 18, 21] {
+            circuit.add_component(GateType::Output, vec![output], Option::None);
+        }
+        circuit
+    }
+
+    // wide() with the Nor and the half adder's And deleted, leaving gates that read them
+    pub fn wide_after_del() -> Circuit {
+        let mut circuit = wide();
+        circuit.delete_component(13);
+        circuit.intergrated_circuits[0].circuit.delete_component(3);
+        circuit
+    }
+
+    // What the Outputs settle to when Circuit::step is called over and over, with the Inputs set
+    // to a vector that has the first Input as its highest bit
+    pub fn stepped_outputs(circuit: &Circuit, vector: u64) -> Vec<bool> {
+        let mut circuit = circuit.clone();
+        let inputs: Vec<u32> = circuit.gates.iter().filter(|g| g.gate_type == GateType::Input).map(|g| g.id).collect();
+        for (bit, id) in inputs.iter().enumerate() {
+            circuit.set_component(*id, (vector >> (inputs.len() - 1 - bit)) & 1 == 1);
+        }
+        circuit.settle(1000).expect("the circuit should settle");
+        circuit.gates.iter().filter(|g| g.gate_type == GateType::Output).map(|g| g.state).collect()
+    }
+
+    #[test]
+    fn nand_and_nor_follow_their_truth_tables() {
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            let nand = Gate::new(GateType::Nand, 0, Vec::new(), Option::None);
+            let nor = Gate::new(GateType::Nor, 0, Vec::new(), Option::None);
+            assert_eq!(nand.get_new_state(vec![a, b]), !(a && b), "Nand of {} and {}", a, b);
+            assert_eq!(nor.get_new_state(vec![a, b]), !(a || b), "Nor of {} and {}", a, b);
+        }
+        let nand = Gate::new(GateType::Nand, 0, Vec::new(), Option::None);
+        assert!(!nand.get_new_state(vec![true, true, true]));
+        assert!(nand.get_new_state(vec![true, false, true]));
+    }
+
+    // The states of every gate, going into ICs
+    fn states(circuit: &Circuit) -> Vec<bool> {
+        circuit.gates.iter().map(|g| g.state)
+            .chain(circuit.intergrated_circuits.iter().flat_map(|ic| states(&ic.circuit)))
+            .collect()
+    }
+
+    #[test]
+    fn parallel_steps_like_serial_steps() {
+        // A ring of three Nots that never settles, a Nor latch, a one input Xor and an IC. Loops
+        // are closed with EDIT, as step can't read a gate that comes later when it is first added.
+        let mut circuit = Circuit::new(5);
+        circuit.add_component(GateType::Input, Vec::new(), Some(String::from("S")));
+        circuit.add_component(GateType::Input, Vec::new(), Some(String::from("R")));
+        circuit.add_component(GateType::Not, Vec::new(), Option::None); // 2
+        circuit.add_component(GateType::Not, vec![2], Option::None); // 3
+        circuit.add_component(GateType::Not, vec![3], Option::None); // 4
+        circuit.add_component(GateType::Nor, vec![1], Some(String::from("Q"))); // 5
+        circuit.add_component(GateType::Nor, vec![0, 5], Option::None); // 6
+        circuit.edit_component(2, GateType::Not, vec![4], Option::None);
+        circuit.edit_component(5, GateType::Nor, vec![1, 6], Some(String::from("Q")));
+        circuit.add_component(GateType::Xor, vec![4], Option::None); // 7
+        circuit.add_intergrated_circuit(half_adder(1), vec![0, 1], vec![4, 5]); // 8, 9 in; 10, 11 out
+        circuit.edit_component(8, GateType::Buffer, vec![7], Option::None);
+        circuit.edit_component(9, GateType::Buffer, vec![5], Option::None);
+        circuit.add_component(GateType::Output, vec![10], Option::None);
+        circuit.add_component(GateType::Output, vec![11], Option::None);
+        circuit.set_component(1, true);
+        let mut deleted = circuit.clone();
+        deleted.delete_component(3);
+        deleted.intergrated_circuits[0].circuit.delete_component(2);
+
+        for (circuit, threads) in [(&circuit, 1), (&circuit, 2), (&circuit, 7), (&deleted, 1), (&deleted, 3)] {
+            let mut serial = circuit.clone();
+            let mut parallel = circuit.clone();
+            for steps in [1, 4, 9] {
+                for _ in 0..steps {
+                    serial.step();
+                }
+                parallel::step(&mut parallel, steps, threads);
+                assert_eq!(states(&parallel), states(&serial), "{} thread(s), {} step(s)", threads, steps);
+            }
+            serial.set_component(1, false);
+            parallel.set_component(1, false);
+            serial.set_component(0, true);
+            parallel.set_component(0, true);
+            for _ in 0..5 {
+                serial.step();
+            }
+            parallel::step(&mut parallel, 5, threads);
+            assert_eq!(states(&parallel), states(&serial), "{} thread(s) after setting", threads);
+        }
+    }
+
+    // What SAVE then LOAD would give back
+    fn saved_and_loaded(circuit: &Circuit) -> Circuit {
+        let mut buf = Vec::new();
+        circuit.write_lo(&mut buf, false).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        let mut loaded = Circuit::new(circuit.id);
+        assert_eq!(loaded.read_lo(&mut text.lines(), &mut Vec::new()).unwrap(), Option::None);
+        loaded
+    }
+
+    #[test]
+    fn levels_and_logic_mode_survive_save_and_load() {
+        let mut circuit = nested();
+        circuit.set_component(0, true);
+        circuit.step();
+        assert_eq!(saved_and_loaded(&circuit), circuit);
+
+        circuit.set_four_valued(true);
+        circuit.set_level(0, Level::Z);
+        circuit.step();
+        circuit.step();
+        assert!(circuit.gates.iter().any(|g| g.level == Level::X) && circuit.gates.iter().any(|g| g.level == Level::Z));
+        assert_eq!(saved_and_loaded(&circuit), circuit);
+    }
+
+    #[test]
+    fn save_then_load_gives_the_same_circuit() {
+        let mut circuit = nested();
+        let text = run(&mut circuit, &mut Vec::new(), &["EDIT 0 INPUT DELAY 4; X", "SET 0 TRUE", "STEP 3"]);
+        assert_eq!(circuit.gates[0].delay, Some(4), "{}", text);
+        assert!(circuit.gates[0].state);
+        saved_and_loaded_by_command(&mut circuit, "save_then_load.lo");
+
+        run(&mut circuit, &mut Vec::new(), &["LOGIC 4", "SET 0 X", "STEP 2"]);
+        saved_and_loaded_by_command(&mut circuit, "save_then_load_4.lo");
+    }
+
+    #[test]
+    fn toggle_counts_stay_out_of_equality() {
+        let mut circuit = half_adder(5);
+        run(&mut circuit, &mut Vec::new(), &["SET 0 TRUE", "STEP 4"]);
+        assert!(circuit.steps > 0 && circuit.gates.iter().any(|g| g.toggles > 0));
+        let loaded = saved_and_loaded_by_command(&mut circuit, "toggle_counts.lo");
+        run(&mut circuit, &mut Vec::new(), &["POWER RESET"]);
+        assert_eq!(loaded, circuit);
+    }
+}
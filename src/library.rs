@@ -0,0 +1,256 @@
+use std::{fs::File, io::{Read, Write}, path::{Path, PathBuf}};
+
+use crate::{invalid_data, logic4::Level, Circuit};
+
+const INCLUDECODE: &str = "INCLUDE";
+
+// A library is a list of circuits that each start with their `#Name` line. ICs inside them are
+// written as `@Name[inputs][outputs]` so they are found by name when loaded, no matter what
+// catalogue id the circuit they use ends up with. `INCLUDE path` lines pull in other libraries,
+// with the path taken from the folder of the file doing the including.
+
+// Gives back how many circuits were saved and the ids of the ones left out for having no name
+pub fn save_library(catalogue: &[Circuit], fp: &str) -> std::io::Result<(usize, Vec<u32>)> {
+    let mut circuits: Vec<&Circuit> = Vec::new();
+    let mut unnamed = Vec::new();
+    for circuit in catalogue.iter() {
+        if circuit.name.is_none() {
+            unnamed.push(circuit.id);
+            continue;
+        }
+        add_with_dependencies(circuit, &mut circuits)?;
+    }
+
+    let mut buf: Vec<u8> = Vec::new();
+    for circuit in circuits.iter() {
+        circuit.write_lo(&mut buf, true)?;
+    }
+    let mut file = File::create(fp)?;
+    file.write_all(&buf)?;
+    Ok((circuits.len(), unnamed))
+}
+
+// Circuits used as ICs are written before the circuits that use them, and only once per name.
+// Two different circuits with the same name can't both be written, as loading would find the
+// first for both.
+fn add_with_dependencies<'a>(circuit: &'a Circuit, circuits: &mut Vec<&'a Circuit>) -> std::io::Result<()> {
+    if let Some(written) = circuits.iter().find(|c| c.name == circuit.name) {
+        if definition(written) != definition(circuit) {
+            return Err(invalid_data(format!("There are different circuits called \"{}\", rename one of them first", circuit.name.clone().unwrap_or_default())));
+        }
+        return Ok(());
+    }
+    for ic in circuit.intergrated_circuits.iter() {
+        if ic.circuit.name.is_some() {
+            add_with_dependencies(&ic.circuit, circuits)?;
+        }
+    }
+    circuits.push(circuit);
+    Ok(())
+}
+
+// What a library keeps of a circuit, leaving out its catalogue id and where it was in a
+// simulation. Stepping moves where inputs are looked for to where they are found, so they are
+// all put there.
+fn definition(circuit: &Circuit) -> Circuit {
+    let mut kept = circuit.clone();
+    kept.id = 0;
+    kept.four_valued = false;
+    for i in 0..kept.gates.len() {
+        for j in 0..kept.gates[i].inputs.len() {
+            kept.gates[i].inputs[j].0 = circuit.index_of(kept.gates[i].inputs[j].1, i);
+        }
+        kept.gates[i].state = false;
+        kept.gates[i].level = Level::X;
+    }
+    for ic in kept.intergrated_circuits.iter_mut() {
+        for port in ic.inputs.iter_mut().chain(ic.outputs.iter_mut()) {
+            port[0].0 = circuit.index_of(port[0].1, port[0].0);
+            port[1].0 = ic.circuit.index_of(port[1].1, port[1].0);
+        }
+        ic.circuit = definition(&ic.circuit);
+    }
+    kept
+}
+
+pub fn load_library(fp: &str, id: &mut u32, catalogue: &mut Vec<Circuit>) -> std::io::Result<Vec<u32>> {
+    let mut entries: Vec<(Circuit, Vec<(usize, String)>)> = Vec::new();
+    read_library(Path::new(fp), &mut Vec::new(), &mut entries)?;
+
+    for (i, (circuit, _)) in entries.iter().enumerate() {
+        if circuit.name.is_some() && entries[..i].iter().any(|(c, _)| c.name == circuit.name) {
+            return Err(invalid_data(format!("\"{}\" is defined more than once", circuit.name.clone().unwrap_or_default())));
+        }
+    }
+
+    let mut next_id = *id;
+    for (circuit, _) in entries.iter_mut() {
+        next_id += 1;
+        circuit.id = next_id;
+    }
+
+    let mut resolved = vec![false; entries.len()];
+    for i in 0..entries.len() {
+        resolve(i, &mut entries, &mut resolved, &mut Vec::new(), catalogue)?;
+    }
+
+    *id = next_id;
+    let mut ids = Vec::new();
+    for (circuit, _) in entries {
+        ids.push(circuit.id);
+        catalogue.push(circuit);
+    }
+    Ok(ids)
+}
+
+fn read_library(fp: &Path, included: &mut Vec<PathBuf>, entries: &mut Vec<(Circuit, Vec<(usize, String)>)>) -> std::io::Result<()> {
+    let full_path = fp.canonicalize()?;
+    if included.contains(&full_path) {
+        return Ok(());
+    }
+    included.push(full_path);
+
+    let mut file = File::open(fp)?;
+    let mut buffer = String::new();
+    file.read_to_string(&mut buffer)?;
+
+    // Split the file up into the lines of each circuit, a `#` inside an IC names the IC's circuit
+    let mut chunks: Vec<Vec<&str>> = Vec::new();
+    let mut depth = 0;
+    for line in buffer.lines().map(|l| l.trim_end_matches('\r')) {
+        if depth == 0 {
+            if let Some(path) = line.strip_prefix(INCLUDECODE) {
+                let path = path.trim();
+                let folder = fp.parent().unwrap_or(Path::new(""));
+                read_library(&folder.join(path), included, entries)
+                    .map_err(|e| std::io::Error::new(e.kind(), format!("{} (included from {})", e, fp.display())))?;
+                continue;
+            }
+            if line.starts_with('#') || chunks.is_empty() {
+                chunks.push(Vec::new());
+            }
+        }
+        if line.starts_with('{') {depth += 1}
+        if line.starts_with('}') {depth -= 1}
+        if let Some(chunk) = chunks.last_mut() {
+            chunk.push(line);
+        }
+    }
+
+    for chunk in chunks {
+        if chunk.iter().all(|l| l.trim().is_empty()) {
+            continue;
+        }
+        let mut circuit = Circuit::new(0);
+        let mut references = Vec::new();
+        if let Some(line) = circuit.read_lo(&mut chunk.into_iter(), &mut references)? {
+            return Err(invalid_data(format!("\"{}\" does not close an IC", line)));
+        }
+        entries.push((circuit, references));
+    }
+    Ok(())
+}
+
+// Fills in the ICs of a library circuit, resolving the circuits it uses first. Names are looked
+// for in the library being loaded and then in the catalogue.
+fn resolve(index: usize, entries: &mut [(Circuit, Vec<(usize, String)>)], resolved: &mut [bool], path: &mut Vec<usize>, catalogue: &[Circuit]) -> std::io::Result<()> {
+    if resolved[index] {
+        return Ok(());
+    }
+    if path.contains(&index) {
+        return Err(invalid_data(format!("\"{}\" ends up using itself as an IC", entries[index].0.name.clone().unwrap_or_default())));
+    }
+    path.push(index);
+
+    for (ic_index, name) in entries[index].1.clone() {
+        let circuit = if let Some(dependency) = entries.iter().position(|(c, _)| c.name.as_ref() == Some(&name)) {
+            resolve(dependency, entries, resolved, path, catalogue)?;
+            entries[dependency].0.clone()
+        }
+        else if let Some(circuit) = catalogue.iter().rev().find(|c| c.name.as_ref() == Some(&name)) {
+            circuit.clone()
+        }
+        else {
+            return Err(invalid_data(format!("No circuit called \"{}\" to use as an IC", name)));
+        };
+        entries[index].0.intergrated_circuits[ic_index].circuit = circuit;
+    }
+
+    path.pop();
+    resolved[index] = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::{half_adder, run, temp_path}, GateType};
+
+    // Two Inputs into a half adder IC
+    fn user() -> Circuit {
+        let mut circuit = Circuit::new(2);
+        circuit.name = Some(String::from("user"));
+        circuit.add_component(GateType::Input, Vec::new(), Option::None);
+        circuit.add_component(GateType::Input, Vec::new(), Option::None);
+        circuit.add_intergrated_circuit(half_adder(1), vec![0, 1], vec![4, 5]);
+        circuit.edit_component(2, GateType::Buffer, vec![0], Option::None);
+        circuit.edit_component(3, GateType::Buffer, vec![1], Option::None);
+        circuit
+    }
+
+    #[test]
+    fn savelib_then_loadlib_gives_the_same_circuits() {
+        let fp = temp_path("library.lo");
+        let mut catalogue = vec![half_adder(1), user()];
+        let text = run(&mut Circuit::new(3), &mut catalogue, &[&format!("SAVELIB {}", fp)]);
+        assert!(text.ends_with(&format!("Saved 2 Circuit(s) to {}\n", fp)), "{}", text);
+
+        let mut loaded = Vec::new();
+        let text = run(&mut Circuit::new(3), &mut loaded, &[&format!("LOADLIB {}", fp)]);
+        std::fs::remove_file(&fp).unwrap();
+        assert!(text.ends_with(&format!("Loaded 2 Circuit(s) from {}\n", fp)), "{}", text);
+        assert_eq!(loaded.iter().map(definition).collect::<Vec<Circuit>>(), catalogue.iter().map(definition).collect::<Vec<Circuit>>());
+    }
+
+    #[test]
+    fn included_libraries_give_their_circuits_as_ics() {
+        let (inner, outer) = (temp_path("included.lo"), temp_path("including.lo"));
+        save_library(&[half_adder(1)], &inner).unwrap();
+        let mut text = Vec::new();
+        user().write_lo(&mut text, true).unwrap();
+        let included = Path::new(&inner).file_name().unwrap().to_string_lossy().to_string();
+        std::fs::write(&outer, format!("{} {}\n{}", INCLUDECODE, included, String::from_utf8(text).unwrap())).unwrap();
+
+        let mut catalogue = Vec::new();
+        let ids = load_library(&outer, &mut 0, &mut catalogue);
+        std::fs::remove_file(&inner).unwrap();
+        std::fs::remove_file(&outer).unwrap();
+        assert_eq!(ids.unwrap(), vec![1, 2]);
+        assert_eq!(definition(&catalogue[1]), definition(&user()));
+
+        let missing = temp_path("missing_include.lo");
+        std::fs::write(&missing, "INCLUDE nowhere.lo\n").unwrap();
+        let error = load_library(&missing, &mut 0, &mut Vec::new()).unwrap_err();
+        std::fs::remove_file(&missing).unwrap();
+        assert!(error.to_string().contains("included from"), "{}", error);
+    }
+
+    #[test]
+    fn different_circuits_with_one_name_are_not_saved() {
+        let fp = temp_path("clash.lo");
+        let mut other = half_adder(3);
+        other.edit_component(2, GateType::Or, vec![0, 1], Option::None);
+        let error = save_library(&[half_adder(1), other.clone()], &fp).unwrap_err();
+        assert!(error.to_string().contains("different circuits called \"half_adder\""), "{}", error);
+
+        // The same circuit twice, or as an IC after being stepped, is fine
+        let mut stepped = user();
+        stepped.set_component(0, true);
+        stepped.step();
+        assert_eq!(save_library(&[half_adder(1), half_adder(4), stepped], &fp).unwrap(), (2, Vec::new()));
+        let mut clash = user();
+        clash.intergrated_circuits[0].circuit = other;
+        assert!(save_library(&[half_adder(1), clash], &fp).is_err());
+        std::fs::remove_file(&fp).unwrap();
+    }
+}
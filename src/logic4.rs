@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+use crate::GateType;
+
+// Signals for the four valued mode, where a wire can also be unknown (X), like a flip flop
+// nobody has reset, or not driven at all (Z). Gates read Z the same as X, since an input left
+// floating could be anything, so Z only ever shows up on Tristates, Wires and Outputs.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Zero,
+    One,
+    X,
+    Z,
+}
+
+impl Level {
+    pub fn from_bool(state: bool) -> Level {
+        if state { Level::One } else { Level::Zero }
+    }
+    pub fn parse(text: &str) -> Option<Level> {
+        match text {
+            "TRUE" | "ON" | "1" => Some(Level::One),
+            "FALSE" | "OFF" | "0" => Some(Level::Zero),
+            "X" => Some(Level::X),
+            "Z" => Some(Level::Z),
+            _ => Option::None,
+        }
+    }
+    fn not(self) -> Level {
+        match self {
+            Level::Zero => Level::One,
+            Level::One => Level::Zero,
+            _ => Level::X,
+        }
+    }
+    // What a gate sees on one of its inputs
+    fn read(self) -> Level {
+        if self == Level::Z { Level::X } else { self }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Level::Zero => '0',
+            Level::One => '1',
+            Level::X => 'X',
+            Level::Z => 'Z',
+        })
+    }
+}
+
+// A 0 on any input of an And decides it whatever the others are, otherwise any unknown input
+// leaves it unknown
+fn and(inputs: &[Level]) -> Level {
+    if inputs.contains(&Level::Zero) { Level::Zero }
+    else if inputs.iter().all(|i| *i == Level::One) { Level::One }
+    else { Level::X }
+}
+
+fn or(inputs: &[Level]) -> Level {
+    if inputs.contains(&Level::One) { Level::One }
+    else if inputs.iter().all(|i| *i == Level::Zero) { Level::Zero }
+    else { Level::X }
+}
+
+fn xor(a: Level, b: Level) -> Level {
+    match (a, b) {
+        (Level::Zero | Level::One, Level::Zero | Level::One) => Level::from_bool(a != b),
+        _ => Level::X,
+    }
+}
+
+// Every driver that isn't letting go of a Wire has to agree on its level, otherwise they are
+// fighting over it and nobody knows what it is. With nothing driving it the pull wins.
+pub fn wire(drivers: &[Level], pull: Option<bool>) -> Level {
+    let mut driven = drivers.iter().filter(|d| **d != Level::Z);
+    match driven.next() {
+        Some(first) if driven.all(|d| d == first) => *first,
+        Some(_) => Level::X,
+        Option::None => pull.map(Level::from_bool).unwrap_or(Level::Z),
+    }
+}
+
+// One driver of a Wire pulling it to 0 while another pulls it to 1, which would short in hardware
+pub fn contention(drivers: &[Level]) -> bool {
+    drivers.contains(&Level::Zero) && drivers.contains(&Level::One)
+}
+
+// The next level of a gate from the levels of its inputs. Like the two valued step, a gate
+// with nothing in is off, except an Output or a Wire, which have nothing driving them.
+pub fn resolve(gate_type: &GateType, own: Level, inputs: &[Level], pull: Option<bool>) -> Level {
+    if *gate_type == GateType::Input {
+        return own;
+    }
+    if *gate_type == GateType::Wire {
+        return wire(inputs, pull);
+    }
+    if inputs.is_empty() {
+        return if *gate_type == GateType::Output { Level::Z } else { Level::Zero };
+    }
+    // Outputs are only there to be looked at, so they show a Z as it is
+    if *gate_type == GateType::Output {
+        return inputs[0];
+    }
+    let inputs: Vec<Level> = inputs.iter().map(|i| i.read()).collect();
+    let second = inputs.get(1).copied().unwrap_or(Level::Zero);
+    match gate_type {
+        GateType::Input | GateType::Output | GateType::Wire => own,
+        GateType::Buffer => inputs[0],
+        GateType::Not => inputs[0].not(),
+        GateType::And => and(&inputs),
+        GateType::Nand => and(&inputs).not(),
+        GateType::Or => or(&inputs),
+        GateType::Nor => or(&inputs).not(),
+        GateType::Xor => xor(inputs[0], second),
+        GateType::Nxor => xor(inputs[0], second).not(),
+        // The second input enables it, and one without is always enabled
+        GateType::Tristate => match inputs.get(1).copied().unwrap_or(Level::One) {
+            Level::One => inputs[0],
+            Level::Zero => Level::Z,
+            _ => Level::X,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [Level; 4] = [Level::Zero, Level::One, Level::X, Level::Z];
+
+    // Every pair of levels in, in the order 0 1 X Z for the first then the second, as one row each
+    fn table(gate_type: GateType, pull: Option<bool>) -> Vec<String> {
+        LEVELS.iter().map(|a| LEVELS.iter().map(|b| resolve(&gate_type, Level::X, &[*a, *b], pull).to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn gates_read_z_as_x() {
+        for (gate_type, rows) in [
+            (GateType::And, ["0000", "01XX", "0XXX", "0XXX"]),
+            (GateType::Nand, ["1111", "10XX", "1XXX", "1XXX"]),
+            (GateType::Or, ["01XX", "1111", "X1XX", "X1XX"]),
+            (GateType::Nor, ["10XX", "0000", "X0XX", "X0XX"]),
+            (GateType::Xor, ["01XX", "10XX", "XXXX", "XXXX"]),
+            (GateType::Nxor, ["10XX", "01XX", "XXXX", "XXXX"]),
+            // The second input enables it, and not knowing whether it is enabled is X
+            (GateType::Tristate, ["Z0XX", "Z1XX", "ZXXX", "ZXXX"]),
+        ] {
+            assert_eq!(table(gate_type.clone(), Option::None), rows, "{:?}", gate_type);
+        }
+        let single = |gate_type: GateType| -> String { LEVELS.iter().map(|l| resolve(&gate_type, Level::X, &[*l], Option::None).to_string()).collect() };
+        assert_eq!(single(GateType::Buffer), "01XX");
+        assert_eq!(single(GateType::Not), "10XX");
+        assert_eq!(single(GateType::Tristate), "01XX");
+        assert_eq!(single(GateType::Output), "01XZ");
+        assert_eq!(resolve(&GateType::Input, Level::Z, &[Level::One], Option::None), Level::Z);
+        assert_eq!(resolve(&GateType::And, Level::X, &[], Option::None), Level::Zero);
+        assert_eq!(resolve(&GateType::Output, Level::X, &[], Option::None), Level::Z);
+    }
+
+    #[test]
+    fn wires_take_what_their_drivers_agree_on() {
+        // Two drivers disagreeing, or one not known, is X, and Z lets go
+        assert_eq!(table(GateType::Wire, Option::None), ["0XX0", "X1X1", "XXXX", "01XZ"]);
+        assert_eq!(table(GateType::Wire, Some(true)), ["0XX0", "X1X1", "XXXX", "01X1"]);
+        assert_eq!(table(GateType::Wire, Some(false)), ["0XX0", "X1X1", "XXXX", "01X0"]);
+        assert_eq!(resolve(&GateType::Wire, Level::One, &[], Option::None), Level::Z);
+        assert_eq!(wire(&[Level::Z, Level::One, Level::Z, Level::One], Option::None), Level::One);
+
+        assert!(contention(&[Level::Zero, Level::Z, Level::One]));
+        assert!(!contention(&[Level::Zero, Level::Z, Level::Zero]));
+        assert!(!contention(&[Level::One, Level::X]));
+        assert!(!contention(&[Level::Z, Level::Z]));
+    }
+}
@@ -0,0 +1,640 @@
+use std::collections::HashMap;
+
+use crate::{invalid_data, Circuit, Gate, GateType, Port, IC};
+
+type Point = (i32, i32);
+
+// Logisim keeps everything in XML. Only elements and their attributes matter here, any text
+// between them is dropped.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|a| a.0 == name).map(|a| a.1.as_str())
+    }
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+    // Components and circuits keep their settings as `<a name="..." val="..."/>`
+    fn setting(&self, name: &str) -> Option<&str> {
+        self.children("a").find(|a| a.attribute("name") == Some(name)).and_then(|a| a.attribute("val"))
+    }
+}
+
+enum Part {
+    Pin { input: bool, facing: u8, label: Option<String> },
+    Constant(bool),
+    Gate { gate_type: GateType, inputs: Vec<(Point, bool)>, label: Option<String> }, // Where each input is; Whether it is negated
+    Tunnel(String),
+    Subcircuit { name: String, facing: u8 },
+}
+
+struct Sheet {
+    name: String,
+    components: Vec<(Point, Part)>,
+    wires: Vec<(Point, Point)>,
+    appearance: Appearance,
+}
+
+enum Appearance {
+    Classic,
+    Custom(Vec<(Point, Point)>, u8), // Where each pin is in the circuit and where its port is on the box; Anchor facing
+    Unsupported(String),
+}
+
+struct Built {
+    circuit: Circuit,
+    ports: Vec<(Point, u32, bool)>, // Offset from the anchor facing east; Internal id; Whether it is an input
+}
+
+// Every circuit in the file becomes a circuit in the catalogue, with the subcircuits it uses
+// as ICs. Wires are joined up by where their ends are, the same as Logisim does it.
+pub fn import_logisim(fp: &str, id: &mut u32, catalogue: &mut Vec<Circuit>) -> std::io::Result<Vec<u32>> {
+    let source = std::fs::read_to_string(fp)?;
+    let project = parse_xml(&source)?;
+    if project.name != "project" {
+        return Err(invalid_data(String::from("This isn't a Logisim project")));
+    }
+
+    // Logisim-evolution gates have 2 inputs unless they say otherwise, Logisim 2 gates have 5
+    let evolution = source.contains("Logisim-evolution")
+        || project.attribute("source").and_then(|s| s.split('.').next()).and_then(|s| s.parse::<u32>().ok()).unwrap_or(0) >= 3;
+    let libraries: HashMap<&str, &str> = project.children("lib")
+        .filter_map(|l| Some((l.attribute("name")?, l.attribute("desc")?)))
+        .collect();
+
+    let mut problems = Vec::new();
+    let sheets: Vec<Sheet> = project.children("circuit").map(|c| read_sheet(c, &libraries, evolution, &mut problems)).collect();
+    if !problems.is_empty() {
+        return Err(invalid_data(format!("These parts of the file aren't supported:\n{}", problems.join("\n"))));
+    }
+    if sheets.is_empty() {
+        return Err(invalid_data(String::from("There are no circuits in the file")));
+    }
+
+    let mut built: Vec<Option<Built>> = sheets.iter().map(|_| Option::None).collect();
+    let mut next_id = *id;
+    for i in 0..sheets.len() {
+        build(i, &sheets, &mut built, &mut Vec::new(), &mut next_id)?;
+    }
+
+    *id = next_id;
+    let circuits: Vec<Circuit> = built.into_iter().flatten().map(|b| b.circuit).collect();
+    let ids = circuits.iter().map(|c| c.id).collect();
+    catalogue.extend(circuits);
+    Ok(ids)
+}
+
+fn read_sheet(element: &Element, libraries: &HashMap<&str, &str>, evolution: bool, problems: &mut Vec<String>) -> Sheet {
+    let name = element.attribute("name").unwrap_or("").to_string();
+    let mut components = Vec::new();
+    let mut wires = Vec::new();
+
+    for wire in element.children("wire") {
+        match (wire.attribute("from").and_then(point), wire.attribute("to").and_then(point)) {
+            (Some(from), Some(to)) => wires.push((from, to)),
+            _ => problems.push(format!("{}: a wire has no ends", name)),
+        }
+    }
+
+    for comp in element.children("comp") {
+        let kind = comp.attribute("name").unwrap_or("");
+        let Some(loc) = comp.attribute("loc").and_then(point) else {
+            problems.push(format!("{}: {} has no location", name, kind));
+            continue;
+        };
+        let library = comp.attribute("lib").map(|l| libraries.get(l).copied().unwrap_or(l));
+        let mut problem = |what: &str| problems.push(format!("{}: {} at ({},{}) {}", name, kind, loc.0, loc.1, what));
+        let facing = match comp.setting("facing").map(facing).unwrap_or(Some(0)) {
+            Some(f) => f,
+            None => {problem("faces a way that doesn't exist"); continue}
+        };
+        if comp.setting("width").is_some_and(|w| w != "1") {
+            problem("is more than one bit wide");
+            continue;
+        }
+        let label = comp.setting("label").filter(|l| !l.is_empty()).map(|l| l.to_string());
+
+        let part = match (library, kind) {
+            (None, _) => Part::Subcircuit { name: kind.to_string(), facing },
+            (Some("#Wiring"), "Pin") => Part::Pin { input: comp.setting("output") != Some("true"), facing, label },
+            (Some("#Wiring"), "Tunnel") => Part::Tunnel(label.unwrap_or_default()),
+            (Some("#Wiring"), "Constant") => Part::Constant(comp.setting("value").map(|v| v.trim_start_matches("0x") != "0").unwrap_or(true)),
+            (Some("#Wiring"), "Power") => Part::Constant(true),
+            (Some("#Wiring"), "Ground") => Part::Constant(false),
+            // These only show things and don't change how the circuit works
+            (Some("#Wiring"), "Probe") | (Some("#Base"), "Text") | (Some("#Base"), "Label") => continue,
+            (Some("#Gates"), "NOT Gate") | (Some("#Gates"), "Buffer") => {
+                let gate_type = if kind == "Buffer" {GateType::Buffer} else {GateType::Not};
+                let length = match comp.setting("size") {
+                    Some("20") | Some("narrow") => 20,
+                    Some(_) | None if kind == "Buffer" => 20,
+                    _ => 30,
+                };
+                Part::Gate { gate_type, inputs: vec![(add(loc, rotate((-length, 0), facing)), false)], label }
+            }
+            (Some("#Gates"), _) => {
+                let (gate_type, bonus) = match kind {
+                    "AND Gate" => (GateType::And, 0),
+                    "OR Gate" => (GateType::Or, 0),
+                    "NAND Gate" => (GateType::Nand, 10),
+                    "NOR Gate" => (GateType::Nor, 10),
+                    "XOR Gate" => (GateType::Xor, 10),
+                    "XNOR Gate" => (GateType::Nxor, 20),
+                    _ => {problem("isn't a gate simlo has"); continue}
+                };
+                if comp.setting("xor") == Some("1") {
+                    problem("is a one hot XOR");
+                    continue;
+                }
+                let size = match comp.setting("size") {
+                    Some("30") | Some("narrow") => 30,
+                    Some("70") | Some("wide") => 70,
+                    _ => 50,
+                };
+                let count = comp.setting("inputs").and_then(|i| i.parse().ok()).unwrap_or(if evolution {2} else {5});
+                let inputs = (0..count).map(|i| {
+                    let negated = comp.setting(&format!("negate{}", i)) == Some("true");
+                    (add(loc, rotate(gate_input(size, bonus, count, i), facing)), negated)
+                }).collect();
+                Part::Gate { gate_type, inputs, label }
+            }
+            (Some(library), _) => {problem(&format!("from {} isn't supported", library.trim_start_matches('#'))); continue}
+        };
+        components.push((loc, part));
+    }
+
+    let appearance = match element.children("appear").next() {
+        Some(appear) => {
+            let centre = |e: &Element| -> Option<Point> {
+                let number = |n: &str| e.attribute(n).and_then(|v| v.parse::<i32>().ok());
+                Some((number("x")? + number("width")? / 2, number("y")? + number("height")? / 2))
+            };
+            match appear.children("circ-anchor").next() {
+                Some(anchor) => {
+                    let origin = centre(anchor).unwrap_or((0, 0));
+                    let anchor_facing = anchor.attribute("facing").and_then(facing).unwrap_or(0);
+                    let ports = appear.children("circ-port")
+                        .filter_map(|p| Some((p.attribute("pin").and_then(point)?, centre(p)?)))
+                        .map(|(pin, port)| (pin, (port.0 - origin.0, port.1 - origin.1)))
+                        .collect();
+                    Appearance::Custom(ports, anchor_facing)
+                }
+                None => Appearance::Unsupported(String::from("its appearance has no anchor")),
+            }
+        }
+        None if element.setting("appearance").is_some_and(|a| a != "classic") || (evolution && element.setting("appearance").is_none()) => {
+            Appearance::Unsupported(String::from("only the Classic Logisim or a custom appearance can be used for subcircuits"))
+        }
+        None => Appearance::Classic,
+    };
+
+    Sheet { name, components, wires, appearance }
+}
+
+// Where input `index` of a gate is from its output when it faces east, worked out the same way
+// Logisim lays them out. `bonus` is the extra length for a negated output or the XOR curve.
+fn gate_input(size: i32, bonus: i32, inputs: i32, index: i32) -> Point {
+    let (start, distance, lower_even) = if inputs <= 3 {
+        if size < 40 {(-5, 10, 10)} else if size < 60 || inputs <= 2 {(-10, 20, 20)} else {(-15, 30, 30)}
+    } else if inputs == 4 && size >= 60 {
+        (-5, 20, 0)
+    } else {
+        (-5, 10, 10)
+    };
+    let dy = if inputs % 2 == 1 {
+        start * (inputs - 1) + distance * index
+    } else {
+        start * inputs + distance * index + if index >= inputs / 2 {lower_even} else {0}
+    };
+    (-(size + bonus), dy)
+}
+
+// Where each pin of a circuit is on the box it is drawn as when used as a subcircuit, relative to
+// the anchor, which is what the location of the subcircuit is
+fn port_offsets(sheet: &Sheet) -> std::io::Result<Vec<(usize, Point)>> {
+    let pins: Vec<(usize, Point, u8)> = sheet.components.iter().enumerate()
+        .filter_map(|(i, (loc, part))| if let Part::Pin { facing, .. } = part {Some((i, *loc, *facing))} else {Option::None})
+        .collect();
+
+    let custom = match &sheet.appearance {
+        Appearance::Unsupported(reason) => return Err(invalid_data(format!("{} can't be used as a subcircuit, {}", sheet.name, reason))),
+        Appearance::Custom(ports, anchor_facing) => Some((ports, *anchor_facing)),
+        Appearance::Classic => Option::None,
+    };
+    if let Some((ports, anchor_facing)) = custom {
+        return Ok(pins.iter().filter_map(|(i, loc, _)| {
+            let offset = ports.iter().find(|p| p.0 == *loc)?.1;
+            Some((*i, rotate(offset, (4 - anchor_facing) % 4)))
+        }).collect());
+    }
+
+    // Pins go on the side of the box opposite the way they face, in order along it
+    let mut edges: [Vec<(usize, Point)>; 4] = Default::default(); // East, North, West, South
+    for (i, loc, facing) in pins.iter() {
+        edges[((facing + 2) % 4) as usize].push((*i, *loc));
+    }
+    for (edge, pins) in edges.iter_mut().enumerate() {
+        if edge % 2 == 0 {pins.sort_by_key(|p| (p.1 .1, p.1 .0))} else {pins.sort_by_key(|p| (p.1 .0, p.1 .1))}
+    }
+    let [east, north, west, south] = edges.each_ref().map(|e| e.len() as i32);
+    let (most_vertical, most_horizontal) = (north.max(south), east.max(west));
+    let offset = |facing: i32, opposite: i32, others: i32| {
+        let most = facing.max(opposite);
+        let start = match most {
+            0 | 1 => if others == 0 {15} else {10},
+            2 => 10,
+            _ => if others == 0 {5} else {10},
+        };
+        start + 10 * ((most - facing) / 2)
+    };
+    let dimension = |this: i32, others: i32| if this < 3 {30} else if others == 0 {10 * this} else {10 * this + 10};
+    let (offset_north, offset_south) = (offset(north, south, most_horizontal), offset(south, north, most_horizontal));
+    let (offset_east, offset_west) = (offset(east, west, most_vertical), offset(west, east, most_vertical));
+    let (width, height) = (dimension(most_vertical, most_horizontal), dimension(most_horizontal, most_vertical));
+
+    let anchor = if east > 0 {(width, offset_east)}
+        else if north > 0 {(offset_north, 0)}
+        else if west > 0 {(0, offset_west)}
+        else if south > 0 {(offset_south, height)}
+        else {(0, 0)};
+
+    let mut offsets = Vec::new();
+    for (edge, pins) in edges.iter().enumerate() {
+        for (n, (i, _)) in pins.iter().enumerate() {
+            let n = n as i32 * 10;
+            let place = match edge {
+                0 => (width, offset_east + n),
+                1 => (offset_north + n, 0),
+                2 => (0, offset_west + n),
+                _ => (offset_south + n, height),
+            };
+            offsets.push((*i, (place.0 - anchor.0, place.1 - anchor.1)));
+        }
+    }
+    Ok(offsets)
+}
+
+fn build(index: usize, sheets: &[Sheet], built: &mut Vec<Option<Built>>, path: &mut Vec<usize>, id: &mut u32) -> std::io::Result<()> {
+    if built[index].is_some() {
+        return Ok(());
+    }
+    let sheet = &sheets[index];
+    if path.contains(&index) {
+        return Err(invalid_data(format!("{} ends up using itself", sheet.name)));
+    }
+    path.push(index);
+    for (_, part) in sheet.components.iter() {
+        if let Part::Subcircuit { name, .. } = part {
+            let Some(dependency) = sheets.iter().position(|s| &s.name == name) else {
+                return Err(invalid_data(format!("{} uses {}, which isn't in the file", sheet.name, name)));
+            };
+            build(dependency, sheets, built, path, id)?;
+        }
+    }
+    path.pop();
+
+    *id += 1;
+    built[index] = Some(lower(sheet, *id, sheets, built)?);
+    Ok(())
+}
+
+enum Reader {
+    Gate(usize, bool),          // Gate index; Negated
+    Ic(usize, u32, String),     // IC; Internal id; Port label
+    Output(usize),              // Gate index
+}
+
+// Every gate, Input pin, constant and subcircuit output drives one gate. Then each wire is worked
+// out by joining everything that touches it, and whatever reads from it gets the gate driving it.
+fn lower(sheet: &Sheet, id: u32, sheets: &[Sheet], built: &[Option<Built>]) -> std::io::Result<Built> {
+    let mut gates: Vec<(GateType, Vec<u32>, Option<String>)> = Vec::new();
+    let mut drivers: Vec<(Point, u32)> = Vec::new();
+    let mut readers: Vec<(Point, Reader)> = Vec::new();
+    let mut ics: Vec<(Circuit, Vec<Port>, Vec<Port>)> = Vec::new();
+    let mut pins: Vec<(usize, u32)> = Vec::new(); // Component; Gate id
+
+    // Inputs go first and Outputs last, each from the top of the circuit down
+    let mut order: Vec<usize> = (0..sheet.components.len()).collect();
+    order.sort_by_key(|i| {
+        let (loc, part) = &sheet.components[*i];
+        let rank = match part {
+            Part::Pin { input: true, .. } => 0,
+            Part::Pin { input: false, .. } => 2,
+            _ => 1,
+        };
+        (rank, loc.1, loc.0)
+    });
+
+    let mut constants: [Option<u32>; 2] = [Option::None; 2];
+    for i in order {
+        let (loc, part) = &sheet.components[i];
+        match part {
+            Part::Pin { input: true, label, .. } => {
+                pins.push((i, gates.len() as u32));
+                drivers.push((*loc, gates.len() as u32));
+                gates.push((GateType::Input, Vec::new(), label.clone()));
+            }
+            Part::Pin { input: false, label, .. } => {
+                pins.push((i, gates.len() as u32));
+                readers.push((*loc, Reader::Output(gates.len())));
+                gates.push((GateType::Output, Vec::new(), label.clone()));
+            }
+            Part::Constant(value) => {
+                // A Buffer with nothing in is always off and a Not of that is always on
+                if constants[0].is_none() {
+                    constants[0] = Some(gates.len() as u32);
+                    gates.push((GateType::Buffer, Vec::new(), Some(String::from("0"))));
+                }
+                if *value && constants[1].is_none() {
+                    constants[1] = Some(gates.len() as u32);
+                    gates.push((GateType::Not, vec![constants[0].unwrap()], Some(String::from("1"))));
+                }
+                drivers.push((*loc, constants[*value as usize].unwrap()));
+            }
+            Part::Gate { gate_type, inputs, label } => {
+                for (input, negated) in inputs.iter() {
+                    readers.push((*input, Reader::Gate(gates.len(), *negated)));
+                }
+                drivers.push((*loc, gates.len() as u32));
+                gates.push((gate_type.clone(), Vec::new(), label.clone()));
+            }
+            Part::Tunnel(_) => {}
+            Part::Subcircuit { name, facing } => {
+                let index = sheets.iter().position(|s| &s.name == name).unwrap();
+                let Some(sub) = &built[index] else { continue };
+                let mut outputs = Vec::new();
+                for (offset, internal, input) in sub.ports.iter() {
+                    let at = add(*loc, rotate(*offset, *facing));
+                    let label = sub.circuit.gates.iter().find(|g| g.id == *internal).and_then(|g| g.label.clone()).unwrap_or(format!("{}", internal));
+                    let label = format!("{}.{}", name, label);
+                    if *input {
+                        readers.push((at, Reader::Ic(ics.len(), *internal, label)));
+                    } else {
+                        let external = gates.len() as u32;
+                        drivers.push((at, external));
+                        gates.push((GateType::Buffer, Vec::new(), Some(label)));
+                        outputs.push([(external as usize, external), (sub.circuit.index_of(*internal, 0), *internal)]);
+                    }
+                }
+                ics.push((sub.circuit.clone(), Vec::new(), outputs));
+            }
+        }
+    }
+
+    // Everything that touches the same point, either end of a wire, a point along a wire or a
+    // tunnel with the same label is on the same net
+    let mut nets = Nets::default();
+    for point in drivers.iter().map(|d| d.0).chain(readers.iter().map(|r| r.0)) {
+        nets.find(point);
+    }
+    for (from, to) in sheet.wires.iter() {
+        nets.union(*from, *to);
+    }
+    let points: Vec<Point> = nets.points.keys().copied().collect();
+    for (from, to) in sheet.wires.iter() {
+        for point in points.iter().filter(|p| on_wire(**p, *from, *to)) {
+            nets.union(*point, *from);
+        }
+    }
+    let mut tunnels: HashMap<&str, Point> = HashMap::new();
+    for (loc, part) in sheet.components.iter() {
+        if let Part::Tunnel(label) = part {
+            match tunnels.get(label.as_str()) {
+                Some(other) => nets.union(*loc, *other),
+                None => {tunnels.insert(label, *loc); nets.find(*loc);}
+            }
+        }
+    }
+
+    let mut driven: HashMap<usize, u32> = HashMap::new();
+    for (point, gate) in drivers.iter() {
+        let net = nets.find(*point);
+        if driven.get(&net).is_some_and(|other| other != gate) {
+            return Err(invalid_data(format!("{}: the wire at ({},{}) is driven by more than one thing", sheet.name, point.0, point.1)));
+        }
+        driven.insert(net, *gate);
+    }
+
+    // Inputs with nothing driving them are left off, like Logisim ignores them
+    for (point, reader) in readers {
+        let Some(source) = driven.get(&nets.find(point)).copied() else { continue };
+        match reader {
+            Reader::Gate(gate, false) | Reader::Output(gate) => gates[gate].1.push(source),
+            Reader::Gate(gate, true) => {
+                let not = gates.len() as u32;
+                gates.push((GateType::Not, vec![source], Option::None));
+                gates[gate].1.push(not);
+            }
+            Reader::Ic(ic, internal, label) => {
+                let external = gates.len() as u32;
+                gates.push((GateType::Buffer, vec![source], Some(label)));
+                let internal_index = ics[ic].0.index_of(internal, 0);
+                ics[ic].1.push([(external as usize, external), (internal_index, internal)]);
+            }
+        }
+    }
+
+    // simlo's XOR only looks at two inputs so bigger ones are made from a chain of them. With
+    // one input it is the same as a Buffer, or a Not for XNOR.
+    for gate in 0..gates.len() {
+        if ![GateType::Xor, GateType::Nxor].contains(&gates[gate].0) {
+            continue;
+        }
+        let inputs = gates[gate].1.clone();
+        if inputs.len() == 1 {
+            gates[gate].0 = if gates[gate].0 == GateType::Xor {GateType::Buffer} else {GateType::Not};
+        }
+        if inputs.len() > 2 {
+            let mut chain = inputs[0];
+            for input in inputs[1..inputs.len() - 1].iter() {
+                gates.push((GateType::Xor, vec![chain, *input], Option::None));
+                chain = gates.len() as u32 - 1;
+            }
+            gates[gate].1 = vec![chain, inputs[inputs.len() - 1]];
+        }
+    }
+
+    let mut circuit = Circuit::new(id);
+    circuit.name = Some(sheet.name.clone());
+    for (gate_type, inputs, label) in gates {
+        // Ids are the same as indexes here so they are also where to find each input
+        let inputs = inputs.into_iter().map(|i| (i as usize, i)).collect();
+        circuit.gates.push(Gate::new(gate_type, circuit.id_counter, inputs, label));
+        circuit.id_counter += 1;
+    }
+    for (ic, inputs, outputs) in ics {
+        circuit.intergrated_circuits.push(IC::new(ic, inputs, outputs));
+    }
+
+    let mut ports = Vec::new();
+    // Only circuits used as subcircuits need to know where their ports are
+    let used = sheets.iter().any(|s| s.components.iter().any(|c| matches!(&c.1, Part::Subcircuit { name, .. } if name == &sheet.name)));
+    if used {
+        for (component, offset) in port_offsets(sheet)? {
+            if let Some((_, gate)) = pins.iter().find(|p| p.0 == component) {
+                ports.push((offset, *gate, circuit.gates[*gate as usize].gate_type == GateType::Input));
+            }
+        }
+    }
+    Ok(Built { circuit, ports })
+}
+
+#[derive(Default)]
+struct Nets {
+    points: HashMap<Point, usize>,
+    parents: Vec<usize>,
+}
+
+impl Nets {
+    fn find(&mut self, point: Point) -> usize {
+        let next = self.parents.len();
+        let mut net = *self.points.entry(point).or_insert(next);
+        if net == next {
+            self.parents.push(next);
+        }
+        while self.parents[net] != net {
+            self.parents[net] = self.parents[self.parents[net]];
+            net = self.parents[net];
+        }
+        net
+    }
+    fn union(&mut self, a: Point, b: Point) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+    }
+}
+
+fn on_wire(point: Point, from: Point, to: Point) -> bool {
+    (from.0.min(to.0)..=from.0.max(to.0)).contains(&point.0) && (from.1.min(to.1)..=from.1.max(to.1)).contains(&point.1)
+}
+
+fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+// Turns an offset worked out for something facing east to face the other way. Each quarter turn
+// is anticlockwise, so 1 is north, 2 is west and 3 is south.
+fn rotate(offset: Point, turns: u8) -> Point {
+    (0..turns).fold(offset, |(x, y), _| (y, -x))
+}
+
+fn facing(text: &str) -> Option<u8> {
+    match text {
+        "east" => Some(0),
+        "north" => Some(1),
+        "west" => Some(2),
+        "south" => Some(3),
+        _ => Option::None,
+    }
+}
+
+fn point(text: &str) -> Option<Point> {
+    let (x, y) = text.trim().strip_prefix('(')?.strip_suffix(')')?.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+fn parse_xml(source: &str) -> std::io::Result<Element> {
+    let mut stack: Vec<Element> = vec![Element { name: String::new(), attributes: Vec::new(), children: Vec::new() }];
+    let mut rest = source;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.split_once("-->").map(|c| c.1).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = rest.split_once('>').map(|c| c.1).unwrap_or("");
+            continue;
+        }
+        let Some(end) = tag_end(rest) else {
+            return Err(invalid_data(String::from("The XML has a tag that doesn't end")));
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().filter(|e| e.name == name.trim() && !stack.is_empty());
+            let Some(element) = element else {
+                return Err(invalid_data(format!("The XML closes {} without opening it", name.trim())));
+            };
+            stack.last_mut().unwrap().children.push(element);
+            continue;
+        }
+        let closed = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let element = Element { name: tag[..name_end].to_string(), attributes: attributes(&tag[name_end..])?, children: Vec::new() };
+        if closed {
+            stack.last_mut().unwrap().children.push(element);
+        } else {
+            stack.push(element);
+        }
+    }
+    if stack.len() > 1 {
+        return Err(invalid_data(format!("The XML doesn't close {}", stack.last().unwrap().name)));
+    }
+    stack.pop().unwrap().children.into_iter().next().ok_or(invalid_data(String::from("The file is empty")))
+}
+
+// Where the `>` closing a tag is, skipping any in quoted attribute values
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = Option::None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = Option::None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    Option::None
+}
+
+fn attributes(mut text: &str) -> std::io::Result<Vec<(String, String)>> {
+    let mut attributes = Vec::new();
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Ok(attributes);
+        }
+        let error = || invalid_data(format!("The XML has a broken attribute: {}", text));
+        let (name, value) = text.split_once('=').ok_or_else(error)?;
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|q| *q == '"' || *q == '\'').ok_or_else(error)?;
+        let (value, after) = value[1..].split_once(quote).ok_or_else(error)?;
+        attributes.push((name.trim().to_string(), unescape(value)));
+        text = after;
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").map(|h| u32::from_str_radix(h, 16).ok())
+                .unwrap_or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {out.push(c); rest = &rest[end + 1..]}
+            None => {out.push('&'); rest = &rest[1..]}
+        }
+    }
+    out.push_str(rest);
+    out
+}
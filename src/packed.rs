@@ -0,0 +1,285 @@
+use std::{fs::File, io::Write};
+
+use crate::{invalid_data, vectors, Circuit, GateType};
+
+// Simulates 64 copies of a circuit at once, one for each bit of a u64, so every gate is one
+// bitwise operation per step. It runs on the flattened circuit and steps the same way as
+// Circuit::step, with each gate worked out from the states of the step before.
+
+const MAX_INPUTS: usize = 32;
+pub const LANES: u64 = 64;
+
+// The lanes where each of the first 6 bits of the lane number is on
+const LANE_BITS: [u64; 6] = [
+    0xAAAA_AAAA_AAAA_AAAA,
+    0xCCCC_CCCC_CCCC_CCCC,
+    0xF0F0_F0F0_F0F0_F0F0,
+    0xFF00_FF00_FF00_FF00,
+    0xFFFF_0000_FFFF_0000,
+    0xFFFF_FFFF_0000_0000,
+];
+
+// A gate's output, or one of its input pins, stuck at 0 or 1 whatever drives it
+#[derive(Clone, Copy, PartialEq)]
+pub struct Fault {
+    pub gate: usize,
+    pub pin: Option<usize>,
+    pub stuck: bool,
+}
+
+pub struct Packed {
+    gates: Vec<(GateType, Vec<usize>)>,
+    states: Vec<u64>,
+    previous: Vec<u64>,
+    start: Vec<u64>,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+    names: Vec<String>,
+    fault: Option<Fault>,
+}
+
+impl Packed {
+    // Every lane starts from the states the circuit is in now
+    pub fn new(circuit: &Circuit) -> Packed {
+        let flat = circuit.flattened();
+        let gates: Vec<(GateType, Vec<usize>)> = flat.gates.iter().map(|g| (g.gate_type.clone(), g.inputs.iter().map(|i| i.1 as usize).collect())).collect();
+        let states: Vec<u64> = flat.gates.iter().map(|g| if g.state { !0 } else { 0 }).collect();
+        let of_type = |gate_type: GateType| flat.gates.iter().enumerate().filter(|(_, g)| g.gate_type == gate_type).map(|(i, _)| i).collect::<Vec<usize>>();
+        let names = flat.gates.iter().enumerate().map(|(i, g)| circuit.flat_short_name(g, i)).collect();
+        Packed { start: states.clone(), previous: states.clone(), states, inputs: of_type(GateType::Input), outputs: of_type(GateType::Output), gates, names, fault: Option::None }
+    }
+    pub fn step(&mut self) {
+        self.previous.clone_from(&self.states);
+        let previous = &self.previous;
+        for (gate, (state, (gate_type, inputs))) in self.states.iter_mut().zip(self.gates.iter()).enumerate() {
+            if *gate_type == GateType::Input {
+                continue;
+            }
+            let stuck_pin = match self.fault {
+                Some(Fault { gate: g, pin: Some(pin), stuck }) if g == gate => Some((pin, if stuck { !0 } else { 0 })),
+                _ => Option::None,
+            };
+            let mut words = inputs.iter().enumerate().map(|(pin, i)| match stuck_pin {
+                Some((stuck, word)) if stuck == pin => word,
+                _ => previous[*i],
+            });
+            *state = if inputs.is_empty() { 0 } else { match gate_type {
+                GateType::Input => *state,
+                GateType::Output | GateType::Buffer => words.next().unwrap_or(0),
+                GateType::Not => !words.next().unwrap_or(0),
+                GateType::And => words.fold(!0, |a, b| a & b),
+                GateType::Nand => !words.fold(!0, |a, b| a & b),
+                GateType::Or => words.fold(0, |a, b| a | b),
+                GateType::Nor => !words.fold(0, |a, b| a | b),
+                GateType::Xor => words.next().unwrap_or(0) ^ words.next().unwrap_or(0),
+                GateType::Nxor => !(words.next().unwrap_or(0) ^ words.next().unwrap_or(0)),
+                GateType::Tristate => words.take(2).fold(!0, |a, b| a & b),
+                GateType::Wire => words.fold(0, |a, b| a | b),
+            }};
+        }
+        self.hold_fault();
+    }
+    // Puts a fault into every lane, or takes it out with None. The states aren't changed until
+    // the next vectors are loaded.
+    pub fn set_fault(&mut self, fault: Option<Fault>) {
+        self.fault = fault;
+    }
+    fn hold_fault(&mut self) {
+        if let Some(Fault { gate, pin: Option::None, stuck }) = self.fault {
+            self.states[gate] = if stuck { !0 } else { 0 };
+        }
+    }
+    // Gates of the flattened circuit, with the gates outside of ICs first and in their own order
+    pub fn gates(&self) -> &[(GateType, Vec<usize>)] {
+        &self.gates
+    }
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+    pub fn input_names(&self) -> Vec<String> {
+        self.inputs.iter().map(|g| self.names[*g].clone()).collect()
+    }
+    pub fn output_names(&self) -> Vec<String> {
+        self.outputs.iter().map(|g| self.names[*g].clone()).collect()
+    }
+    // Puts the circuit back how it started, with one vector of Input states in each lane
+    pub fn load_lanes(&mut self, vectors: &[&[bool]]) {
+        self.states.clone_from(&self.start);
+        for (i, gate) in self.inputs.iter().enumerate() {
+            self.states[*gate] = vectors.iter().enumerate().fold(0, |word, (lane, v)| word | ((v[i] as u64) << lane));
+        }
+        self.hold_fault();
+    }
+    // Steps until nothing changes in any lane, giving back the lanes still changing if it doesn't.
+    // A circuit without loops always settles within one step per gate.
+    pub fn settle(&mut self) -> u64 {
+        for _ in 0..=self.gates.len() {
+            if self.changed_by_step() == 0 {
+                return 0;
+            }
+        }
+        self.changed_by_step()
+    }
+    fn changed_by_step(&mut self) -> u64 {
+        self.step();
+        self.previous.iter().zip(self.states.iter()).fold(0, |c, (a, b)| c | (a ^ b))
+    }
+    // Puts the circuit back how it started, with input vector `first + lane` in each lane.
+    // The first Input is the highest bit of the vector, like the left column of a truth table.
+    pub fn load_vectors(&mut self, first: u64) {
+        self.states.clone_from(&self.start);
+        let count = self.inputs.len();
+        for (i, gate) in self.inputs.iter().enumerate() {
+            let bit = count - 1 - i;
+            self.states[*gate] = match LANE_BITS.get(bit) {
+                Some(word) => *word,
+                Option::None if (first >> bit) & 1 == 1 => !0,
+                Option::None => 0,
+            };
+        }
+        self.hold_fault();
+    }
+    pub fn outputs(&self) -> Vec<u64> {
+        self.outputs.iter().map(|o| self.states[*o]).collect()
+    }
+    fn check_size(&self) -> std::io::Result<()> {
+        if self.inputs.len() > MAX_INPUTS {
+            return Err(invalid_data(format!("The circuit has {} Inputs, which is more than the {} that can be checked", self.inputs.len(), MAX_INPUTS)));
+        }
+        Ok(())
+    }
+}
+
+pub fn bits(vector: u64, count: usize) -> String {
+    (0..count).rev().map(|b| if (vector >> b) & 1 == 1 { '1' } else { '0' }).collect()
+}
+
+pub fn lane_bits(words: &[u64], lane: u64) -> String {
+    words.iter().map(|w| if (w >> lane) & 1 == 1 { '1' } else { '0' }).collect()
+}
+
+// Goes through every input vector 64 at a time, giving the first vector of each batch, how many
+// lanes are used, and the settled outputs
+fn exhaustive(packed: &mut Packed, mut visit: impl FnMut(u64, u64, &[u64]) -> std::io::Result<()>) -> std::io::Result<()> {
+    packed.check_size()?;
+    let total = 1u64 << packed.inputs.len();
+    let mut first = 0;
+    while first < total {
+        let lanes = (total - first).min(LANES);
+        packed.load_vectors(first);
+        let unsettled = packed.settle() & lane_mask(lanes);
+        if unsettled != 0 {
+            let vector = first + unsettled.trailing_zeros() as u64;
+            return Err(invalid_data(format!("The circuit never settles when the inputs are {}", bits(vector, packed.inputs.len()))));
+        }
+        visit(first, lanes, &packed.outputs())?;
+        first += lanes;
+    }
+    Ok(())
+}
+
+pub fn lane_mask(lanes: u64) -> u64 {
+    if lanes >= LANES { !0 } else { (1 << lanes) - 1 }
+}
+
+pub fn truth_table(circuit: &Circuit, fp: Option<&str>, out: &mut impl Write) -> std::io::Result<()> {
+    let mut packed = Packed::new(circuit);
+    let mut table: Vec<u8> = Vec::new();
+    writeln!(table, "{}", vectors::header(&packed.input_names(), &packed.output_names()))?;
+    let count = packed.inputs.len();
+    exhaustive(&mut packed, |first, lanes, outputs| {
+        for lane in 0..lanes {
+            writeln!(table, "{} | {}", bits(first + lane, count), lane_bits(outputs, lane))?;
+        }
+        Ok(())
+    })?;
+    match fp {
+        Some(fp) => File::create(fp)?.write_all(&table),
+        Option::None => out.write_all(&table),
+    }
+}
+
+// Inputs and Outputs are matched up by the order they are in each circuit
+pub fn equivalent(circuit: &Circuit, other: &Circuit, out: &mut impl Write) -> std::io::Result<()> {
+    let mut first_packed = Packed::new(circuit);
+    let mut second_packed = Packed::new(other);
+    if first_packed.inputs.len() != second_packed.inputs.len() || first_packed.outputs.len() != second_packed.outputs.len() {
+        return Err(invalid_data(format!(
+            "The circuits don't line up, one has {} Input(s) and {} Output(s) and the other has {} and {}",
+            first_packed.inputs.len(), first_packed.outputs.len(), second_packed.inputs.len(), second_packed.outputs.len()
+        )));
+    }
+    second_packed.check_size()?;
+
+    let count = first_packed.inputs.len();
+    let mut differences = 0u64;
+    let mut example: Option<(u64, String, String)> = Option::None;
+    exhaustive(&mut first_packed, |first, lanes, outputs| {
+        second_packed.load_vectors(first);
+        let unsettled = second_packed.settle() & lane_mask(lanes);
+        if unsettled != 0 {
+            let vector = first + unsettled.trailing_zeros() as u64;
+            return Err(invalid_data(format!("The other circuit never settles when the inputs are {}", bits(vector, count))));
+        }
+        let other_outputs = second_packed.outputs();
+        let differ = outputs.iter().zip(other_outputs.iter()).fold(0, |d, (a, b)| d | (a ^ b)) & lane_mask(lanes);
+        differences += differ.count_ones() as u64;
+        if differ != 0 && example.is_none() {
+            let lane = differ.trailing_zeros() as u64;
+            example = Some((first + lane, lane_bits(outputs, lane), lane_bits(&other_outputs, lane)));
+        }
+        Ok(())
+    })?;
+
+    match example {
+        Option::None => writeln!(out, "The circuits match for all {} input vector(s)", 1u64 << count),
+        Some((vector, ours, theirs)) => writeln!(
+            out,
+            "The circuits differ for {} of {} input vector(s), like {} which gives {} here and {} in the other",
+            differences, 1u64 << count, bits(vector, count), ours, theirs
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{stepped_outputs, wide, wide_after_del};
+
+    fn table_rows(text: &[u8]) -> Vec<String> {
+        String::from_utf8_lossy(text).lines().skip(1).map(String::from).collect()
+    }
+
+    #[test]
+    fn truth_table_matches_stepping() {
+        // Gates that read a deleted gate read it as off, the way stepping does
+        for circuit in [wide(), wide_after_del()] {
+            let mut text = Vec::new();
+            truth_table(&circuit, Option::None, &mut text).unwrap();
+            let expected: Vec<String> = (0..256).map(|v| {
+                let outputs: String = stepped_outputs(&circuit, v).iter().map(|o| if *o {'1'} else {'0'}).collect();
+                format!("{} | {}", bits(v, 8), outputs)
+            }).collect();
+            assert_eq!(table_rows(&text), expected);
+        }
+    }
+
+    #[test]
+    fn equivalent_counts_the_vectors_stepping_tells_apart() {
+        let circuit = wide();
+        let mut text = Vec::new();
+        equivalent(&circuit, &circuit.clone(), &mut text).unwrap();
+        assert_eq!(String::from_utf8_lossy(&text), "The circuits match for all 256 input vector(s)\n");
+
+        let mut other = wide();
+        other.edit_component(17, GateType::Or, vec![15, 16, 3], Option::None);
+        let differences = (0..256).filter(|v| stepped_outputs(&circuit, *v) != stepped_outputs(&other, *v)).count();
+        assert!(differences > 0);
+        let mut text = Vec::new();
+        equivalent(&circuit, &other, &mut text).unwrap();
+        assert_eq!(String::from_utf8_lossy(&text).split(", like").next().unwrap(), format!("The circuits differ for {} of 256 input vector(s)", differences));
+    }
+}
@@ -0,0 +1,66 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Barrier}, thread};
+
+use crate::{Circuit, GateType};
+
+// Steps the flattened circuit on several threads, each one looking after its own run of gates.
+// Every gate is worked out from the states of the step before, which are kept in a buffer of
+// their own, so it doesn't matter what order the threads get to them in and the result is the
+// same as calling Circuit::step that many times.
+
+pub fn step(circuit: &mut Circuit, steps: u32, threads: usize) {
+    let flat = circuit.flattened();
+    let gates: Vec<(GateType, Vec<usize>)> = flat.gates.iter().map(|g| (g.gate_type.clone(), g.inputs.iter().map(|i| i.1 as usize).collect())).collect();
+    let buffers: [Vec<AtomicBool>; 2] = [
+        flat.gates.iter().map(|g| AtomicBool::new(g.state)).collect(),
+        flat.gates.iter().map(|g| AtomicBool::new(g.state)).collect(),
+    ];
+
+    let threads = threads.clamp(1, gates.len().max(1));
+    let chunk = gates.len().div_ceil(threads);
+    let barrier = Barrier::new(threads);
+    // Each thread counts the toggles of its own gates and hands them back at the end
+    let toggles: Vec<u64> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads).map(|t| {
+            let (gates, buffers, barrier) = (&gates, &buffers, &barrier);
+            scope.spawn(move || {
+                let range = (t * chunk).min(gates.len())..((t + 1) * chunk).min(gates.len());
+                let mut toggles = vec![0; range.len()];
+                for s in 0..steps as usize {
+                    let (previous, next) = (&buffers[s % 2], &buffers[(s + 1) % 2]);
+                    for i in range.clone() {
+                        let (gate_type, inputs) = &gates[i];
+                        let read = |i: &usize| previous[*i].load(Ordering::Relaxed);
+                        let state = if inputs.is_empty() && *gate_type != GateType::Input { false } else { match gate_type {
+                            GateType::Input => previous[i].load(Ordering::Relaxed),
+                            GateType::Output | GateType::Buffer => read(&inputs[0]),
+                            GateType::Not => !read(&inputs[0]),
+                            GateType::And => inputs.iter().all(read),
+                            GateType::Nand => !inputs.iter().all(read),
+                            GateType::Or => inputs.iter().any(read),
+                            GateType::Nor => !inputs.iter().any(read),
+                            GateType::Xor => read(&inputs[0]) != inputs.get(1).map(read).unwrap_or(false),
+                            GateType::Nxor => read(&inputs[0]) == inputs.get(1).map(read).unwrap_or(false),
+                            GateType::Tristate => read(&inputs[0]) && inputs.get(1).map(read).unwrap_or(true),
+                            GateType::Wire => inputs.iter().any(read),
+                        }};
+                        if state != previous[i].load(Ordering::Relaxed) {
+                            toggles[i - range.start] += 1;
+                        }
+                        next[i].store(state, Ordering::Relaxed);
+                    }
+                    // Nobody starts the next step until every gate of this one is done
+                    barrier.wait();
+                }
+                toggles
+            })
+        }).collect();
+        handles.into_iter().flat_map(|h| h.join().expect("A stepping thread panicked")).collect()
+    });
+
+    // Before the states, as the flattened order depends on the states of IC Inputs it started with
+    circuit.unflatten_toggles(&toggles, steps as u64, &mut 0);
+    let states: Vec<bool> = buffers[steps as usize % 2].iter().map(|s| s.load(Ordering::Relaxed)).collect();
+    circuit.unflatten_states(&states, &mut 0);
+}
+
+
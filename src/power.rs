@@ -0,0 +1,117 @@
+use std::io::Write;
+
+use crate::{Circuit, Gate, GateType, GATE_TYPES};
+
+// Dynamic power goes with how often each gate switches and how much it has to charge when it
+// does, P = a C V^2 f. The toggles counted while stepping give the activity a of each gate, and
+// a weight for each type of gate stands in for its capacitance C, in no particular unit, so two
+// designs can be compared on the same run of inputs.
+
+const MAX_SHOWN: usize = 10;
+
+// Roughly how many transistors each type of gate has in CMOS, in the same order as GATE_TYPES, as
+// their gates are most of what a switching output charges. Every circuit starts with these.
+pub const WEIGHTS: [f64; GATE_TYPES.len()] = [1.0, 1.0, 4.0, 2.0, 6.0, 6.0, 4.0, 4.0, 12.0, 12.0, 6.0, 2.0];
+
+pub fn set_weight(circuit: &mut Circuit, gate_type: &GateType, weight: f64) {
+    circuit.weights[gate_type.index()] = weight;
+}
+
+// Every gate, going into ICs, with a name saying which IC it is in
+fn collect<'a>(circuit: &'a Circuit, within: Option<&str>, gates: &mut Vec<(String, &'a Gate)>) {
+    for gate in circuit.gates.iter() {
+        let name = match within {
+            Some(ic) => format!("{} in {}", gate.name(), ic),
+            Option::None => gate.name(),
+        };
+        gates.push((name, gate));
+    }
+    for ic in circuit.intergrated_circuits.iter() {
+        let ic_name = ic.circuit.name.clone().unwrap_or(format!("Circuit {}", ic.circuit.id));
+        let ic_name = match within {
+            Some(outer) => format!("{} in {}", ic_name, outer),
+            Option::None => ic_name,
+        };
+        collect(&ic.circuit, Some(&ic_name), gates);
+    }
+}
+
+pub fn report(circuit: &Circuit, out: &mut impl Write) -> std::io::Result<()> {
+    let steps = circuit.steps;
+    let weights = circuit.weights;
+    let mut gates = Vec::new();
+    collect(circuit, Option::None, &mut gates);
+    let switched = |gate: &Gate| gate.toggles as f64 * weights[gate.gate_type.index()];
+    let toggles: u64 = gates.iter().map(|g| g.1.toggles).sum();
+    let total: f64 = gates.iter().map(|g| switched(g.1)).sum();
+    writeln!(out, "{} toggle(s) over {} step(s) since counting started", toggles, steps)?;
+
+    if toggles > 0 {
+        gates.sort_by(|a, b| switched(b.1).total_cmp(&switched(a.1)).then(b.1.toggles.cmp(&a.1.toggles)));
+        writeln!(out)?;
+        writeln!(out, "Hottest gates:")?;
+        writeln!(out, "  Toggles  Activity  Switched   Share  Gate")?;
+        for (name, gate) in gates.iter().filter(|g| g.1.toggles > 0).take(MAX_SHOWN) {
+            let activity = gate.toggles as f64 / steps.max(1) as f64;
+            writeln!(out, "{:>9} {:>9.3} {:>9.1} {:>6.1}%  {}", gate.toggles, activity, switched(gate), 100.0 * switched(gate) / total.max(f64::MIN_POSITIVE), name)?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "By type of gate:")?;
+    writeln!(out, "  Type        Gates  Toggles  Weight  Switched")?;
+    for gate_type in GATE_TYPES.iter() {
+        let of_type: Vec<&Gate> = gates.iter().map(|g| g.1).filter(|g| g.gate_type == *gate_type).collect();
+        if of_type.is_empty() {
+            continue;
+        }
+        let type_toggles: u64 = of_type.iter().map(|g| g.toggles).sum();
+        let type_switched: f64 = of_type.iter().map(|g| switched(g)).sum();
+        writeln!(out, "  {:<10} {:>6} {:>8} {:>7} {:>9.1}", format!("{:?}", gate_type), of_type.len(), type_toggles, weights[gate_type.index()], type_switched)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Estimated dynamic power: {:.2} weight switched per step, {:.1} in all", total / steps.max(1) as f64, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::run, Circuit};
+
+    // A switched on then off through a Not, after counting starts with the Not already on
+    fn toggled(id: u32, weight: Option<&str>) -> String {
+        let mut circuit = Circuit::new(id);
+        let mut commands = vec!["INPUT; A", "NOT 0", "OUTPUT 1; Y", "POWER RESET", "SET 0 TRUE", "STEP 3", "SET 0 FALSE", "STEP 3"];
+        commands.extend(weight);
+        commands.push("POWER");
+        run(&mut circuit, &mut Vec::new(), &commands)
+    }
+
+    #[test]
+    fn toggles_are_weighed_by_each_circuits_weights() {
+        let weighted = toggled(1, Some("POWER NOT 5"));
+        assert!(weighted.ends_with("\
+Not gates now weigh 5
+6 toggle(s) over 12 step(s) since counting started
+
+Hottest gates:
+  Toggles  Activity  Switched   Share  Gate
+        2     0.167      10.0   71.4%  Not 1
+        2     0.167       2.0   14.3%  Input 0 A
+        2     0.167       2.0   14.3%  Output 2 Y
+
+By type of gate:
+  Type        Gates  Toggles  Weight  Switched
+  Input           1        2       1       2.0
+  Output          1        2       1       2.0
+  Not             1        2       5      10.0
+
+Estimated dynamic power: 1.17 weight switched per step, 14.0 in all
+"), "{}", weighted);
+
+        // Another circuit still has the weights it started with, over one step fewer
+        let unweighted = toggled(2, Option::None);
+        assert!(unweighted.contains("  Not             1        2       2       4.0\n"), "{}", unweighted);
+        assert!(unweighted.ends_with("Estimated dynamic power: 0.73 weight switched per step, 8.0 in all\n"), "{}", unweighted);
+    }
+}
@@ -0,0 +1,258 @@
+use std::process::{Command, Stdio};
+
+use crate::{input, layout::{Layout, Part}, Circuit, GateType};
+
+const COLUMN_WIDTH: usize = 17;
+const BOX_WIDTH: usize = 11;
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+const GREEN: &str = "\x1b[1;32m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy)]
+struct Cell {
+    wire: u8, // Which directions the wire in this cell goes
+    text: Option<char>,
+    on: bool,
+}
+
+struct Grid {
+    cells: Vec<Vec<Cell>>,
+}
+
+impl Grid {
+    fn cell(&mut self, x: usize, y: usize) -> &mut Cell {
+        if self.cells.len() <= y {
+            self.cells.resize(y + 1, Vec::new());
+        }
+        if self.cells[y].len() <= x {
+            self.cells[y].resize(x + 1, Cell { wire: 0, text: Option::None, on: false });
+        }
+        &mut self.cells[y][x]
+    }
+    fn text(&mut self, x: usize, y: usize, text: &str, on: bool) {
+        for (i, c) in text.chars().enumerate() {
+            let cell = self.cell(x + i, y);
+            cell.text = Some(c);
+            cell.on = on;
+        }
+    }
+    // Draws a wire through each of the points, only going straight across or up and down
+    fn wire(&mut self, points: &[(usize, usize)], on: bool) {
+        for pair in points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+            let mut last = (x1, y1);
+            let steps: Vec<(usize, usize)> = if y1 == y2 {
+                if x1 <= x2 {(x1+1..=x2).map(|x| (x, y1)).collect()} else {(x2..x1).rev().map(|x| (x, y1)).collect()}
+            } else if y1 <= y2 {(y1+1..=y2).map(|y| (x1, y)).collect()} else {(y2..y1).rev().map(|y| (x1, y)).collect()};
+            for step in steps {
+                let (from, to) = match (step.0 > last.0, step.0 < last.0, step.1 > last.1) {
+                    (true, _, _) => (RIGHT, LEFT),
+                    (_, true, _) => (LEFT, RIGHT),
+                    (_, _, true) => (DOWN, UP),
+                    _ => (UP, DOWN),
+                };
+                let cell = self.cell(last.0, last.1);
+                cell.wire |= from;
+                cell.on |= on;
+                let cell = self.cell(step.0, step.1);
+                cell.wire |= to;
+                cell.on |= on;
+                last = step;
+            }
+        }
+    }
+    fn render(&self, x: usize, y: usize, width: usize, height: usize) -> String {
+        let mut out = String::new();
+        for row in self.cells.iter().skip(y).take(height) {
+            let mut colour = false;
+            for cell in row.iter().skip(x).take(width) {
+                if cell.on != colour {
+                    out.push_str(if cell.on {GREEN} else {RESET});
+                    colour = cell.on;
+                }
+                out.push(cell.text.unwrap_or(match cell.wire {
+                    0 => ' ',
+                    w if w == LEFT || w == RIGHT || w == LEFT | RIGHT => '─',
+                    w if w == UP || w == DOWN || w == UP | DOWN => '│',
+                    w if w == DOWN | RIGHT => '┌',
+                    w if w == DOWN | LEFT => '┐',
+                    w if w == UP | RIGHT => '└',
+                    w if w == UP | LEFT => '┘',
+                    w if w == UP | DOWN | RIGHT => '├',
+                    w if w == UP | DOWN | LEFT => '┤',
+                    w if w == LEFT | RIGHT | DOWN => '┬',
+                    w if w == LEFT | RIGHT | UP => '┴',
+                    _ => '┼',
+                }));
+            }
+            if colour {
+                out.push_str(RESET);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// Draws the circuit with gates in columns by logic depth, like EXPORT SVG but with box drawing
+// characters. Anything that is on is green. Circuits bigger than the terminal are shown a page at
+// a time and can be moved around with WASD.
+pub fn show_schematic(circuit: &Circuit) {
+    let grid = draw(circuit);
+    let height = grid.cells.len();
+    let width = grid.cells.iter().map(|r| r.len()).max().unwrap_or(0);
+    let (page_width, page_height) = terminal_size();
+    let page_height = page_height.saturating_sub(2).max(5);
+
+    if width <= page_width && height <= page_height {
+        print!("{}", grid.render(0, 0, width, height));
+        return;
+    }
+
+    let (mut x, mut y) = (0, 0);
+    loop {
+        print!("{}", grid.render(x, y, page_width, page_height));
+        let across = width.div_ceil(page_width);
+        let down = height.div_ceil(page_height);
+        let command = input(&format!("Page {},{} of {},{} [W/A/S/D to move, Q to stop]>", x / page_width + 1, y / page_height + 1, across, down));
+        match command.trim().to_uppercase().as_str() {
+            "W" => y = y.saturating_sub(page_height),
+            "A" => x = x.saturating_sub(page_width),
+            "S" | "" => if y + page_height < height {y += page_height} else if x + page_width < width {x += page_width; y = 0} else {break},
+            "D" if x + page_width < width => x += page_width,
+            "Q" => break,
+            _ => {}
+        }
+    }
+}
+
+pub fn terminal_size() -> (usize, usize) {
+    let from_stty = Command::new("stty").arg("size").stdin(Stdio::inherit()).stderr(Stdio::null()).output().ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .and_then(|s| {
+            let mut parts = s.split_whitespace().map(|p| p.parse::<usize>().ok());
+            Some((parts.next()??, parts.next()??))
+        });
+    if let Some((rows, columns)) = from_stty {
+        return (columns, rows);
+    }
+    let env = |name: &str, default: usize| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+    (env("COLUMNS", 80), env("LINES", 24))
+}
+
+fn draw(circuit: &Circuit) -> Grid {
+    let layout = Layout::new(circuit);
+    let mut grid = Grid { cells: Vec::new() };
+
+    // Where the top left of each node is, and how tall it is
+    let mut boxes = vec![(0, 0, 0); layout.nodes.len()];
+    let mut bottom = 0;
+    for (column, nodes) in layout.columns.iter().enumerate() {
+        let mut y = 0;
+        for node in nodes.iter() {
+            let height = match &layout.nodes[*node].part {
+                Part::Gate(i) => circuit.gates[*i].inputs.len().max(2) + 2,
+                Part::Ic(i) => circuit.intergrated_circuits[*i].inputs.len().max(circuit.intergrated_circuits[*i].outputs.len()).max(2) + 2,
+                Part::Wire(..) => 1,
+            };
+            boxes[*node] = (6 + column * COLUMN_WIDTH, y, height);
+            y += height + 1;
+        }
+        bottom = bottom.max(y);
+    }
+
+    let mut loops = 0;
+    for (node, data) in layout.nodes.iter().enumerate() {
+        let (x, y, _) = boxes[node];
+        for (pin, input) in data.inputs.iter().enumerate() {
+            let Some((source, source_pin)) = *input else { continue };
+            let (sx, sy) = output_pin(&layout, source, source_pin, boxes[source]);
+            let on = output_state(&layout, source, source_pin, circuit);
+            let (tx, ty) = (x - 1, if let Part::Wire(..) = data.part {y} else {y + 1 + pin});
+
+            if layout.nodes[source].column < data.column {
+                let channel = x - 2 - (data.row * 3 + pin) % 4;
+                grid.wire(&[(sx, sy), (channel, sy), (channel, ty), (tx, ty)], on);
+            } else {
+                loops += 1;
+                let under = bottom + loops;
+                grid.wire(&[(sx, sy), (sx + 1 + loops % 3, sy), (sx + 1 + loops % 3, under), (x - 5 + loops % 3, under), (x - 5 + loops % 3, ty), (tx, ty)], on);
+            }
+        }
+        if let Part::Wire(..) = data.part {
+            grid.wire(&[(x - 1, y), (x + BOX_WIDTH, y)], output_state(&layout, node, 0, circuit));
+        }
+    }
+
+    for (node, data) in layout.nodes.iter().enumerate() {
+        let (x, y, height) = boxes[node];
+        let (title, label, on, inputs, outputs) = match &data.part {
+            Part::Wire(..) => continue,
+            Part::Gate(i) => {
+                let gate = &circuit.gates[*i];
+                let name = match gate.gate_type {
+                    GateType::Input => "IN",
+                    GateType::Output => "OUT",
+                    GateType::Buffer => "BUF",
+                    GateType::Not => "NOT",
+                    GateType::And => "AND",
+                    GateType::Or => "OR",
+                    GateType::Nand => "NAND",
+                    GateType::Nor => "NOR",
+                    GateType::Xor => "XOR",
+                    GateType::Nxor => "NXOR",
+                    GateType::Tristate => "TRI",
+                    GateType::Wire => "WIRE",
+                };
+                (format!("{} {} {}", name, gate.id, if gate.state {1} else {0}), gate.label.clone(), gate.state, gate.inputs.len(), Option::None)
+            }
+            Part::Ic(i) => {
+                let ic = &circuit.intergrated_circuits[*i];
+                (format!("IC {}", i), ic.circuit.name.clone(), false, ic.inputs.len(), Some(ic.outputs.len()))
+            }
+        };
+        grid.text(x, y, &format!("┌{}┐", "─".repeat(BOX_WIDTH - 2)), on);
+        // Gates have their output in the middle and ICs have theirs from the top down
+        for row in 1..height - 1 {
+            let left = if row <= inputs {'┤'} else {'│'};
+            let output = match outputs {
+                Some(outputs) => row <= outputs,
+                None => row == (height - 1) / 2,
+            };
+            let right = if output {'├'} else {'│'};
+            grid.text(x, y + row, &format!("{}{}{}", left, " ".repeat(BOX_WIDTH - 2), right), on);
+        }
+        grid.text(x, y + height - 1, &format!("└{}┘", "─".repeat(BOX_WIDTH - 2)), on);
+        grid.text(x + 1, y + 1, &fit(&title), on);
+        if let Some(label) = label {
+            grid.text(x + 1, y + 2, &fit(&label), on);
+        }
+    }
+    grid
+}
+
+fn fit(text: &str) -> String {
+    text.chars().take(BOX_WIDTH - 2).collect()
+}
+
+fn output_pin(layout: &Layout, node: usize, pin: usize, (x, y, height): (usize, usize, usize)) -> (usize, usize) {
+    match layout.nodes[node].part {
+        Part::Wire(..) => (x + BOX_WIDTH, y),
+        Part::Gate(_) => (x + BOX_WIDTH, y + (height - 1) / 2),
+        Part::Ic(_) => (x + BOX_WIDTH, y + 1 + pin),
+    }
+}
+
+fn output_state(layout: &Layout, node: usize, pin: usize, circuit: &Circuit) -> bool {
+    match &layout.nodes[node].part {
+        Part::Gate(i) => circuit.gates[*i].state,
+        Part::Wire(source, source_pin) => output_state(layout, *source, *source_pin, circuit),
+        Part::Ic(i) => {
+            let ic = &circuit.intergrated_circuits[*i];
+            ic.outputs.get(pin).and_then(|port| ic.circuit.gates.iter().find(|g| g.id == port[1].1)).map(|g| g.state).unwrap_or(false)
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use std::{io::{BufRead, BufReader, Write}, sync::{mpsc::{self, Sender}, Arc, Mutex}, thread};
+
+use crate::{json::{self, Json}, parse_gate_name, Circuit, GateType};
+
+// `simlo serve` speaks JSON-RPC 2.0 with one message per line, over stdin and stdout or, with
+// `--socket path`, to every client that connects to a Unix domain socket. Each circuit being
+// worked on is a session, and every client can use every session and the one shared catalogue.
+// Clients that create or subscribe to a session are sent an `outputs.changed` notification
+// whenever one of its Outputs changes.
+
+const SOCKETFLAG: &str = "--socket";
+const SETTLE_LIMIT: u32 = 1000;
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const SERVER_ERROR: i32 = -32000;
+
+type Failure = (i32, String); // Error code; Message
+type Reply = Result<Json, Failure>;
+
+struct Session {
+    circuit: Circuit,
+    subscribers: Vec<(usize, Sender<String>)>, // Connection; Where its messages go
+    outputs: Vec<(u32, bool)>,
+}
+
+struct Server {
+    sessions: Vec<Session>,
+    catalogue: Vec<Circuit>,
+    id: u32,
+    connections: usize,
+}
+
+pub fn run(args: &[String]) -> std::io::Result<()> {
+    let server = Arc::new(Mutex::new(Server { sessions: Vec::new(), catalogue: Vec::new(), id: 0, connections: 0 }));
+    match args.first().map(|a| a.as_str()) {
+        Some(SOCKETFLAG) => {
+            let fp = args.get(1).ok_or_else(|| crate::invalid_data(format!("{} needs a path", SOCKETFLAG)))?;
+            serve_socket(fp, server)
+        }
+        Some(arg) => Err(crate::invalid_data(format!("Unknown option \"{}\", use {} [path] or nothing for stdin", arg, SOCKETFLAG))),
+        Option::None => {
+            serve(std::io::stdin().lock(), std::io::stdout(), &server);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(unix)]
+fn serve_socket(fp: &str, server: Arc<Mutex<Server>>) -> std::io::Result<()> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+
+    // A socket left behind by a server that didn't shut down cleanly is replaced, anything else isn't touched
+    if std::fs::metadata(fp).map(|m| m.file_type().is_socket()).unwrap_or(false) {
+        std::fs::remove_file(fp)?;
+    }
+    let listener = UnixListener::bind(fp)?;
+    eprintln!("Listening on {}", fp);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let server = Arc::clone(&server);
+        thread::spawn(move || serve(reader, stream, &server));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_fp: &str, _server: Arc<Mutex<Server>>) -> std::io::Result<()> {
+    Err(crate::invalid_data(String::from("Unix domain sockets aren't available here, leave out --socket to use stdin")))
+}
+
+// Replies and notifications go through a channel so a notification from another client's request
+// can't land in the middle of a reply
+fn serve(reader: impl BufRead, mut writer: impl Write + Send + 'static, server: &Mutex<Server>) {
+    let (sender, receiver) = mpsc::channel::<String>();
+    let writing = thread::spawn(move || {
+        for message in receiver {
+            if writeln!(writer, "{}", message).and_then(|_| writer.flush()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let connection = {
+        let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+        server.connections += 1;
+        server.connections
+    };
+    for line in reader.lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+        server.request(&line, connection, &sender);
+    }
+
+    let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+    for session in server.sessions.iter_mut() {
+        session.subscribers.retain(|(c, _)| *c != connection);
+    }
+    drop(server);
+    drop(sender);
+    let _ = writing.join();
+}
+
+impl Server {
+    fn request(&mut self, line: &str, connection: usize, sender: &Sender<String>) {
+        let request = match json::parse(line) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = sender.send(response(Json::Null, Err((PARSE_ERROR, e.to_string()))));
+                return;
+            }
+        };
+        let id = request.get("id").cloned();
+        let method = request.get("method").and_then(|m| m.as_str());
+        let params = request.get("params").cloned().unwrap_or(Json::Object(Vec::new()));
+        let reply = match method {
+            Some(method) if matches!(params, Json::Object(_)) => self.call(method, &params, connection, sender),
+            Some(_) => Err((INVALID_PARAMS, String::from("params have to be an object"))),
+            Option::None => Err((INVALID_REQUEST, String::from("A request needs a method"))),
+        };
+        // Requests without an id are notifications and don't get a reply
+        if let Some(id) = id {
+            let _ = sender.send(response(id, reply));
+        }
+        self.notify();
+    }
+
+    fn call(&mut self, method: &str, params: &Json, connection: usize, sender: &Sender<String>) -> Reply {
+        match method {
+            "circuit.create" => {
+                let mut circuit = match params.get("from") {
+                    Some(_) => self.catalogued(number(params, "from")?)?.clone(),
+                    Option::None => Circuit::new(0),
+                };
+                if let Some(name) = text(params, "name")? {
+                    circuit.name = Some(name);
+                }
+                Ok(self.open(circuit, connection, sender))
+            }
+            "circuit.load" => {
+                let fp = path(params)?;
+                let mut circuit = Circuit::new(0);
+                circuit.load_from_file(&fp).map_err(failed)?;
+                Ok(self.open(circuit, connection, sender))
+            }
+            "circuit.save" => {
+                let fp = path(params)?;
+                self.session(params)?.circuit.save_to_file(&fp).map_err(failed)?;
+                Ok(Json::Null)
+            }
+            "circuit.close" => {
+                let id = number(params, "session")?;
+                self.session(params)?;
+                self.sessions.retain(|s| s.circuit.id != id);
+                Ok(Json::Null)
+            }
+            "circuit.list" => Ok(Json::Array(self.sessions.iter().map(|s| summary(&s.circuit)).collect())),
+            "circuit.state" => Ok(json::circuit_to_json(&self.session(params)?.circuit)),
+            "circuit.io" => {
+                let circuit = &self.session(params)?.circuit;
+                let io = |gate_type: GateType| Json::Array(circuit.gates.iter().filter(|g| g.gate_type == gate_type).map(|g| Json::Object(vec![
+                    ("id".to_string(), Json::Number(g.id as f64)),
+                    ("label".to_string(), g.label.clone().map(Json::String).unwrap_or(Json::Null)),
+                    ("state".to_string(), Json::Bool(g.state)),
+                ])).collect());
+                Ok(Json::Object(vec![("inputs".to_string(), io(GateType::Input)), ("outputs".to_string(), io(GateType::Output))]))
+            }
+            "circuit.compile" => {
+                let mut circuit = self.session(params)?.circuit.clone();
+                circuit.normalize();
+                self.id += 1;
+                circuit.id = self.id;
+                self.catalogue.push(circuit);
+                Ok(Json::Object(vec![("id".to_string(), Json::Number(self.id as f64))]))
+            }
+            "circuit.import" => {
+                let other = self.catalogued(number(params, "circuit")?)?.clone();
+                self.session(params)?.circuit.import_circuit(other);
+                Ok(Json::Null)
+            }
+            "circuit.subscribe" => {
+                let session = self.session(params)?;
+                if !session.subscribers.iter().any(|(c, _)| *c == connection) {
+                    session.subscribers.push((connection, sender.clone()));
+                }
+                Ok(Json::Null)
+            }
+            "circuit.unsubscribe" => {
+                self.session(params)?.subscribers.retain(|(c, _)| *c != connection);
+                Ok(Json::Null)
+            }
+            "gate.add" => {
+                let (gate_type, inputs, label) = gate(params)?;
+                let circuit = &mut self.session(params)?.circuit;
+                let id = circuit.id_counter;
+                circuit.add_component(gate_type, inputs, label);
+                Ok(Json::Object(vec![("id".to_string(), Json::Number(id as f64))]))
+            }
+            "gate.edit" => {
+                let id = number(params, "id")?;
+                let (gate_type, inputs, label) = gate(params)?;
+                if !self.session(params)?.circuit.edit_component(id, gate_type, inputs, label) {
+                    return Err((INVALID_PARAMS, format!("Component {} does not exist", id)));
+                }
+                Ok(Json::Null)
+            }
+            "gate.delete" => {
+                let id = number(params, "id")?;
+                if !self.session(params)?.circuit.delete_component(id) {
+                    return Err((INVALID_PARAMS, format!("Component {} does not exist", id)));
+                }
+                Ok(Json::Null)
+            }
+            "gate.set" => {
+                let state = params.get("state").and_then(|s| s.as_bool()).ok_or((INVALID_PARAMS, String::from("\"state\" has to be true or false")))?;
+                let label = text(params, "label")?;
+                let id = if params.get("id").is_some() { Some(number(params, "id")?) } else { Option::None };
+                let circuit = &mut self.session(params)?.circuit;
+                let id = match (id, label) {
+                    (Some(id), _) => id,
+                    (Option::None, Some(label)) => circuit.gates.iter().find(|g| g.label.as_deref() == Some(label.as_str())).map(|g| g.id)
+                        .ok_or((INVALID_PARAMS, format!("No component is labelled \"{}\"", label)))?,
+                    (Option::None, Option::None) => return Err((INVALID_PARAMS, String::from("Give an \"id\" or a \"label\""))),
+                };
+                if !circuit.set_component(id, state) {
+                    return Err((INVALID_PARAMS, format!("Component {} does not exist", id)));
+                }
+                Ok(Json::Null)
+            }
+            "ic.add" => {
+                let ic = self.catalogued(number(params, "circuit")?)?.clone();
+                let inputs = numbers(params, "inputs")?;
+                let outputs = numbers(params, "outputs")?;
+                let circuit = &mut self.session(params)?.circuit;
+                let first = circuit.id_counter;
+                circuit.add_intergrated_circuit(ic, inputs, outputs);
+                let ports = (first..circuit.id_counter).map(|id| Json::Number(id as f64)).collect();
+                Ok(Json::Object(vec![("ports".to_string(), Json::Array(ports))]))
+            }
+            "sim.step" => {
+                let count = if params.get("count").is_some() { number(params, "count")? } else { 1 };
+                let session = self.session(params)?;
+                for _ in 0..count {
+                    session.circuit.step();
+                }
+                Ok(Json::Object(vec![("outputs".to_string(), outputs(&session.circuit))]))
+            }
+            "sim.settle" => {
+                let limit = if params.get("limit").is_some() { number(params, "limit")? } else { SETTLE_LIMIT };
+                let session = self.session(params)?;
+                let settled = session.circuit.settle(limit);
+                Ok(Json::Object(vec![
+                    ("steps".to_string(), Json::Number(settled.unwrap_or(limit) as f64)),
+                    ("settled".to_string(), Json::Bool(settled.is_some())),
+                    ("outputs".to_string(), outputs(&session.circuit)),
+                ]))
+            }
+            "catalogue.list" => Ok(Json::Array(self.catalogue.iter().map(summary).collect())),
+            "catalogue.load" => {
+                let fp = path(params)?;
+                let mut circuit = Circuit::new(self.id + 1);
+                circuit.load_from_file(&fp).map_err(failed)?;
+                self.id += 1;
+                self.catalogue.push(circuit);
+                Ok(Json::Object(vec![("id".to_string(), Json::Number(self.id as f64))]))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("There is no method \"{}\"", method))),
+        }
+    }
+
+    fn open(&mut self, mut circuit: Circuit, connection: usize, sender: &Sender<String>) -> Json {
+        self.id += 1;
+        circuit.id = self.id;
+        let outputs = output_states(&circuit);
+        self.sessions.push(Session { circuit, subscribers: vec![(connection, sender.clone())], outputs });
+        Json::Object(vec![("session".to_string(), Json::Number(self.id as f64))])
+    }
+
+    fn session(&mut self, params: &Json) -> Result<&mut Session, Failure> {
+        let id = number(params, "session")?;
+        self.sessions.iter_mut().find(|s| s.circuit.id == id).ok_or((INVALID_PARAMS, format!("There is no session {}", id)))
+    }
+
+    fn catalogued(&self, id: u32) -> Result<&Circuit, Failure> {
+        self.catalogue.iter().find(|c| c.id == id).ok_or((INVALID_PARAMS, format!("There is no circuit {} in the catalogue", id)))
+    }
+
+    fn notify(&mut self) {
+        for session in self.sessions.iter_mut() {
+            let states = output_states(&session.circuit);
+            if states == session.outputs {
+                continue;
+            }
+            session.outputs = states;
+            let message = Json::Object(vec![
+                ("jsonrpc".to_string(), Json::String(String::from("2.0"))),
+                ("method".to_string(), Json::String(String::from("outputs.changed"))),
+                ("params".to_string(), Json::Object(vec![
+                    ("session".to_string(), Json::Number(session.circuit.id as f64)),
+                    ("outputs".to_string(), outputs(&session.circuit)),
+                ])),
+            ]).compact();
+            session.subscribers.retain(|(_, s)| s.send(message.clone()).is_ok());
+        }
+    }
+}
+
+fn response(id: Json, reply: Reply) -> String {
+    let outcome = match reply {
+        Ok(result) => ("result".to_string(), result),
+        Err((code, message)) => ("error".to_string(), Json::Object(vec![
+            ("code".to_string(), Json::Number(code as f64)),
+            ("message".to_string(), Json::String(message)),
+        ])),
+    };
+    Json::Object(vec![("jsonrpc".to_string(), Json::String(String::from("2.0"))), outcome, ("id".to_string(), id)]).compact()
+}
+
+fn output_states(circuit: &Circuit) -> Vec<(u32, bool)> {
+    circuit.gates.iter().filter(|g| g.gate_type == GateType::Output).map(|g| (g.id, g.state)).collect()
+}
+
+fn outputs(circuit: &Circuit) -> Json {
+    Json::Array(circuit.gates.iter().filter(|g| g.gate_type == GateType::Output).map(|g| Json::Object(vec![
+        ("id".to_string(), Json::Number(g.id as f64)),
+        ("label".to_string(), g.label.clone().map(Json::String).unwrap_or(Json::Null)),
+        ("state".to_string(), Json::Bool(g.state)),
+    ])).collect())
+}
+
+fn summary(circuit: &Circuit) -> Json {
+    Json::Object(vec![
+        ("id".to_string(), Json::Number(circuit.id as f64)),
+        ("name".to_string(), circuit.name.clone().map(Json::String).unwrap_or(Json::Null)),
+        ("gates".to_string(), Json::Number(circuit.gates.len() as f64)),
+    ])
+}
+
+fn invalid(e: std::io::Error) -> Failure {
+    (INVALID_PARAMS, e.to_string())
+}
+
+fn failed(e: std::io::Error) -> Failure {
+    (SERVER_ERROR, e.to_string())
+}
+
+fn number(params: &Json, key: &str) -> Result<u32, Failure> {
+    json::number(params, key).map_err(invalid)
+}
+
+fn numbers(params: &Json, key: &str) -> Result<Vec<u32>, Failure> {
+    json::array(params, key).map_err(invalid)?.iter()
+        .map(|n| n.as_u32().ok_or((INVALID_PARAMS, format!("\"{}\" should only have ids in it", key))))
+        .collect()
+}
+
+fn text(params: &Json, key: &str) -> Result<Option<String>, Failure> {
+    json::text(params, key).map_err(invalid)
+}
+
+fn path(params: &Json) -> Result<String, Failure> {
+    text(params, "path")?.ok_or((INVALID_PARAMS, String::from("Missing \"path\"")))
+}
+
+fn gate(params: &Json) -> Result<(GateType, Vec<u32>, Option<String>), Failure> {
+    let type_name = text(params, "type")?.unwrap_or_default();
+    let gate_type = parse_gate_name(&type_name).ok_or((INVALID_PARAMS, format!("\"{}\" is not a gate", type_name)))?;
+    Ok((gate_type, numbers(params, "inputs")?, text(params, "label")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Somewhere to write that the test can still read after serve has taken it
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn served(requests: &[&str]) -> Vec<String> {
+        let written = Shared(Arc::new(Mutex::new(Vec::new())));
+        let server = Mutex::new(Server { sessions: Vec::new(), catalogue: Vec::new(), id: 0, connections: 0 });
+        serve(requests.join("\n").as_bytes(), written.clone(), &server);
+        let text = String::from_utf8(written.0.lock().unwrap().clone()).unwrap();
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn bad_requests_get_their_error_codes() {
+        let replies = served(&[
+            "not json",
+            r#"{"jsonrpc": "2.0", "id": 1}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "circuit.fly"}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "sim.step", "params": [1]}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "sim.step", "params": {"session": 9}}"#,
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "circuit.load", "params": {"path": "/simlo/no/such/file.lo"}}"#,
+        ]);
+        let codes: Vec<(Option<u32>, Option<i32>)> = replies.iter().map(|r| {
+            let reply = json::parse(r).unwrap();
+            let code = match reply.get("error").and_then(|e| e.get("code")) {
+                Some(Json::Number(code)) => Some(*code as i32),
+                _ => Option::None,
+            };
+            (reply.get("id").and_then(|i| i.as_u32()), code)
+        }).collect();
+        assert_eq!(codes, [
+            (Option::None, Some(PARSE_ERROR)),
+            (Some(1), Some(INVALID_REQUEST)),
+            (Some(2), Some(METHOD_NOT_FOUND)),
+            (Some(3), Some(INVALID_PARAMS)),
+            (Some(4), Some(INVALID_PARAMS)),
+            (Some(5), Some(SERVER_ERROR)),
+        ]);
+        assert!(replies[4].contains("There is no session 9"), "{}", replies[4]);
+    }
+
+    #[test]
+    fn changed_outputs_are_sent_to_subscribers() {
+        let replies = served(&[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "circuit.create", "params": {"name": "Wire"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "gate.add", "params": {"session": 1, "type": "Input", "inputs": [], "label": "A"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "gate.add", "params": {"session": 1, "type": "Output", "inputs": [0], "label": "Y"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "gate.set", "params": {"session": 1, "label": "A", "state": true}}"#,
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "sim.step", "params": {"session": 1}}"#,
+            // A notification, which gets no reply, and a step that doesn't change anything
+            r#"{"jsonrpc": "2.0", "method": "sim.step", "params": {"session": 1}}"#,
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "circuit.unsubscribe", "params": {"session": 1}}"#,
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "gate.set", "params": {"session": 1, "id": 0, "state": false}}"#,
+            r#"{"jsonrpc": "2.0", "id": 8, "method": "sim.step", "params": {"session": 1}}"#,
+        ]);
+        assert_eq!(replies, [
+            r#"{"jsonrpc":"2.0","result":{"session":1},"id":1}"#,
+            r#"{"jsonrpc":"2.0","result":{"id":0},"id":2}"#,
+            r#"{"jsonrpc":"2.0","result":{"id":1},"id":3}"#,
+            // The new Output counts as a change
+            r#"{"jsonrpc":"2.0","method":"outputs.changed","params":{"session":1,"outputs":[{"id":1,"label":"Y","state":false}]}}"#,
+            r#"{"jsonrpc":"2.0","result":null,"id":4}"#,
+            r#"{"jsonrpc":"2.0","result":{"outputs":[{"id":1,"label":"Y","state":true}]},"id":5}"#,
+            r#"{"jsonrpc":"2.0","method":"outputs.changed","params":{"session":1,"outputs":[{"id":1,"label":"Y","state":true}]}}"#,
+            r#"{"jsonrpc":"2.0","result":null,"id":6}"#,
+            r#"{"jsonrpc":"2.0","result":null,"id":7}"#,
+            r#"{"jsonrpc":"2.0","result":{"outputs":[{"id":1,"label":"Y","state":false}]},"id":8}"#,
+        ]);
+    }
+}
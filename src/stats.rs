@@ -0,0 +1,224 @@
+use std::{collections::{HashMap, HashSet}, io::Write};
+
+use crate::{layout, Circuit, Gate, GateType, GATE_TYPES};
+
+// Numbers for comparing designs on more than how many gates they have. Gates are counted as they
+// were put down, going into ICs, rather than in the flattened circuit, where IC ports turn into
+// Buffers. The Buffers standing in for the ports of an IC are only there to connect it, so they
+// aren't counted as transistors or in the fan-in and fan-out.
+
+const BAR_WIDTH: usize = 40;
+const MAX_SHOWN: usize = 16;
+
+// A rough count for a static CMOS gate with this many inputs
+fn transistors(gate: &Gate) -> usize {
+    let n = gate.inputs.len().max(2);
+    match gate.gate_type {
+        GateType::Input | GateType::Output | GateType::Wire => 0,
+        GateType::Not => 2,
+        GateType::Buffer => 4,
+        GateType::Nand | GateType::Nor => 2 * n,
+        GateType::And | GateType::Or => 2 * n + 2,
+        GateType::Xor | GateType::Nxor => 12,
+        GateType::Tristate => 6,
+    }
+}
+
+fn name(circuit: &Circuit) -> String {
+    circuit.name.clone().unwrap_or(format!("Circuit {}", circuit.id))
+}
+
+// Gates in the circuit and every IC in it, all the way down
+fn size(circuit: &Circuit) -> usize {
+    circuit.gates.len() + circuit.intergrated_circuits.iter().map(|ic| size(&ic.circuit)).sum::<usize>()
+}
+
+fn ic_count(circuit: &Circuit) -> usize {
+    circuit.intergrated_circuits.iter().map(|ic| 1 + ic_count(&ic.circuit)).sum()
+}
+
+#[derive(Default)]
+struct Stats {
+    here: [usize; GATE_TYPES.len()],
+    in_ics: [usize; GATE_TYPES.len()],
+    transistors: [usize; GATE_TYPES.len()],
+    fan_in: Vec<usize>,
+    fan_out: Vec<usize>,
+    unused: Vec<String>,
+    dangling: Vec<String>,
+}
+
+fn count(histogram: &mut Vec<usize>, value: usize) {
+    if histogram.len() <= value {
+        histogram.resize(value + 1, 0);
+    }
+    histogram[value] += 1;
+}
+
+// Goes through the gates of a circuit and then its ICs. `exported` are the gates an IC's outputs
+// are read from by the circuit around it.
+fn tally(circuit: &Circuit, within: Option<&str>, exported: &HashSet<u32>, stats: &mut Stats) {
+    let ids: HashSet<u32> = circuit.gates.iter().map(|g| g.id).collect();
+    let driven: HashSet<u32> = circuit.intergrated_circuits.iter().flat_map(|ic| ic.outputs.iter().map(|p| p[0].1)).collect();
+    let ports: HashSet<u32> = circuit.intergrated_circuits.iter().flat_map(|ic| ic.inputs.iter().map(|p| p[0].1)).chain(driven.iter().copied()).collect();
+    let mut readers: HashMap<u32, usize> = HashMap::new();
+    for input in circuit.gates.iter().flat_map(|g| g.inputs.iter()) {
+        *readers.entry(input.1).or_default() += 1;
+    }
+    for port in circuit.intergrated_circuits.iter().flat_map(|ic| ic.inputs.iter()) {
+        *readers.entry(port[0].1).or_default() += 1;
+    }
+
+    for gate in circuit.gates.iter() {
+        let i = gate.gate_type.index();
+        if within.is_some() { stats.in_ics[i] += 1 } else { stats.here[i] += 1 }
+        let read = readers.get(&gate.id).copied().unwrap_or(0);
+        if !ports.contains(&gate.id) {
+            stats.transistors[i] += transistors(gate);
+            count(&mut stats.fan_in, gate.inputs.len());
+            count(&mut stats.fan_out, read);
+        }
+
+        let gate_name = match within {
+            Some(ic) => format!("{} in {}", gate.name(), ic),
+            Option::None => gate.name(),
+        };
+        for input in gate.inputs.iter().filter(|i| !ids.contains(&i.1)) {
+            stats.dangling.push(format!("{} reads {}, which isn't in the circuit", gate_name, input.1));
+        }
+        if gate.inputs.is_empty() && gate.gate_type != GateType::Input && !driven.contains(&gate.id) {
+            stats.dangling.push(format!("{} has nothing driving it", gate_name));
+        }
+        if read == 0 && gate.gate_type != GateType::Output && !exported.contains(&gate.id) {
+            stats.unused.push(gate_name);
+        }
+    }
+
+    for ic in circuit.intergrated_circuits.iter() {
+        let ic_name = match within {
+            Some(outer) => format!("{} in {}", name(&ic.circuit), outer),
+            Option::None => name(&ic.circuit),
+        };
+        let exported = ic.outputs.iter().map(|p| p[1].1).collect();
+        tally(&ic.circuit, Some(&ic_name), &exported, stats);
+    }
+}
+
+fn histogram(out: &mut impl Write, counts: &[usize]) -> std::io::Result<()> {
+    let most = counts.iter().max().copied().unwrap_or(1).max(1);
+    for (value, count) in counts.iter().enumerate() {
+        writeln!(out, "{:>4} | {} {}", value, "#".repeat((count * BAR_WIDTH).div_ceil(most)), count)?;
+    }
+    Ok(())
+}
+
+fn list(out: &mut impl Write, title: &str, items: &[String]) -> std::io::Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    writeln!(out)?;
+    writeln!(out, "{}:", title)?;
+    for item in items.iter().take(MAX_SHOWN) {
+        writeln!(out, "  {}", item)?;
+    }
+    if items.len() > MAX_SHOWN {
+        writeln!(out, "  ... and {} more", items.len() - MAX_SHOWN)?;
+    }
+    Ok(())
+}
+
+pub fn report(circuit: &Circuit, out: &mut impl Write) -> std::io::Result<()> {
+    let mut stats = Stats::default();
+    tally(circuit, Option::None, &HashSet::new(), &mut stats);
+    let total = size(circuit);
+    writeln!(out, "{}: {} gate(s), {} more in {} IC(s), {} in all", name(circuit), circuit.gates.len(), total - circuit.gates.len(), ic_count(circuit), total)?;
+
+    writeln!(out)?;
+    writeln!(out, "  Type         Here  In ICs  Transistors")?;
+    for (i, gate_type) in GATE_TYPES.iter().enumerate() {
+        if stats.here[i] + stats.in_ics[i] > 0 {
+            writeln!(out, "  {:<10} {:>6} {:>7} {:>12}", format!("{:?}", gate_type), stats.here[i], stats.in_ics[i], stats.transistors[i])?;
+        }
+    }
+    writeln!(out, "  {:<10} {:>6} {:>7} {:>12}", "Total", stats.here.iter().sum::<usize>(), stats.in_ics.iter().sum::<usize>(), stats.transistors.iter().sum::<usize>())?;
+    writeln!(out, "Transistors are a rough count for static CMOS, with nothing for Inputs, Outputs, Wires and IC ports")?;
+
+    writeln!(out)?;
+    writeln!(out, "Gates by how many inputs they have:")?;
+    histogram(out, &stats.fan_in)?;
+    writeln!(out)?;
+    writeln!(out, "Gates by how many things read them:")?;
+    histogram(out, &stats.fan_out)?;
+
+    let flat = circuit.flattened();
+    let depth = layout::longest_paths(&layout::gate_predecessors(&flat)).iter().max().copied().unwrap_or(0);
+    writeln!(out)?;
+    writeln!(out, "The deepest gate is {} gate(s) after an Input, with loops broken at their feedback", depth)?;
+
+    if !circuit.intergrated_circuits.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "ICs:")?;
+        for ic in circuit.intergrated_circuits.iter() {
+            writeln!(out, "  {} with {} gate(s), {} in all with the {} IC(s) inside it", name(&ic.circuit), ic.circuit.gates.len(), size(&ic.circuit), ic_count(&ic.circuit))?;
+        }
+    }
+
+    list(out, "Unused, nothing reads them", &stats.unused)?;
+    list(out, "Dangling", &stats.dangling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::wide_after_del;
+
+    #[test]
+    fn gates_are_counted_by_type_here_and_in_ics() {
+        let mut text = Vec::new();
+        report(&wide_after_del(), &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        // The deleted Nor and the half adder's And leave gates reading nothing, and I5 with nothing reading it
+        assert_eq!(text, "\
+Circuit 4: 26 gate(s), 5 more in 1 IC(s), 31 in all
+
+  Type         Here  In ICs  Transistors
+  Input           8       2            0
+  Output          5       2            0
+  Buffer          5       0            4
+  Not             1       0            2
+  And             1       0            8
+  Or              1       0            6
+  Nand            1       0            6
+  Xor             1       1           24
+  Nxor            1       0           12
+  Tristate        1       0            6
+  Wire            1       0            0
+  Total          26       5           68
+Transistors are a rough count for static CMOS, with nothing for Inputs, Outputs, Wires and IC ports
+
+Gates by how many inputs they have:
+   0 | ######################################## 10
+   1 | #################################### 9
+   2 | ######################## 6
+   3 | ######## 2
+
+Gates by how many things read them:
+   0 | #################### 8
+   1 | ######################################## 16
+   2 | ######## 3
+
+The deepest gate is 8 gate(s) after an Input, with loops broken at their feedback
+
+ICs:
+  half_adder with 5 gate(s), 5 in all with the 0 IC(s) inside it
+
+Unused, nothing reads them:
+  Input 5 I5
+
+Dangling:
+  Or 16 reads 13, which isn't in the circuit
+  Wire 20 reads 13, which isn't in the circuit
+  Output 5 C in half_adder reads 3, which isn't in the circuit
+");
+    }
+}
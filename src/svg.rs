@@ -0,0 +1,160 @@
+use std::{fs::File, io::Write};
+
+use crate::{layout::{Layout, Part}, Circuit, GateType};
+
+const MARGIN: f32 = 30.0;
+const COLUMN_WIDTH: f32 = 150.0;
+const GAP: f32 = 30.0;
+const BODY: f32 = 40.0;
+const PIN_GAP: f32 = 12.0;
+const ON: &str = "#2a9d2a";
+const OFF: &str = "#333333";
+
+// Gates are drawn with the usual symbols in columns by logic depth. Wires go right, then up or
+// down in the gap before the gate they go into, then right again. Wires that go back to an
+// earlier column are taken around underneath everything else.
+pub fn export_svg(circuit: &Circuit, fp: &str) -> std::io::Result<()> {
+    let layout = Layout::new(circuit);
+
+    // Where the top left of each node is, and how tall it is
+    let mut boxes = vec![(0.0, 0.0, 0.0); layout.nodes.len()];
+    let mut bottom: f32 = MARGIN;
+    for (column, nodes) in layout.columns.iter().enumerate() {
+        let mut y = MARGIN;
+        for node in nodes.iter() {
+            let height = height(&layout.nodes[*node].part, circuit);
+            boxes[*node] = (MARGIN + column as f32 * COLUMN_WIDTH, y, height);
+            y += height + GAP;
+        }
+        bottom = bottom.max(y);
+    }
+    let width = MARGIN * 2.0 + layout.columns.len() as f32 * COLUMN_WIDTH;
+
+    let mut wires: Vec<u8> = Vec::new();
+    let mut loops = 0;
+    for (node, data) in layout.nodes.iter().enumerate() {
+        let (x, y, h) = boxes[node];
+        if let Part::Wire(..) = data.part {
+            let colour = if output_state(&layout, &data.part, 0, circuit) {ON} else {OFF};
+            writeln!(wires, "  <path d=\"M{},{} H{}\" stroke=\"{}\" fill=\"none\"/>", x, y + h / 2.0, x + BODY, colour)?;
+        }
+        for (pin, input) in data.inputs.iter().enumerate() {
+            let Some((source, source_pin)) = *input else { continue };
+            let (sx, sy) = output_pin(&layout.nodes[source].part, boxes[source], source_pin, circuit);
+            let (tx, ty) = (x, y + h * (pin + 1) as f32 / (data.inputs.len() + 1) as f32);
+            let colour = if output_state(&layout, &layout.nodes[source].part, source_pin, circuit) {ON} else {OFF};
+
+            let path = if layout.nodes[source].column < data.column {
+                let channel = tx - 8.0 - ((data.row * 3 + pin) % 6) as f32 * 3.0;
+                format!("M{},{} H{} V{} H{}", sx, sy, channel, ty, tx)
+            } else {
+                loops += 1;
+                let under = bottom + loops as f32 * 6.0;
+                format!("M{},{} H{} V{} H{} V{} H{}", sx, sy, sx + 6.0 + loops as f32 * 2.0, under, tx - 14.0, ty, tx)
+            };
+            writeln!(wires, "  <path d=\"{}\" stroke=\"{}\" fill=\"none\"/>", path, colour)?;
+        }
+    }
+
+    let mut buf: Vec<u8> = Vec::new();
+    let height = bottom + loops as f32 * 6.0 + MARGIN;
+    writeln!(buf, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\" font-size=\"10\">", width, height, width, height)?;
+    writeln!(buf, "  <rect width=\"100%\" height=\"100%\" fill=\"white\"/>")?;
+    if let Some(name) = &circuit.name {
+        writeln!(buf, "  <text x=\"{}\" y=\"{}\" font-size=\"14\">{}</text>", MARGIN, MARGIN / 2.0 + 5.0, escape(name))?;
+    }
+    buf.extend(wires);
+    for (node, data) in layout.nodes.iter().enumerate() {
+        draw(&data.part, boxes[node], circuit, &mut buf)?;
+    }
+    writeln!(buf, "</svg>")?;
+
+    let mut file = File::create(fp)?;
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+fn height(part: &Part, circuit: &Circuit) -> f32 {
+    let pins = match part {
+        Part::Gate(i) => circuit.gates[*i].inputs.len(),
+        Part::Ic(i) => circuit.intergrated_circuits[*i].inputs.len().max(circuit.intergrated_circuits[*i].outputs.len()),
+        Part::Wire(..) => return PIN_GAP,
+    };
+    ((pins.max(1) + 1) as f32 * PIN_GAP).max(30.0)
+}
+
+fn inverted(gate_type: &GateType) -> bool {
+    [GateType::Not, GateType::Nand, GateType::Nor, GateType::Nxor].contains(gate_type)
+}
+
+fn output_pin(part: &Part, (x, y, h): (f32, f32, f32), pin: usize, circuit: &Circuit) -> (f32, f32) {
+    match part {
+        Part::Gate(i) => (x + BODY + if inverted(&circuit.gates[*i].gate_type) {8.0} else {0.0}, y + h / 2.0),
+        Part::Ic(i) => (x + BODY * 1.5, y + h * (pin + 1) as f32 / (circuit.intergrated_circuits[*i].outputs.len() + 1) as f32),
+        Part::Wire(..) => (x + BODY, y + h / 2.0),
+    }
+}
+
+fn output_state(layout: &Layout, part: &Part, pin: usize, circuit: &Circuit) -> bool {
+    match part {
+        Part::Wire(source, source_pin) => output_state(layout, &layout.nodes[*source].part, *source_pin, circuit),
+        Part::Gate(i) => circuit.gates[*i].state,
+        Part::Ic(i) => {
+            let ic = &circuit.intergrated_circuits[*i];
+            ic.outputs.get(pin).and_then(|port| ic.circuit.gates.iter().find(|g| g.id == port[1].1)).map(|g| g.state).unwrap_or(false)
+        }
+    }
+}
+
+fn draw(part: &Part, (x, y, h): (f32, f32, f32), circuit: &Circuit, buf: &mut Vec<u8>) -> std::io::Result<()> {
+    let gate = match part {
+        Part::Gate(i) => &circuit.gates[*i],
+        Part::Wire(..) => return Ok(()),
+        Part::Ic(i) => {
+            let ic = &circuit.intergrated_circuits[*i];
+            let name = ic.circuit.name.clone().unwrap_or(format!("Circuit {}", ic.circuit.id));
+            writeln!(buf, "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#f4f4ff\" stroke=\"{}\"/>", x, y, BODY * 1.5, h, OFF)?;
+            writeln!(buf, "  <text x=\"{}\" y=\"{}\">IC {}</text>", x + 3.0, y + 11.0, i)?;
+            writeln!(buf, "  <text x=\"{}\" y=\"{}\">{}</text>", x, y + h + 11.0, escape(&name))?;
+            return Ok(());
+        }
+    };
+    let colour = if gate.state {ON} else {OFF};
+    let fill = if gate.state {"#e3f6e3"} else {"white"};
+    let (w, m) = (BODY, y + h / 2.0);
+    let body = match gate.gate_type {
+        GateType::Input | GateType::Output => format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"6\"", x, y, w, h),
+        GateType::Buffer | GateType::Not | GateType::Tristate => format!("<path d=\"M{},{} L{},{} L{},{} Z\"", x, y, x + w, m, x, y + h),
+        GateType::And | GateType::Nand => format!("<path d=\"M{},{} H{} A{},{} 0 0 1 {},{} H{} Z\"", x, y, x + w / 2.0, w / 2.0, h / 2.0, x + w / 2.0, y + h, x),
+        GateType::Or | GateType::Nor | GateType::Xor | GateType::Nxor => format!(
+            "<path d=\"M{},{} Q{},{} {},{} Q{},{} {},{} Q{},{} {},{} Z\"",
+            x, y, x + 10.0, m, x, y + h, x + w * 0.7, y + h, x + w, m, x + w * 0.7, y, x, y),
+        // A bar the drivers of the net all meet at
+        GateType::Wire => format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"4\"", x, m - 2.0, w),
+    };
+    writeln!(buf, "  {} fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>", body, fill, colour, if gate.gate_type == GateType::Output {2} else {1})?;
+    if [GateType::Xor, GateType::Nxor].contains(&gate.gate_type) {
+        writeln!(buf, "  <path d=\"M{},{} Q{},{} {},{}\" fill=\"none\" stroke=\"{}\"/>", x - 5.0, y, x + 5.0, m, x - 5.0, y + h, colour)?;
+    }
+    // The enable comes in at the bottom
+    if gate.gate_type == GateType::Tristate {
+        writeln!(buf, "  <path d=\"M{},{} V{}\" fill=\"none\" stroke=\"{}\"/>", x + w / 2.0, y + h * 0.75, y + h, colour)?;
+    }
+    if inverted(&gate.gate_type) {
+        writeln!(buf, "  <circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"white\" stroke=\"{}\"/>", x + w + 4.0, m, colour)?;
+    }
+    if [GateType::Input, GateType::Output].contains(&gate.gate_type) {
+        writeln!(buf, "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>", x + w / 2.0, m + 4.0, if gate.state {1} else {0})?;
+    }
+
+    let text = match &gate.label {
+        Some(label) => format!("{} {}", gate.id, escape(label)),
+        None => format!("{}", gate.id),
+    };
+    writeln!(buf, "  <text x=\"{}\" y=\"{}\">{}</text>", x, y + h + 11.0, text)?;
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,164 @@
+use std::{io::Write, time::Instant};
+
+use crate::{layout, Circuit, GateType};
+
+// A circuit compiled down to a flat list of instructions, with its ICs flattened into it and
+// every input turned into an index so nothing has to be searched for while it runs. The
+// instructions are sorted by how deep the gate is, so each one runs after everything it reads
+// from and one run of the tape settles all the logic between the Inputs and any loops. Gates
+// that close a loop are read as they were on the run before, which keeps flip flops working.
+
+#[derive(Clone, Copy)]
+enum Op {
+    Buffer,
+    Not,
+    And,
+    Nand,
+    Or,
+    Nor,
+    Xor,
+    Nxor,
+    Off,
+}
+
+struct Instruction {
+    op: Op,
+    output: u32,
+    first: u32, // Where its operands start
+    count: u32,
+}
+
+pub struct Tape {
+    instructions: Vec<Instruction>,
+    operands: Vec<u32>,
+    states: Vec<bool>,
+    levels: usize,
+    loops: usize,
+}
+
+impl Tape {
+    pub fn compile(circuit: &Circuit) -> Tape {
+        let flat = circuit.flattened();
+        let predecessors = layout::gate_predecessors(&flat);
+        let depths = layout::longest_paths(&predecessors);
+        let loops = layout::feedback(&predecessors).iter().filter(|f| **f).count();
+
+        let mut order: Vec<usize> = (0..flat.gates.len()).filter(|i| flat.gates[*i].gate_type != GateType::Input).collect();
+        order.sort_by_key(|i| depths[*i]);
+
+        let mut instructions = Vec::new();
+        let mut operands = Vec::new();
+        for i in order {
+            let gate = &flat.gates[i];
+            let op = match gate.gate_type {
+                _ if gate.inputs.is_empty() => Op::Off,
+                GateType::Input | GateType::Output | GateType::Buffer => Op::Buffer,
+                GateType::Not => Op::Not,
+                GateType::And => Op::And,
+                GateType::Nand => Op::Nand,
+                GateType::Or => Op::Or,
+                GateType::Nor => Op::Nor,
+                GateType::Xor => Op::Xor,
+                GateType::Nxor => Op::Nxor,
+                // Only the data and enable inputs of a Tristate count
+                GateType::Tristate => Op::And,
+                GateType::Wire => Op::Or,
+            };
+            let inputs = if gate.gate_type == GateType::Tristate { &gate.inputs[..gate.inputs.len().min(2)] } else { &gate.inputs[..] };
+            instructions.push(Instruction { op, output: i as u32, first: operands.len() as u32, count: inputs.len() as u32 });
+            // Flattened ids are indexes
+            operands.extend(inputs.iter().map(|input| input.1));
+        }
+        Tape {
+            instructions,
+            operands,
+            states: flat.gates.iter().map(|g| g.state).collect(),
+            levels: depths.iter().max().map(|d| d + 1).unwrap_or(0),
+            loops,
+        }
+    }
+    pub fn run(&mut self) {
+        let states = &mut self.states;
+        for instruction in self.instructions.iter() {
+            let operands = &self.operands[instruction.first as usize..(instruction.first + instruction.count) as usize];
+            let read = |o: &u32| states[*o as usize];
+            let state = match instruction.op {
+                Op::Buffer => read(&operands[0]),
+                Op::Not => !read(&operands[0]),
+                Op::And => operands.iter().all(read),
+                Op::Nand => !operands.iter().all(read),
+                Op::Or => operands.iter().any(read),
+                Op::Nor => !operands.iter().any(read),
+                Op::Xor => read(&operands[0]) != operands.get(1).map(read).unwrap_or(false),
+                Op::Nxor => read(&operands[0]) == operands.get(1).map(read).unwrap_or(false),
+                Op::Off => false,
+            };
+            states[instruction.output as usize] = state;
+        }
+    }
+    // Puts the states the tape has got to back into the circuit it was compiled from
+    pub fn write_back(&self, circuit: &mut Circuit) {
+        circuit.unflatten_states(&self.states, &mut 0);
+    }
+    pub fn describe(&self) -> String {
+        format!(
+            "{} instruction(s) over {} level(s) for {} gate(s), with {} loop(s) read from the run before",
+            self.instructions.len(), self.levels, self.states.len(), self.loops
+        )
+    }
+}
+
+// Times `steps` calls of Circuit::step against as many runs of the tape, both on copies so the
+// circuit itself isn't changed
+pub fn benchmark(circuit: &Circuit, steps: u32, out: &mut impl Write) -> std::io::Result<()> {
+    let mut stepped = circuit.clone();
+    let start = Instant::now();
+    for _ in 0..steps {
+        stepped.step();
+    }
+    let step_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut tape = Tape::compile(circuit);
+    let compile_time = start.elapsed();
+    let start = Instant::now();
+    for _ in 0..steps {
+        tape.run();
+    }
+    let tape_time = start.elapsed();
+
+    let per_second = |time: std::time::Duration| steps as f64 / time.as_secs_f64().max(f64::EPSILON);
+    writeln!(out, "Tape: {}", tape.describe())?;
+    writeln!(out, "Stepper: {} step(s) in {:.3}ms, {:.0} a second", steps, step_time.as_secs_f64() * 1000.0, per_second(step_time))?;
+    writeln!(out, "Tape:    {} run(s) in {:.3}ms, {:.0} a second, after compiling in {:.3}ms", steps, tape_time.as_secs_f64() * 1000.0, per_second(tape_time), compile_time.as_secs_f64() * 1000.0)?;
+    writeln!(out, "Each run of the tape took {:.1}x less time than a step and settles what could take {} step(s)", step_time.as_secs_f64() / tape_time.as_secs_f64().max(f64::EPSILON), tape.levels)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{stepped_outputs, wide, wide_after_del};
+
+    #[test]
+    fn one_run_settles_like_stepping() {
+        for circuit in [wide(), wide_after_del()] {
+            settles_like_stepping(&circuit);
+        }
+    }
+
+    fn settles_like_stepping(circuit: &Circuit) {
+        let inputs: Vec<u32> = circuit.gates.iter().filter(|g| g.gate_type == GateType::Input).map(|g| g.id).collect();
+        for vector in 0..256u64 {
+            let mut ran = circuit.clone();
+            for (bit, id) in inputs.iter().enumerate() {
+                ran.set_component(*id, (vector >> (inputs.len() - 1 - bit)) & 1 == 1);
+            }
+            let mut tape = Tape::compile(&ran);
+            tape.run();
+            tape.write_back(&mut ran);
+            let outputs: Vec<bool> = ran.gates.iter().filter(|g| g.gate_type == GateType::Output).map(|g| g.state).collect();
+            assert_eq!(outputs, stepped_outputs(circuit, vector), "vector {:08b}", vector);
+        }
+    }
+}
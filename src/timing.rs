@@ -0,0 +1,96 @@
+use std::io::Write;
+
+use crate::{layout, Circuit, GateType};
+
+// Static timing: how long a change at the Inputs can take to get through to each Output, going
+// by the delays of the gates on the slowest path and not by simulating anything. It runs on the
+// flattened circuit so paths go through the insides of ICs. Loops are broken where the layout
+// breaks them, so the gates closing a flip flop start paths of their own.
+
+const BAR_WIDTH: usize = 40;
+
+pub fn report(circuit: &Circuit, out: &mut impl Write) -> std::io::Result<()> {
+    let flat = circuit.flattened();
+    let predecessors = layout::gate_predecessors(&flat);
+    let delays: Vec<u64> = flat.gates.iter().map(|g| g.delay() as u64).collect();
+    let (arrivals, slowest) = layout::slowest_paths(&predecessors, &delays);
+    let depths = layout::longest_paths(&predecessors);
+
+    let name = |i: usize| circuit.flat_gate_name(&flat.gates[i], i);
+    let path = |mut i: usize| {
+        let mut path = vec![i];
+        while let Some(p) = slowest[i] {
+            path.push(p);
+            i = p;
+        }
+        path.reverse();
+        path
+    };
+
+    let outputs: Vec<usize> = circuit.flat_indexes_of(GateType::Output);
+    if outputs.is_empty() {
+        writeln!(out, "The circuit has no Outputs to time")?;
+    }
+    for output in outputs.iter() {
+        let gates = path(*output).iter().filter(|g| ![GateType::Input, GateType::Output].contains(&flat.gates[**g].gate_type)).count();
+        writeln!(out, "{} settles {} tick(s) after its Inputs, through {} gate(s)", name(*output), arrivals[*output], gates)?;
+    }
+
+    if let Some(critical) = outputs.iter().copied().max_by_key(|o| arrivals[*o]) {
+        writeln!(out)?;
+        writeln!(out, "Critical path to {}:", name(critical))?;
+        writeln!(out, "  Tick  Delay  Gate")?;
+        for gate in path(critical) {
+            writeln!(out, "{:>6} {:>6}  {}", arrivals[gate], delays[gate], name(gate))?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Gates by how many gates deep they are:")?;
+    let deepest = depths.iter().max().copied().unwrap_or(0);
+    let mut counts = vec![0; deepest + 1];
+    for depth in depths.iter() {
+        counts[*depth] += 1;
+    }
+    let most = counts.iter().max().copied().unwrap_or(1).max(1);
+    for (depth, count) in counts.iter().enumerate() {
+        writeln!(out, "{:>4} | {} {}", depth, "#".repeat((count * BAR_WIDTH).div_ceil(most)), count)?;
+    }
+
+    writeln!(out)?;
+    let settle = arrivals.iter().max().copied().unwrap_or(0);
+    writeln!(out, "Everything settles within {} tick(s) of an Input changing", settle)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::run, Circuit};
+
+    #[test]
+    fn the_critical_path_adds_up_the_slowest_delays() {
+        // A takes 2 ticks through the Not and 3 more through the And, but only 1 through the Or
+        let mut circuit = Circuit::new(1);
+        let text = run(&mut circuit, &mut Vec::new(), &[
+            "INPUT; A", "INPUT; B", "NOT 0 DELAY 2", "AND 2 1 DELAY 3", "OR 0 1 DELAY 1", "OUTPUT 3; Y", "OUTPUT 4; Z", "TIMING",
+        ]);
+        assert_eq!(text, "\
+Output 5 Y settles 5 tick(s) after its Inputs, through 2 gate(s)
+Output 6 Z settles 1 tick(s) after its Inputs, through 1 gate(s)
+
+Critical path to Output 5 Y:
+  Tick  Delay  Gate
+     0      0  Input 0 A
+     2      2  Not 2
+     5      3  And 3
+     5      0  Output 5 Y
+
+Gates by how many gates deep they are:
+   0 | ######################################## 2
+   1 | ######################################## 2
+   2 | ######################################## 2
+   3 | #################### 1
+
+Everything settles within 5 tick(s) of an Input changing
+");
+    }
+}
@@ -0,0 +1,466 @@
+use std::{collections::{HashMap, VecDeque}, io::{IsTerminal, Read, Write}, process::{Command, Stdio}, sync::mpsc, thread, time::{Duration, Instant}};
+
+use crate::{invalid_data, run_command, schematic::terminal_size, Circuit, GateType, NEWCIRCUITCODE, SCHEMATICCODE};
+
+const NORMAL: &str = "\x1b[0m";
+const INVERSE: &str = "\x1b[0;7m";
+const GREEN: &str = "\x1b[0;1;32m";
+const SELECTED_GREEN: &str = "\x1b[0;1;32;7m";
+
+const RATECODE: &str = "RATE";
+const PAUSECODE: &str = "PAUSE";
+const RUNCODE: &str = "RUN";
+const STEPCODE: &str = "STEP";
+const QUITCODE: &str = "QUIT";
+
+const MAX_RATE: u32 = 1000;
+const LOG_LENGTH: usize = 500;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Command,
+    Io,
+    Gates,
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Quit,
+}
+
+// Puts the terminal in raw mode on the alternate screen and puts it back how it was when dropped,
+// even if something panics on the way out
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn new() -> std::io::Result<Self> {
+        if !std::io::stdin().is_terminal() {
+            return Err(invalid_data(String::from("The TUI needs to be run in a terminal")));
+        }
+        let saved = Command::new("stty").arg("-g").stdin(Stdio::inherit()).output()?;
+        let saved = String::from_utf8_lossy(&saved.stdout).trim().to_string();
+        Command::new("stty").args(["raw", "-echo"]).stdin(Stdio::inherit()).status()?;
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("{}\x1b[?25h\x1b[?1049l", NORMAL);
+        let _ = std::io::stdout().flush();
+        let _ = Command::new("stty").arg(&self.saved).stdin(Stdio::inherit()).status();
+    }
+}
+
+struct Screen {
+    width: usize,
+    cells: Vec<Vec<(char, &'static str)>>,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize) -> Self {
+        Self { width, cells: vec![vec![(' ', NORMAL); width]; height] }
+    }
+    // Writes text along a row, cutting it off at `width` characters and filling the rest with
+    // spaces in the same style
+    fn text(&mut self, x: usize, y: usize, width: usize, text: &str, style: &'static str) {
+        let Some(row) = self.cells.get_mut(y) else { return };
+        let mut chars = text.chars().map(|c| if c == '\t' {' '} else {c});
+        for cell in row.iter_mut().skip(x).take(width) {
+            *cell = (chars.next().unwrap_or(' '), style);
+        }
+    }
+    fn render(&self) -> String {
+        let mut out = String::from("\x1b[H");
+        for (y, row) in self.cells.iter().enumerate() {
+            let mut style = "";
+            for (c, s) in row.iter().take(self.width) {
+                if *s != style {
+                    out.push_str(s);
+                    style = s;
+                }
+                out.push(*c);
+            }
+            out.push_str(NORMAL);
+            if y + 1 < self.cells.len() {
+                out.push_str("\r\n");
+            }
+        }
+        out
+    }
+}
+
+struct Tui {
+    circuit: Circuit,
+    id: u32,
+    catalogue: Vec<Circuit>,
+    focus: Focus,
+    command: String,
+    history: Vec<String>,
+    history_index: usize,
+    log: VecDeque<String>,
+    log_scroll: usize,
+    gate_scroll: usize,
+    selected: usize,
+    waves: HashMap<u32, VecDeque<bool>>,
+    rate: u32,
+    running: bool,
+    steps: u64,
+    quit: bool,
+}
+
+// `simlo tui [file]` opens a circuit, or an empty one, on a full screen view of the gates, the
+// Inputs and Outputs with how they have changed over time, and a command line that takes the same
+// commands as the REPL. The circuit steps on its own at RATE steps a second until PAUSEd.
+pub fn run(fp: Option<&str>) -> std::io::Result<()> {
+    let mut tui = Tui {
+        circuit: Circuit::new(1),
+        id: 1,
+        catalogue: Vec::new(),
+        focus: Focus::Command,
+        command: String::new(),
+        history: Vec::new(),
+        history_index: 0,
+        log: VecDeque::new(),
+        log_scroll: 0,
+        gate_scroll: 0,
+        selected: 0,
+        waves: HashMap::new(),
+        rate: 10,
+        running: true,
+        steps: 0,
+        quit: false,
+    };
+    if let Some(fp) = fp {
+        tui.circuit.load_from_file(fp)?;
+        tui.message(format!("Loaded {}", fp));
+    }
+    tui.message(String::from("Tab switches between the command line, the IO panel and the gate list. HELP shows the commands."));
+
+    let _terminal = RawTerminal::new()?;
+    let keys = read_keys();
+    let mut size = terminal_size();
+    let mut last_size_check = Instant::now();
+    let mut next_step = Instant::now();
+    let mut dirty = true;
+
+    while !tui.quit {
+        if tui.running {
+            let period = Duration::from_secs(1) / tui.rate;
+            let mut stepped = 0;
+            while Instant::now() >= next_step && stepped < MAX_RATE {
+                tui.step();
+                next_step += period;
+                stepped += 1;
+                dirty = true;
+            }
+            // Falls behind rather than trying to catch up on steps it didn't have time for
+            if Instant::now() >= next_step {
+                next_step = Instant::now() + period;
+            }
+        }
+
+        if last_size_check.elapsed() >= Duration::from_millis(500) {
+            let new_size = terminal_size();
+            dirty |= new_size != size;
+            size = new_size;
+            last_size_check = Instant::now();
+        }
+        if dirty {
+            print!("{}", tui.draw(size.0, size.1).render());
+            std::io::stdout().flush()?;
+            dirty = false;
+        }
+
+        let wait = if tui.running {next_step.saturating_duration_since(Instant::now())} else {Duration::from_millis(500)};
+        match keys.recv_timeout(wait.min(Duration::from_millis(500))) {
+            Ok(key) => {
+                let was_running = tui.running;
+                tui.key(key);
+                if tui.running && !was_running {
+                    next_step = Instant::now();
+                }
+                dirty = true;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(())
+}
+
+// Keys are read on a thread of their own so the circuit can keep stepping while nothing is pressed
+fn read_keys() -> mpsc::Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 64];
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            let read = match stdin.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(read) => read,
+            };
+            pending.extend_from_slice(&buf[..read]);
+            for key in parse_keys(&mut pending) {
+                if sender.send(key).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+// Takes the keys out of the start of `bytes`, leaving anything that could be the start of a
+// longer character for the next read
+fn parse_keys(bytes: &mut Vec<u8>) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let (key, length) = match bytes[i] {
+            3 | 4 => (Some(Key::Quit), 1), // Ctrl-C, Ctrl-D
+            b'\r' | b'\n' => (Some(Key::Enter), 1),
+            8 | 127 => (Some(Key::Backspace), 1),
+            b'\t' => (Some(Key::Tab), 1),
+            0x1b if bytes.get(i + 1) == Some(&b'[') || bytes.get(i + 1) == Some(&b'O') => {
+                // The sequence ends at the first letter or `~`
+                let Some(end) = bytes[i + 2..].iter().position(|b| (0x40..=0x7e).contains(b)) else { break };
+                let key = match &bytes[i + 2..i + 3 + end] {
+                    b"A" => Some(Key::Up),
+                    b"B" => Some(Key::Down),
+                    b"5~" => Some(Key::PageUp),
+                    b"6~" => Some(Key::PageDown),
+                    _ => Option::None,
+                };
+                (key, end + 3)
+            }
+            0x1b => (Some(Key::Escape), 1),
+            b if b < 0x20 => (Option::None, 1),
+            b => {
+                let length = match b {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                };
+                if i + length > bytes.len() {
+                    break;
+                }
+                (std::str::from_utf8(&bytes[i..i + length]).ok().and_then(|s| s.chars().next()).map(Key::Char), length)
+            }
+        };
+        keys.extend(key);
+        i += length;
+    }
+    bytes.drain(..i);
+    keys
+}
+
+impl Tui {
+    fn io_gates(&self) -> Vec<usize> {
+        self.circuit.gates.iter().enumerate()
+            .filter(|(_, g)| [GateType::Input, GateType::Output].contains(&g.gate_type))
+            .map(|(i, _)| i)
+            .collect()
+    }
+    fn message(&mut self, line: String) {
+        self.log.push_back(line);
+        if self.log.len() > LOG_LENGTH {
+            self.log.pop_front();
+        }
+        self.log_scroll = 0;
+    }
+    fn step(&mut self) {
+        self.circuit.step();
+        self.steps += 1;
+        for gate in self.circuit.gates.iter().filter(|g| [GateType::Input, GateType::Output].contains(&g.gate_type)) {
+            let wave = self.waves.entry(gate.id).or_default();
+            wave.push_back(gate.state);
+            if wave.len() > LOG_LENGTH {
+                wave.pop_front();
+            }
+        }
+    }
+    fn key(&mut self, key: Key) {
+        match (self.focus, key) {
+            (_, Key::Quit) => self.quit = true,
+            (_, Key::Tab) => self.focus = match self.focus {
+                Focus::Command => Focus::Io,
+                Focus::Io => Focus::Gates,
+                Focus::Gates => Focus::Command,
+            },
+            (_, Key::Escape) => self.focus = Focus::Command,
+
+            (Focus::Command, Key::Char(c)) => self.command.push(c),
+            (Focus::Command, Key::Backspace) => {self.command.pop();}
+            (Focus::Command, Key::Enter) => {
+                let command = std::mem::take(&mut self.command);
+                if !command.trim().is_empty() {
+                    self.history.push(command.clone());
+                }
+                self.history_index = self.history.len();
+                self.run(command);
+            }
+            (Focus::Command, Key::Up) if self.history_index > 0 => {
+                self.history_index -= 1;
+                self.command = self.history[self.history_index].clone();
+            }
+            (Focus::Command, Key::Down) if self.history_index < self.history.len() => {
+                self.history_index += 1;
+                self.command = self.history.get(self.history_index).cloned().unwrap_or_default();
+            }
+            (Focus::Command, Key::PageUp) => self.log_scroll = (self.log_scroll + 5).min(self.log.len().saturating_sub(1)),
+            (Focus::Command, Key::PageDown) => self.log_scroll = self.log_scroll.saturating_sub(5),
+
+            (Focus::Io, Key::Up) => self.selected = self.selected.saturating_sub(1),
+            (Focus::Io, Key::Down) => self.selected += 1,
+            (Focus::Io, Key::Enter) | (Focus::Io, Key::Char(' ')) => {
+                let io = self.io_gates();
+                if let Some(index) = io.get(self.selected.min(io.len().saturating_sub(1))) {
+                    let gate = &self.circuit.gates[*index];
+                    if gate.gate_type == GateType::Input {
+                        // The same way SET does it, so the level and the toggle count follow
+                        let (id, state) = (gate.id, !gate.state);
+                        self.circuit.set_component(id, state);
+                    }
+                }
+            }
+            (Focus::Io, Key::Char('+')) => self.rate = (self.rate * 2).min(MAX_RATE),
+            (Focus::Io, Key::Char('-')) => self.rate = (self.rate / 2).max(1),
+            (Focus::Io, Key::Char('p')) => self.running = !self.running,
+            (Focus::Io, Key::Char('s')) => self.step(),
+            (Focus::Io, Key::Char('q')) => self.quit = true,
+
+            (Focus::Gates, Key::Up) => self.gate_scroll = self.gate_scroll.saturating_sub(1),
+            (Focus::Gates, Key::Down) => self.gate_scroll += 1,
+            (Focus::Gates, Key::PageUp) => self.gate_scroll = self.gate_scroll.saturating_sub(10),
+            (Focus::Gates, Key::PageDown) => self.gate_scroll += 10,
+            _ => {}
+        }
+    }
+    // Anything that isn't about the TUI itself goes to the REPL's commands, and the circuit steps
+    // once afterwards like it does in the REPL
+    fn run(&mut self, command: String) {
+        self.message(format!("{}>{}", self.circuit.id, command));
+        let mut words = command.split_whitespace();
+        let first = words.next().unwrap_or("");
+        match first {
+            RATECODE => match words.next().map(|w| w.parse::<u32>()) {
+                Some(Ok(rate)) if (1..=MAX_RATE).contains(&rate) => {
+                    self.rate = rate;
+                    self.message(format!("Stepping {} times a second", rate));
+                }
+                _ => self.message(format!("The rate has to be a number of steps a second from 1 to {}", MAX_RATE)),
+            },
+            PAUSECODE => self.running = false,
+            RUNCODE => self.running = true,
+            STEPCODE => match words.next().map(|w| w.parse::<u32>()).unwrap_or(Ok(1)) {
+                Ok(steps) => (0..steps).for_each(|_| self.step()),
+                Err(_) => self.message(String::from("STEP takes a number of steps")),
+            },
+            QUITCODE | "HLT" => self.quit = true,
+            NEWCIRCUITCODE | SCHEMATICCODE => self.message(format!("{} isn't available in the TUI", first)),
+            _ => {
+                if first == "HELP" {
+                    self.message(String::from("RATE [steps]                           - Sets how many times a second the circuit steps"));
+                    self.message(String::from("PAUSE / RUN                            - Stops and starts the circuit stepping on its own"));
+                    self.message(String::from("STEP (steps)                           - Steps the circuit by hand"));
+                    self.message(String::from("QUIT                                   - Leaves the TUI"));
+                    self.message(String::from("IO panel: Up/Down select, Space toggles an Input, +/- rate, P pause, S step, Q quit"));
+                }
+                let mut out: Vec<u8> = Vec::new();
+                if let Err(e) = run_command(command, &mut self.circuit, &mut self.id, &mut self.catalogue, &mut out) {
+                    self.message(e.to_string());
+                }
+                for line in String::from_utf8_lossy(&out).lines() {
+                    self.message(line.to_string());
+                }
+                self.step();
+            }
+        }
+    }
+
+    fn draw(&self, width: usize, height: usize) -> Screen {
+        let mut screen = Screen::new(width, height);
+        let (width, height) = (width.max(20), height.max(10));
+        let left = (width * 2 / 5).max(10);
+        let right = width - left - 1;
+        let log_height = (height / 4).max(3);
+        let top = height - log_height - 3; // Height of the gate list, IO panel and waveform
+        let io_height = (top / 2).max(1);
+
+        let name = self.circuit.name.clone().unwrap_or(format!("Circuit {}", self.circuit.id));
+        let status = if self.running {format!("running at {}/s", self.rate)} else {String::from("paused")};
+        screen.text(0, 0, width, &format!(" simlo - {} - step {} - {}", name, self.steps, status), INVERSE);
+
+        // Gate list
+        let title_style = |focus: Focus| if self.focus == focus {INVERSE} else {NORMAL};
+        screen.text(0, 1, left, &format!("Gates ({})", self.circuit.gates.len()), title_style(Focus::Gates));
+        let scroll = self.gate_scroll.min(self.circuit.gates.len().saturating_sub(top - 1));
+        for (row, gate) in self.circuit.gates.iter().skip(scroll).take(top - 1).enumerate() {
+            screen.text(0, 2 + row, left, &gate.to_string(), if gate.state {GREEN} else {NORMAL});
+        }
+        for row in 1..=top {
+            screen.text(left, row, 1, "│", NORMAL);
+        }
+
+        // IO panel
+        let io = self.io_gates();
+        let selected = self.selected.min(io.len().saturating_sub(1));
+        let x = left + 1;
+        screen.text(x, 1, right, "Inputs and Outputs", title_style(Focus::Io));
+        let io_scroll = (selected + 1).saturating_sub(io_height - 1);
+        for (row, index) in io.iter().enumerate().skip(io_scroll).take(io_height - 1) {
+            let gate = &self.circuit.gates[*index];
+            let style = match (self.focus == Focus::Io && row == selected, gate.state) {
+                (true, true) => SELECTED_GREEN,
+                (true, false) => INVERSE,
+                (false, true) => GREEN,
+                (false, false) => NORMAL,
+            };
+            let kind = if gate.gate_type == GateType::Input {"IN "} else {"OUT"};
+            let text = format!(" {} {:>4} {} {}", kind, gate.id, if gate.state {1} else {0}, gate.label.clone().unwrap_or_default());
+            screen.text(x, 2 + row - io_scroll, right, &text, style);
+        }
+
+        // Waveform, newest step on the right
+        let wave_top = 1 + io_height;
+        screen.text(x, wave_top, right, &format!("{:─<1$}", "Waveform ", right), NORMAL);
+        let wave_width = right.saturating_sub(12);
+        for (row, index) in io.iter().skip(io_scroll).take(top - io_height - 1).enumerate() {
+            let gate = &self.circuit.gates[*index];
+            let name = gate.label.clone().unwrap_or(format!("{}", gate.id));
+            let wave: String = self.waves.get(&gate.id).map(|w| {
+                w.iter().skip(w.len().saturating_sub(wave_width)).map(|s| if *s {'‾'} else {'_'}).collect()
+            }).unwrap_or_default();
+            screen.text(x, wave_top + 1 + row, 11, &name, NORMAL);
+            screen.text(x + 11, wave_top + 1 + row, 1, "│", NORMAL);
+            screen.text(x + 12, wave_top + 1 + row, wave_width, &format!("{:>1$}", wave, wave_width), if gate.state {GREEN} else {NORMAL});
+        }
+
+        // Messages and the command line
+        screen.text(0, top + 1, width, &format!("{:─<1$}", "Messages ", width), NORMAL);
+        let end = self.log.len() - self.log_scroll.min(self.log.len());
+        let start = end.saturating_sub(log_height);
+        for (row, line) in self.log.range(start..end).enumerate() {
+            screen.text(0, top + 2 + row, width, line, NORMAL);
+        }
+        let prompt = format!("{}>{}", self.circuit.id, self.command);
+        let prompt: String = prompt.chars().skip(prompt.chars().count().saturating_sub(width - 1)).collect();
+        screen.text(0, height - 1, width, &format!("{}{}", prompt, if self.focus == Focus::Command {"█"} else {""}), title_style(Focus::Command));
+        screen
+    }
+}
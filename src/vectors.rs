@@ -0,0 +1,74 @@
+use std::{fs::File, io::{Read, Write}};
+
+use crate::invalid_data;
+
+// Test vectors are kept one to a line as the states of the Inputs, the first Input first, like
+// `0110`. A line can go on with ` | ` and the states the Outputs should be in, which is how TRUTH
+// writes its rows, so a saved truth table can be read back as vectors. Lines starting with `#`
+// are comments, which is how the header naming the Inputs and Outputs is written.
+
+pub struct Vector {
+    pub inputs: Vec<bool>,
+    pub outputs: Option<Vec<bool>>,
+}
+
+pub fn read_vectors(fp: &str, inputs: usize, outputs: usize) -> std::io::Result<Vec<Vector>> {
+    let mut text = String::new();
+    File::open(fp)?.read_to_string(&mut text)?;
+
+    let mut vectors = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (ins, outs) = match line.split_once('|') {
+            Some((ins, outs)) => (ins, Some(outs)),
+            None => (line, Option::None),
+        };
+        let Some(ins) = bits(ins) else {
+            return Err(invalid_data(format!("Line {}: \"{}\" should be made of 0s and 1s", number + 1, ins.trim())));
+        };
+        if ins.len() != inputs {
+            return Err(invalid_data(format!("Line {}: there are {} Input state(s) for a circuit with {} Input(s)", number + 1, ins.len(), inputs)));
+        }
+        let outs = match outs.map(bits) {
+            Some(Some(outs)) if outs.len() == outputs => Some(outs),
+            Some(Some(outs)) => return Err(invalid_data(format!("Line {}: there are {} Output state(s) for a circuit with {} Output(s)", number + 1, outs.len(), outputs))),
+            Some(Option::None) => return Err(invalid_data(format!("Line {}: the Outputs should be made of 0s and 1s", number + 1))),
+            Option::None => Option::None,
+        };
+        vectors.push(Vector { inputs: ins, outputs: outs });
+    }
+    Ok(vectors)
+}
+
+// Saves vectors with a header naming the Inputs and Outputs. It is a comment, as names that are
+// only ids would read as states.
+pub fn write_vectors(fp: &str, inputs: &[String], outputs: &[String], vectors: &[Vector]) -> std::io::Result<()> {
+    let mut file = File::create(fp)?;
+    writeln!(file, "{}", header(inputs, outputs))?;
+    for vector in vectors {
+        match &vector.outputs {
+            Some(outs) => writeln!(file, "{} | {}", write_bits(&vector.inputs), write_bits(outs))?,
+            Option::None => writeln!(file, "{}", write_bits(&vector.inputs))?,
+        }
+    }
+    Ok(())
+}
+
+pub fn header(inputs: &[String], outputs: &[String]) -> String {
+    format!("# {} | {}", inputs.join(" "), outputs.join(" "))
+}
+
+fn write_bits(states: &[bool]) -> String {
+    states.iter().map(|s| if *s {'1'} else {'0'}).collect()
+}
+
+fn bits(text: &str) -> Option<Vec<bool>> {
+    text.chars().filter(|c| !c.is_whitespace()).map(|c| match c {
+        '0' => Some(false),
+        '1' => Some(true),
+        _ => Option::None,
+    }).collect()
+}
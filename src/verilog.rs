@@ -0,0 +1,873 @@
+use std::{fs::File, io::Write};
+
+use crate::{invalid_data, Circuit, Gate, GateType, Port, IC};
+
+const KEYWORDS: &[&str] = &[
+    "always", "and", "assign", "begin", "buf", "bufif1", "case", "default", "else", "end", "endcase", "endmodule",
+    "for", "function", "if", "initial", "inout", "input", "integer", "module", "nand", "negedge", "nor",
+    "not", "or", "output", "parameter", "posedge", "reg", "supply0", "supply1", "tri", "tri0", "tri1", "wire",
+    "xnor", "xor",
+];
+
+// Inputs and Outputs become the ports of the module, named from their labels, and every other
+// gate drives a wire called `w` and its id. ICs become instances of their own module, which is
+// written after the module using it.
+pub fn export_verilog(circuit: &Circuit, fp: &str) -> std::io::Result<()> {
+    let mut buf: Vec<u8> = Vec::new();
+    write_module(circuit, &mut Vec::new(), &mut Vec::new(), &mut buf)?;
+    let mut file = File::create(fp)?;
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+fn write_module(circuit: &Circuit, names: &mut Vec<(u32, String)>, written: &mut Vec<u32>, buf: &mut Vec<u8>) -> std::io::Result<()> {
+    written.push(circuit.id);
+    let module = module_name(circuit, names);
+    let nets = net_names(circuit);
+    let net = |id: u32| nets.iter().find(|n| n.0 == id).map(|n| n.1.clone()).unwrap_or(String::from("1'b0"));
+
+    let inputs: Vec<String> = circuit.gates.iter().filter(|g| g.gate_type == GateType::Input).map(|g| net(g.id)).collect();
+    let outputs: Vec<String> = circuit.gates.iter().filter(|g| g.gate_type == GateType::Output).map(|g| net(g.id)).collect();
+    writeln!(buf, "module {} ({});", module, [inputs.clone(), outputs.clone()].concat().join(", "))?;
+    for port in inputs.iter() {
+        writeln!(buf, "    input {};", port)?;
+    }
+    for port in outputs.iter() {
+        writeln!(buf, "    output {};", port)?;
+    }
+    for gate in circuit.gates.iter() {
+        if ![GateType::Input, GateType::Output].contains(&gate.gate_type) {
+            // Nets with more than one driver are tri, pulled up or down by tri1 or tri0
+            let kind = match (&gate.gate_type, gate.pull) {
+                (GateType::Wire, Some(true)) => "tri1",
+                (GateType::Wire, Some(false)) => "tri0",
+                (GateType::Wire, Option::None) => "tri",
+                _ => "wire",
+            };
+            match &gate.label {
+                Some(label) => writeln!(buf, "    {} {}; // {}", kind, net(gate.id), label)?,
+                None => writeln!(buf, "    {} {};", kind, net(gate.id))?,
+            }
+        }
+    }
+    writeln!(buf)?;
+
+    // Gates on the outside of an IC output are driven by the IC instead
+    let driven_by_ics: Vec<u32> = circuit.intergrated_circuits.iter().flat_map(|ic| ic.outputs.iter().map(|o| o[0].1)).collect();
+
+    for gate in circuit.gates.iter() {
+        if gate.gate_type == GateType::Input || driven_by_ics.contains(&gate.id) {
+            continue;
+        }
+        let mut sources: Vec<String> = gate.inputs.iter().map(|i| net(i.1)).collect();
+        if gate.gate_type == GateType::Wire {
+            for source in sources {
+                writeln!(buf, "    assign {} = {};", net(gate.id), source)?;
+            }
+            continue;
+        }
+        if sources.is_empty() {
+            writeln!(buf, "    assign {} = 1'b0;", net(gate.id))?;
+            continue;
+        }
+        let primitive = match gate.gate_type {
+            GateType::Output => {
+                writeln!(buf, "    assign {} = {};", net(gate.id), sources[0])?;
+                continue;
+            }
+            GateType::Input => continue,
+            GateType::Buffer => {sources.truncate(1); "buf"}
+            GateType::Not => {sources.truncate(1); "not"}
+            GateType::And => "and",
+            GateType::Or => "or",
+            GateType::Nand => "nand",
+            GateType::Nor => "nor",
+            // Only the first two inputs of an Xor are used when simulating
+            GateType::Xor => {sources.truncate(2); "xor"}
+            GateType::Nxor => {sources.truncate(2); "xnor"}
+            GateType::Tristate if sources.len() == 1 => "buf",
+            GateType::Tristate => {sources.truncate(2); "bufif1"}
+            GateType::Wire => continue,
+        };
+        writeln!(buf, "    {} ({}, {});", primitive, net(gate.id), sources.join(", "))?;
+    }
+
+    for (i, ic) in circuit.intergrated_circuits.iter().enumerate() {
+        let inner_nets = net_names(&ic.circuit);
+        let mut connections = Vec::new();
+        for port in ic.inputs.iter().chain(ic.outputs.iter()) {
+            let Some(inner) = ic.circuit.gates.iter().find(|g| g.id == port[1].1) else {
+                return Err(invalid_data(format!("IC {} is connected to gate {} which it does not have", i, port[1].1)));
+            };
+            if ![GateType::Input, GateType::Output].contains(&inner.gate_type) {
+                return Err(invalid_data(format!("IC {} is connected to gate {} which is not an Input or Output", i, inner.id)));
+            }
+            let inner_net = inner_nets.iter().find(|n| n.0 == inner.id).map(|n| n.1.clone()).unwrap_or_default();
+            connections.push(format!(".{}({})", inner_net, net(port[0].1)));
+        }
+        writeln!(buf, "    {} ic{} ({});", module_name(&ic.circuit, names), i, connections.join(", "))?;
+    }
+    writeln!(buf, "endmodule")?;
+
+    for ic in circuit.intergrated_circuits.iter() {
+        if !written.contains(&ic.circuit.id) {
+            writeln!(buf)?;
+            write_module(&ic.circuit, names, written, buf)?;
+        }
+    }
+    Ok(())
+}
+
+// Circuits keep the first module name they are given, and different circuits with the same
+// name get their id on the end
+pub(crate) fn module_name(circuit: &Circuit, names: &mut Vec<(u32, String)>) -> String {
+    if let Some((_, name)) = names.iter().find(|n| n.0 == circuit.id) {
+        return name.clone();
+    }
+    let mut name = match &circuit.name {
+        Some(n) => identifier(n),
+        None => format!("circuit{}", circuit.id),
+    };
+    if names.iter().any(|n| n.1 == name) {
+        name = format!("{}_{}", name, circuit.id);
+    }
+    names.push((circuit.id, name.clone()));
+    name
+}
+
+pub(crate) fn net_names(circuit: &Circuit) -> Vec<(u32, String)> {
+    let mut nets: Vec<(u32, String)> = circuit.gates.iter()
+        .filter(|g| ![GateType::Input, GateType::Output].contains(&g.gate_type))
+        .map(|g| (g.id, format!("w{}", g.id)))
+        .collect();
+    for gate in circuit.gates.iter() {
+        let prefix = match gate.gate_type {
+            GateType::Input => "in",
+            GateType::Output => "out",
+            _ => continue,
+        };
+        let mut name = match &gate.label {
+            Some(label) => identifier(label),
+            None => format!("{}{}", prefix, gate.id),
+        };
+        if nets.iter().any(|n| n.1 == name) {
+            name = format!("{}_{}", name, gate.id);
+        }
+        nets.push((gate.id, name));
+    }
+    nets
+}
+
+fn identifier(text: &str) -> String {
+    let mut name: String = text.trim().chars().map(|c| if c.is_ascii_alphanumeric() {c} else {'_'}).collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || KEYWORDS.contains(&name.as_str()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+// Importing reads the gate level part of Verilog: ports, wires and tri nets, the gate primitives
+// and bufif1, assigns made of `~ & | ^` and instances of other modules. Every module becomes a circuit in the catalogue
+// and the modules it uses become its ICs, so they are added after the modules they use.
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Number(String),
+    Symbol(String),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Expr {
+    Net(String),
+    Constant(bool),
+    Gate(GateType, Vec<Expr>),
+}
+
+pub(crate) struct Instance {
+    pub(crate) module: String,
+    pub(crate) name: String,
+    pub(crate) connections: Vec<(Option<String>, Option<Expr>)>, // Port name, or None when connected by position
+    pub(crate) line: usize,
+}
+
+pub(crate) struct Module {
+    pub(crate) name: String,
+    pub(crate) ports: Vec<String>,
+    pub(crate) inputs: Vec<String>,
+    pub(crate) outputs: Vec<String>,
+    pub(crate) drivers: Vec<(String, Expr, usize)>, // Net; What drives it; Line
+    pub(crate) tris: Vec<(String, Option<bool>, usize)>, // Net; Pulled up or down; Line
+    pub(crate) instances: Vec<Instance>,
+    pub(crate) line: usize,
+}
+
+pub fn import_verilog(fp: &str, id: &mut u32, catalogue: &mut Vec<Circuit>) -> std::io::Result<Vec<u32>> {
+    let mut file = File::open(fp)?;
+    let mut source = String::new();
+    std::io::Read::read_to_string(&mut file, &mut source)?;
+
+    let mut parser = Parser { tokens: lex(&source)?, position: 0 };
+    let mut modules = Vec::new();
+    while parser.peek().is_some() {
+        modules.push(parser.module()?);
+    }
+    if modules.is_empty() {
+        return Err(invalid_data(String::from("There are no modules in the file")));
+    }
+
+    let circuits = build_modules(&modules, id, catalogue)?;
+    let ids = circuits.iter().map(|c| c.id).collect();
+    catalogue.extend(circuits);
+    Ok(ids)
+}
+
+// Makes a circuit of each module, after the modules it uses. Nothing is kept and `id` is left
+// alone if any of them can't be made.
+pub(crate) fn build_modules(modules: &[Module], id: &mut u32, catalogue: &[Circuit]) -> std::io::Result<Vec<Circuit>> {
+    let mut built: Vec<(Circuit, Vec<String>)> = Vec::new(); // Circuit; Port order
+    let mut next_id = *id;
+    for i in 0..modules.len() {
+        build(i, modules, &mut built, &mut Vec::new(), &mut next_id, catalogue)?;
+    }
+    *id = next_id;
+    Ok(built.into_iter().map(|b| b.0).collect())
+}
+
+fn build(index: usize, modules: &[Module], built: &mut Vec<(Circuit, Vec<String>)>, path: &mut Vec<usize>, id: &mut u32, catalogue: &[Circuit]) -> std::io::Result<()> {
+    let module = &modules[index];
+    if built.iter().any(|b| b.0.name.as_ref() == Some(&module.name)) {
+        return Ok(());
+    }
+    if path.contains(&index) {
+        return Err(invalid_data(format!("Module {} ends up using itself", module.name)));
+    }
+    path.push(index);
+    for instance in module.instances.iter() {
+        if let Some(dependency) = modules.iter().position(|m| m.name == instance.module) {
+            build(dependency, modules, built, path, id, catalogue)?;
+        }
+    }
+    path.pop();
+
+    *id += 1;
+    let circuit = lower(module, *id, built, catalogue)?;
+    built.push((circuit, module.ports.clone()));
+    Ok(())
+}
+
+// Every driven net gets one gate, made before the gates inside its expression so that the
+// wires can be used before they are driven
+fn lower(module: &Module, id: u32, built: &[(Circuit, Vec<String>)], catalogue: &[Circuit]) -> std::io::Result<Circuit> {
+    let mut gates: Vec<(GateType, Vec<u32>, Option<String>)> = Vec::new();
+    let mut nets: Vec<(String, u32)> = Vec::new();
+    let mut ic_inputs: Vec<(usize, u32, Expr)> = Vec::new(); // Instance; Internal id; Connection
+    let mut ics: Vec<(Circuit, Vec<Port>, Vec<Port>)> = Vec::new();
+
+    for port in module.inputs.iter() {
+        nets.push((port.clone(), gates.len() as u32));
+        gates.push((GateType::Input, Vec::new(), Some(port.clone())));
+    }
+
+    fn drive(net: &str, line: usize, nets: &mut Vec<(String, u32)>, gate: u32) -> std::io::Result<()> {
+        if nets.iter().any(|n| n.0 == net) {
+            return Err(invalid_data(format!("Line {}: {} is driven more than once", line, net)));
+        }
+        nets.push((net.to_string(), gate));
+        Ok(())
+    }
+
+    for (i, instance) in module.instances.iter().enumerate() {
+        let (circuit, ports) = if let Some((circuit, ports)) = built.iter().find(|b| b.0.name.as_ref() == Some(&instance.module)) {
+            (circuit.clone(), ports.clone())
+        }
+        else if let Some(circuit) = catalogue.iter().rev().find(|c| c.name.as_ref() == Some(&instance.module)) {
+            let ports = circuit.gates.iter()
+                .filter(|g| g.gate_type == GateType::Input).chain(circuit.gates.iter().filter(|g| g.gate_type == GateType::Output))
+                .filter_map(|g| g.label.clone()).collect();
+            (circuit.clone(), ports)
+        }
+        else {
+            return Err(invalid_data(format!("Line {}: there is no module called {}", instance.line, instance.module)));
+        };
+
+        let mut outputs = Vec::new();
+        for (position, (port, connection)) in instance.connections.iter().enumerate() {
+            let Some(port) = port.clone().or(ports.get(position).cloned()) else {
+                return Err(invalid_data(format!("Line {}: {} has too many connections", instance.line, instance.name)));
+            };
+            let Some(internal) = circuit.gates.iter().find(|g| g.label.as_ref() == Some(&port) && [GateType::Input, GateType::Output].contains(&g.gate_type)) else {
+                return Err(invalid_data(format!("Line {}: {} has no port called {}", instance.line, instance.module, port)));
+            };
+            let Some(connection) = connection else { continue };
+
+            if internal.gate_type == GateType::Input {
+                ic_inputs.push((i, internal.id, connection.clone()));
+                continue;
+            }
+            let Expr::Net(net) = connection else {
+                return Err(invalid_data(format!("Line {}: output {} of {} must be connected to a wire", instance.line, port, instance.name)));
+            };
+            let external = gates.len() as u32;
+            gates.push((GateType::Buffer, Vec::new(), Some(format!("{}.{}", instance.name, port))));
+            drive(net, instance.line, &mut nets, external)?;
+            outputs.push([(external as usize, external), (circuit.index_of(internal.id, 0), internal.id)]);
+        }
+        ics.push((circuit, Vec::new(), outputs));
+    }
+
+    // A tri net is a Wire that everything driving the net goes into
+    let mut wires: Vec<(&String, u32, Option<bool>)> = Vec::new();
+    for (net, pull, line) in module.tris.iter() {
+        let wire = gates.len() as u32;
+        gates.push((GateType::Wire, Vec::new(), Some(net.clone())));
+        drive(net, *line, &mut nets, wire)?;
+        wires.push((net, wire, *pull));
+    }
+
+    let mut roots = Vec::new();
+    let mut wire_drivers = Vec::new();
+    for (net, expr, line) in module.drivers.iter() {
+        if let Some(wire) = wires.iter().find(|w| w.0 == net) {
+            wire_drivers.push((wire.1, expr));
+            continue;
+        }
+        let root = gates.len() as u32;
+        gates.push((GateType::Buffer, Vec::new(), Some(net.clone())));
+        drive(net, *line, &mut nets, root)?;
+        roots.push((root, expr));
+    }
+
+    let mut lowering = Lowering { gates, nets, constants: [Option::None; 2] };
+    for (root, expr) in roots {
+        let (gate_type, inputs) = match expr {
+            Expr::Gate(gate_type, args) => (gate_type.clone(), args.iter().map(|a| lowering.expr(a)).collect()),
+            other => (GateType::Buffer, vec![lowering.expr(other)]),
+        };
+        lowering.gates[root as usize].0 = gate_type;
+        lowering.gates[root as usize].1 = inputs;
+    }
+    for (wire, expr) in wire_drivers {
+        let input = lowering.expr(expr);
+        lowering.gates[wire as usize].1.push(input);
+    }
+
+    for (i, internal, connection) in ic_inputs {
+        let source = lowering.expr(&connection);
+        let external = lowering.gates.len() as u32;
+        let label = format!("{}.{}", module.instances[i].name, ics[i].0.gates.iter().find(|g| g.id == internal).and_then(|g| g.label.clone()).unwrap_or_default());
+        lowering.gates.push((GateType::Buffer, vec![source], Some(label)));
+        let internal_index = ics[i].0.index_of(internal, 0);
+        ics[i].1.push([(external as usize, external), (internal_index, internal)]);
+    }
+
+    for port in module.outputs.iter() {
+        let source = lowering.expr(&Expr::Net(port.clone()));
+        lowering.gates.push((GateType::Output, vec![source], Some(port.clone())));
+    }
+
+    let mut circuit = Circuit::new(id);
+    circuit.name = Some(module.name.clone());
+    for (gate_type, inputs, label) in lowering.gates {
+        // Ids are the same as indexes here so they are also where to find each input
+        let inputs = inputs.into_iter().map(|i| (i as usize, i)).collect();
+        circuit.gates.push(Gate::new(gate_type, circuit.id_counter, inputs, label));
+        circuit.id_counter += 1;
+    }
+    for (_, wire, pull) in wires {
+        circuit.gates[wire as usize].pull = pull;
+    }
+    for (ic, inputs, outputs) in ics {
+        circuit.intergrated_circuits.push(IC::new(ic, inputs, outputs));
+    }
+    Ok(circuit)
+}
+
+struct Lowering {
+    gates: Vec<(GateType, Vec<u32>, Option<String>)>,
+    nets: Vec<(String, u32)>,
+    constants: [Option<u32>; 2],
+}
+
+impl Lowering {
+    fn expr(&mut self, expr: &Expr) -> u32 {
+        match expr {
+            Expr::Net(net) => {
+                if let Some((_, id)) = self.nets.iter().find(|n| &n.0 == net) {
+                    return *id;
+                }
+                // Nothing drives this wire so it stays off
+                let id = self.gates.len() as u32;
+                self.gates.push((GateType::Buffer, Vec::new(), Some(net.clone())));
+                self.nets.push((net.clone(), id));
+                id
+            }
+            Expr::Constant(value) => {
+                if let Some(id) = self.constants[*value as usize] {
+                    return id;
+                }
+                // A Buffer with nothing in is always off and a Not of that is always on
+                if *value {
+                    let zero = self.expr(&Expr::Constant(false));
+                    self.gates.push((GateType::Not, vec![zero], Some(String::from("1'b1"))));
+                } else {
+                    self.gates.push((GateType::Buffer, Vec::new(), Some(String::from("1'b0"))));
+                }
+                let id = self.gates.len() as u32 - 1;
+                self.constants[*value as usize] = Some(id);
+                id
+            }
+            Expr::Gate(gate_type, args) => {
+                let inputs = args.iter().map(|a| self.expr(a)).collect();
+                self.gates.push((gate_type.clone(), inputs, Option::None));
+                self.gates.len() as u32 - 1
+            }
+        }
+    }
+}
+
+fn lex(source: &str) -> std::io::Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied().unwrap_or(' ');
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        }
+        else if c.is_whitespace() {
+            i += 1;
+        }
+        else if c == '/' && next == '/' {
+            while i < chars.len() && chars[i] != '\n' {i += 1}
+        }
+        else if (c == '/' && next == '*') || (c == '(' && next == '*' && chars.get(i + 2) != Some(&')')) {
+            // Block comments and attributes like `(* src = "..." *)` are both skipped
+            let end = if c == '/' {'/'} else {')'};
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&end)) {
+                if chars[i] == '\n' {line += 1}
+                i += 1;
+            }
+            i += 2;
+        }
+        else if c == '`' {
+            // Compiler directives like `timescale take up the rest of the line
+            while i < chars.len() && chars[i] != '\n' {i += 1}
+        }
+        else if c.is_ascii_alphabetic() || c == '_' || c == '\\' {
+            let start = i;
+            if c == '\\' {
+                while i < chars.len() && !chars[i].is_whitespace() {i += 1}
+                tokens.push((Token::Word(chars[start+1..i].iter().collect()), line));
+            } else {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$') {i += 1}
+                tokens.push((Token::Word(chars[start..i].iter().collect()), line));
+            }
+        }
+        else if c.is_ascii_digit() || c == '\'' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '\'' || chars[i] == '_') {i += 1}
+            tokens.push((Token::Number(chars[start..i].iter().collect()), line));
+        }
+        else if (c == '~' && next == '^') || (c == '^' && next == '~') {
+            tokens.push((Token::Symbol(String::from("~^")), line));
+            i += 2;
+        }
+        else if "()[]{},;.=~&|^!:#?@*+-<>".contains(c) {
+            tokens.push((Token::Symbol(c.to_string()), line));
+            i += 1;
+        }
+        else {
+            return Err(invalid_data(format!("Line {}: unexpected \"{}\"", line, c)));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|t| &t.0)
+    }
+    fn line(&self) -> usize {
+        self.tokens.get(self.position).or(self.tokens.last()).map(|t| t.1).unwrap_or(0)
+    }
+    fn next(&mut self) -> std::io::Result<Token> {
+        let token = self.tokens.get(self.position).map(|t| t.0.clone());
+        self.position += 1;
+        token.ok_or_else(|| invalid_data(String::from("The file ends in the middle of a module")))
+    }
+    fn error<T>(&self, message: &str) -> std::io::Result<T> {
+        Err(invalid_data(format!("Line {}: {}", self.line(), message)))
+    }
+    fn is_symbol(&self, symbol: &str) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol.to_string()))
+    }
+    fn is_word(&self, word: &str) -> bool {
+        self.peek() == Some(&Token::Word(word.to_string()))
+    }
+    fn symbol(&mut self, symbol: &str) -> std::io::Result<()> {
+        if self.is_symbol(symbol) {
+            self.position += 1;
+            return Ok(());
+        }
+        self.error(&format!("expected \"{}\"", symbol))
+    }
+    fn word(&mut self) -> std::io::Result<String> {
+        match self.peek() {
+            Some(Token::Word(w)) if !KEYWORDS.contains(&w.as_str()) => {
+                let w = w.clone();
+                self.position += 1;
+                Ok(w)
+            }
+            Some(Token::Symbol(s)) if s == "[" => self.error("buses like [3:0] are not supported, split them into single wires"),
+            _ => self.error("expected a name"),
+        }
+    }
+    // Skips `#1` and `#(1, 2)` delays, which don't mean anything to simlo
+    fn skip_delay(&mut self) -> std::io::Result<()> {
+        if self.is_symbol("#") {
+            self.position += 1;
+            if self.is_symbol("(") {
+                while !self.is_symbol(")") {self.next()?;}
+            }
+            self.next()?;
+        }
+        Ok(())
+    }
+
+    fn module(&mut self) -> std::io::Result<Module> {
+        let line = self.line();
+        if !self.is_word("module") {
+            return self.error("expected \"module\"");
+        }
+        self.position += 1;
+        let mut module = Module { name: self.word()?, ports: Vec::new(), inputs: Vec::new(), outputs: Vec::new(), drivers: Vec::new(), tris: Vec::new(), instances: Vec::new(), line };
+        if self.is_symbol("#") {
+            return self.error("module parameters are not supported");
+        }
+
+        if self.is_symbol("(") {
+            self.position += 1;
+            let mut direction: Option<String> = Option::None;
+            while !self.is_symbol(")") {
+                if self.is_word("input") || self.is_word("output") || self.is_word("inout") {
+                    direction = Some(self.declaration_direction()?);
+                }
+                let port = self.word()?;
+                match direction.as_deref() {
+                    Some("input") => module.inputs.push(port.clone()),
+                    Some(_) => module.outputs.push(port.clone()),
+                    None => {}
+                }
+                module.ports.push(port);
+                if !self.is_symbol(")") {
+                    self.symbol(",")?;
+                }
+            }
+            self.position += 1;
+        }
+        self.symbol(";")?;
+
+        loop {
+            let line = self.line();
+            let Token::Word(word) = self.next()? else {
+                return self.error("expected a declaration, assign, gate or instance");
+            };
+            match word.as_str() {
+                "endmodule" => break,
+                "input" | "output" | "inout" => {
+                    self.position -= 1;
+                    let direction = self.declaration_direction()?;
+                    for port in self.names()? {
+                        if !module.ports.contains(&port) {
+                            return Err(invalid_data(format!("Line {}: {} is not in the port list of {}", line, port, module.name)));
+                        }
+                        if direction == "input" {module.inputs.push(port)} else {module.outputs.push(port)}
+                    }
+                }
+                "wire" => {
+                    loop {
+                        let net = self.word()?;
+                        if self.is_symbol("=") {
+                            self.position += 1;
+                            let expr = self.expr()?;
+                            module.drivers.push((net, expr, line));
+                        }
+                        if self.is_symbol(";") {break}
+                        self.symbol(",")?;
+                    }
+                    self.symbol(";")?;
+                }
+                "assign" => {
+                    self.skip_delay()?;
+                    loop {
+                        let net = self.word()?;
+                        self.symbol("=")?;
+                        let expr = self.expr()?;
+                        module.drivers.push((net, expr, line));
+                        if self.is_symbol(";") {break}
+                        self.symbol(",")?;
+                    }
+                    self.symbol(";")?;
+                }
+                "tri" | "tri0" | "tri1" => {
+                    let pull = match word.as_str() { "tri1" => Some(true), "tri0" => Some(false), _ => Option::None };
+                    for net in self.names()? {
+                        module.tris.push((net, pull, line));
+                    }
+                }
+                "and" | "or" | "nand" | "nor" | "xor" | "xnor" | "not" | "buf" | "bufif1" => {
+                    self.skip_delay()?;
+                    loop {
+                        if !self.is_symbol("(") {
+                            self.word()?;
+                        }
+                        self.symbol("(")?;
+                        let mut terminals = vec![self.expr()?];
+                        while self.is_symbol(",") {
+                            self.position += 1;
+                            terminals.push(self.expr()?);
+                        }
+                        self.symbol(")")?;
+                        if terminals.len() < 2 {
+                            return Err(invalid_data(format!("Line {}: {} needs an output and an input", line, word)));
+                        }
+                        for (net, expr) in primitive(&word, terminals, line)? {
+                            module.drivers.push((net, expr, line));
+                        }
+                        if self.is_symbol(";") {break}
+                        self.symbol(",")?;
+                    }
+                    self.symbol(";")?;
+                }
+                "reg" | "always" | "initial" | "function" | "task" | "generate" | "parameter" | "localparam" | "integer" | "supply0" | "supply1" => {
+                    return Err(invalid_data(format!("Line {}: \"{}\" is not part of the gate level Verilog simlo reads", line, word)));
+                }
+                _ => {
+                    if self.is_symbol("#") {
+                        return self.error("module parameters are not supported");
+                    }
+                    loop {
+                        let name = self.word()?;
+                        self.symbol("(")?;
+                        let mut connections = Vec::new();
+                        while !self.is_symbol(")") {
+                            if self.is_symbol(".") {
+                                self.position += 1;
+                                let port = self.word()?;
+                                self.symbol("(")?;
+                                let expr = if self.is_symbol(")") {Option::None} else {Some(self.expr()?)};
+                                self.symbol(")")?;
+                                connections.push((Some(port), expr));
+                            } else {
+                                connections.push((Option::None, Some(self.expr()?)));
+                            }
+                            if !self.is_symbol(")") {
+                                self.symbol(",")?;
+                            }
+                        }
+                        self.position += 1;
+                        module.instances.push(Instance { module: word.clone(), name, connections, line });
+                        if self.is_symbol(";") {break}
+                        self.symbol(",")?;
+                    }
+                    self.symbol(";")?;
+                }
+            }
+        }
+
+        for port in module.ports.iter() {
+            if !module.inputs.contains(port) && !module.outputs.contains(port) {
+                return Err(invalid_data(format!("Line {}: port {} of {} is never declared as an input or output", module.line, port, module.name)));
+            }
+        }
+        Ok(module)
+    }
+    fn declaration_direction(&mut self) -> std::io::Result<String> {
+        let Token::Word(direction) = self.next()? else { unreachable!() };
+        if direction == "inout" {
+            return self.error("inout ports are not supported");
+        }
+        if self.is_word("wire") {
+            self.position += 1;
+        }
+        if self.is_word("reg") {
+            return self.error("reg ports are not supported");
+        }
+        Ok(direction)
+    }
+    fn names(&mut self) -> std::io::Result<Vec<String>> {
+        let mut names = vec![self.word()?];
+        while self.is_symbol(",") {
+            self.position += 1;
+            names.push(self.word()?);
+        }
+        self.symbol(";")?;
+        Ok(names)
+    }
+
+    // Or is the loosest, then Xor, then And, then the unary operators
+    fn expr(&mut self) -> std::io::Result<Expr> {
+        let mut terms = vec![self.xor_expr()?];
+        while self.is_symbol("|") {
+            self.position += 1;
+            terms.push(self.xor_expr()?);
+        }
+        Ok(if terms.len() == 1 {terms.remove(0)} else {Expr::Gate(GateType::Or, terms)})
+    }
+    fn xor_expr(&mut self) -> std::io::Result<Expr> {
+        let mut expr = self.and_expr()?;
+        while self.is_symbol("^") || self.is_symbol("~^") {
+            let gate_type = if self.is_symbol("^") {GateType::Xor} else {GateType::Nxor};
+            self.position += 1;
+            expr = Expr::Gate(gate_type, vec![expr, self.and_expr()?]);
+        }
+        Ok(expr)
+    }
+    fn and_expr(&mut self) -> std::io::Result<Expr> {
+        let mut terms = vec![self.unary_expr()?];
+        while self.is_symbol("&") {
+            self.position += 1;
+            terms.push(self.unary_expr()?);
+        }
+        Ok(if terms.len() == 1 {terms.remove(0)} else {Expr::Gate(GateType::And, terms)})
+    }
+    fn unary_expr(&mut self) -> std::io::Result<Expr> {
+        if self.is_symbol("~") || self.is_symbol("!") {
+            self.position += 1;
+            return Ok(Expr::Gate(GateType::Not, vec![self.unary_expr()?]));
+        }
+        if self.is_symbol("(") {
+            self.position += 1;
+            let expr = self.expr()?;
+            self.symbol(")")?;
+            return Ok(expr);
+        }
+        if let Some(Token::Number(number)) = self.peek().cloned() {
+            self.position += 1;
+            let (width, value) = number.split_once('\'').map(|(w, v)| (w, &v[1.min(v.len())..])).unwrap_or(("1", number.as_str()));
+            return match (width, value) {
+                ("1" | "", "0") => Ok(Expr::Constant(false)),
+                ("1" | "", "1") => Ok(Expr::Constant(true)),
+                _ => self.error(&format!("{} is not a single bit 0 or 1", number)),
+            };
+        }
+        let net = self.word()?;
+        if self.is_symbol("[") {
+            return self.error("buses like [3:0] are not supported, split them into single wires");
+        }
+        if self.is_symbol("?") {
+            return self.error("the ? operator is not supported, use gates instead");
+        }
+        Ok(Expr::Net(net))
+    }
+}
+
+// Gates that take many inputs have their output first, and `not` and `buf` can have many outputs
+// with the input last. Xor gates only look at two inputs in simlo so longer ones are chained.
+fn primitive(word: &str, mut terminals: Vec<Expr>, line: usize) -> std::io::Result<Vec<(String, Expr)>> {
+    let net = |expr: &Expr| match expr {
+        Expr::Net(net) => Ok(net.clone()),
+        _ => Err(invalid_data(format!("Line {}: the outputs of {} must be wires", line, word))),
+    };
+    if word == "not" || word == "buf" {
+        let input = terminals.pop().unwrap_or(Expr::Constant(false));
+        let gate_type = if word == "not" {GateType::Not} else {GateType::Buffer};
+        return terminals.iter().map(|t| Ok((net(t)?, Expr::Gate(gate_type.clone(), vec![input.clone()])))).collect();
+    }
+
+    let output = net(&terminals.remove(0))?;
+    let expr = match word {
+        "and" => Expr::Gate(GateType::And, terminals),
+        "or" => Expr::Gate(GateType::Or, terminals),
+        "nand" => Expr::Gate(GateType::Nand, terminals),
+        "nor" => Expr::Gate(GateType::Nor, terminals),
+        // The data comes before what enables it, the same as the inputs of a Tristate
+        "bufif1" => Expr::Gate(GateType::Tristate, terminals),
+        _ => {
+            let mut inputs = terminals.into_iter();
+            let first = inputs.next().unwrap_or(Expr::Constant(false));
+            let mut chain = match inputs.next() {
+                Some(second) => Expr::Gate(GateType::Xor, vec![first, second]),
+                None => Expr::Gate(GateType::Buffer, vec![first]),
+            };
+            for input in inputs {
+                chain = Expr::Gate(GateType::Xor, vec![chain, input]);
+            }
+            if word == "xnor" {
+                chain = match chain {
+                    Expr::Gate(GateType::Xor, args) => Expr::Gate(GateType::Nxor, args),
+                    other => Expr::Gate(GateType::Not, vec![other]),
+                };
+            }
+            chain
+        }
+    };
+    Ok(vec![(output, expr)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{stepped_outputs, temp_path, wide};
+
+    fn imported(source: &str, name: &str) -> std::io::Result<Vec<Circuit>> {
+        let fp = temp_path(name);
+        std::fs::write(&fp, source)?;
+        let mut catalogue = Vec::new();
+        let result = import_verilog(&fp, &mut 100, &mut catalogue);
+        std::fs::remove_file(&fp)?;
+        result.map(|_| catalogue)
+    }
+
+    #[test]
+    fn export_then_import_steps_the_same() {
+        // Xors with more than two inputs only use the first two, as stepping does
+        let mut circuit = wide();
+        circuit.add_component(GateType::Xor, vec![0, 1, 2], Option::None); // 27
+        circuit.add_component(GateType::Nxor, vec![3, 4, 5], Option::None); // 28
+        circuit.add_component(GateType::Output, vec![27], Option::None);
+        circuit.add_component(GateType::Output, vec![28], Option::None);
+        circuit.gates.iter_mut().find(|g| g.id == 20).unwrap().pull = Some(true);
+
+        let fp = temp_path("round_trip.v");
+        export_verilog(&circuit, &fp).unwrap();
+        let mut catalogue = Vec::new();
+        import_verilog(&fp, &mut 100, &mut catalogue).unwrap();
+        std::fs::remove_file(&fp).unwrap();
+
+        let names: Vec<Option<String>> = catalogue.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, [Some(String::from("half_adder")), Some(String::from("circuit4"))]);
+        let top = &catalogue[1];
+        assert!(top.gates.iter().any(|g| g.gate_type == GateType::Tristate));
+        assert!(top.gates.iter().any(|g| g.gate_type == GateType::Wire && g.pull == Some(true) && g.inputs.len() == 2));
+        for vector in 0..256 {
+            assert_eq!(stepped_outputs(top, vector), stepped_outputs(&circuit, vector), "vector {:08b}", vector);
+        }
+    }
+
+    #[test]
+    fn malformed_verilog_is_an_error() {
+        for (source, error) in [
+            ("", "There are no modules in the file"),
+            ("module m (a, y);\n    input a;\n    output y;\n    assign y = a $ a;\nendmodule\n", "Line 4: unexpected \"$\""),
+            ("module m (a, y);\n    input a;\n    output y;\n    reg r;\nendmodule\n", "Line 4: \"reg\" is not part of the gate level Verilog simlo reads"),
+            ("module m (a, y);\n    input a;\n    output y;\n    assign y = a;\n    assign y = ~a;\nendmodule\n", "Line 5: y is driven more than once"),
+            ("module m (a, y);\n    input a;\n    output y;\n    other o (a, y);\nendmodule\n", "Line 4: there is no module called other"),
+            ("module m (a, y);\n    input a;\nendmodule\n", "Line 1: port y of m is never declared as an input or output"),
+            ("module m (a, y);\n    input a;\n    output y;\n    m inner (a, y);\nendmodule\n", "Module m ends up using itself"),
+        ] {
+            let result = imported(source, "malformed.v");
+            assert_eq!(result.err().map(|e| e.to_string()), Some(String::from(error)), "{:?}", source);
+        }
+    }
+}